@@ -0,0 +1,70 @@
+// benches/micro.rs
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion};
+use infinity_chess_bot::board::{Board, Coordinate};
+use infinity_chess_bot::notation::parse_fen;
+use infinity_chess_bot::r#move::{MoveGen, MoveList};
+use infinity_chess_bot::tools::legal_moves;
+
+// Timings of the building blocks of the search, one position at a time, so a change to move
+// generation or evaluation shows up on its own instead of only in the full-search bench.
+// Run as `cargo bench --features microbench --bench micro`.
+const POSITIONS: [(&str, &str); 3] = [
+    ("dense", "r1,8;n2,8;b3,8;q4,8;k5,8;b6,8;n7,8;r8,8;p1,7;p2,7;p3,7;p4,7;p5,7;p6,7;p7,7;p8,7;P1,2;P2,2;P3,2;P4,2;P5,2;P6,2;P7,2;P8,2;R1,1;N2,1;B3,1;Q4,1;K5,1;B6,1;N7,1;R8,1 w KQkq -"),
+    ("sparse", "k20,20;P0,3;p-4,0;R0,0;n7,0;K-5,-5 w - -"),
+    ("far", "p1e12,1e12;K-5,0;B0,0;P-2,-2;k20,-3;q-1e30,7 w - -"),
+];
+
+fn positions() -> impl Iterator<Item = (&'static str, Board)> {
+    POSITIONS.iter().map(|(name, fen)| (*name, parse_fen(fen).unwrap()))
+}
+
+fn movegen(c: &mut Criterion) {
+    let mut group = c.benchmark_group("movegen");
+    for (name, board) in positions() {
+        group.bench_function(name, |b| b.iter(|| {
+            let mut move_list = MoveList::new();
+            MoveGen::generate_moves(black_box(&board), &mut move_list);
+            move_list.count
+        }));
+    }
+    group.finish();
+}
+
+// make/unmake of every legal move in turn
+fn make_unmake(c: &mut Criterion) {
+    let mut group = c.benchmark_group("make_unmake");
+    for (name, mut board) in positions() {
+        let moves = legal_moves(&mut board);
+        group.bench_function(name, |b| b.iter(|| {
+            for mv in &moves {
+                black_box(board.make(mv.clone()));
+                board.unmake(mv.clone());
+            }
+        }));
+    }
+    group.finish();
+}
+
+fn evaluation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluation");
+    for (name, board) in positions() {
+        group.bench_function(name, |b| b.iter(|| black_box(&board).evaluate()));
+    }
+    group.finish();
+}
+
+// The king square for a hit, an empty one where every piece has to be tried
+fn attacks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attacks");
+    for (name, board) in positions() {
+        let king = board.king_position(board.side_to_move).unwrap();
+        let empty = Coordinate::new(3, 5);
+        group.bench_function(format!("{}/king", name), |b| b.iter(|| board.is_square_attacked(black_box(&king), !board.side_to_move)));
+        group.bench_function(format!("{}/empty", name), |b| b.iter(|| board.is_square_attacked(black_box(&empty), !board.side_to_move)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, movegen, make_unmake, evaluation, attacks);
+criterion_main!(benches);
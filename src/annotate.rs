@@ -0,0 +1,155 @@
+// src/annotate.rs
+use std::fs;
+use clap::Args;
+use crate::board::{Board, Classification, Phase};
+use crate::notation::{format_move, parse_move};
+use crate::search::Searcher;
+use crate::threats::describe_threats;
+
+pub const INACCURACY: i32 = 50;
+pub const MISTAKE: i32 = 100;
+pub const BLUNDER: i32 = 300;
+
+pub struct Annotation {
+    pub played: String,
+    pub best: String,
+    pub loss: i32, // centipawns lost compared to the engine's choice
+    pub threats: Vec<String>, // what either side threatens after the move, see threats.rs
+    pub position: Classification, // after the move
+}
+
+impl Annotation {
+    pub fn label(&self) -> Option<(&'static str, &'static str)> {
+        if self.loss >= BLUNDER {
+            Some(("??", "blunder"))
+        } else if self.loss >= MISTAKE {
+            Some(("?", "mistake"))
+        } else if self.loss >= INACCURACY {
+            Some(("?!", "inaccuracy"))
+        } else {
+            None
+        }
+    }
+}
+
+// Split a game text into move tokens. Moves are separated by whitespace or '|', move
+// numbers ("12."), {comments}, [Tag "pairs"] and results are skipped so annotated and
+// exported games can be read back in.
+pub fn game_moves(text: &str) -> Vec<String> {
+    let mut moves = Vec::new();
+    let mut in_comment = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with(['#', '[']) {
+            continue;
+        }
+        for token in line.split(|c: char| c.is_whitespace() || c == '|') {
+            if in_comment {
+                in_comment = !token.ends_with('}');
+                continue;
+            }
+            if token.starts_with('{') {
+                in_comment = !token.ends_with('}');
+                continue;
+            }
+            let token = token.trim_end_matches(['?', '!']);
+            if token.is_empty() || token.ends_with('.') || matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                continue;
+            }
+            moves.push(token.to_string());
+        }
+    }
+
+    moves
+}
+
+// Replay a game from the classical start position, comparing every move with the
+// engine's choice at a fixed depth
+pub fn annotate_game(moves: &[String], depth: u8) -> Result<Vec<Annotation>, String> {
+    let mut board = Board::new();
+    let mut searcher = Searcher::new();
+    searcher.print_info = false;
+    let mut annotations = Vec::new();
+
+    for text in moves {
+        let played = parse_move(&board, text)?;
+        let best = searcher.search_position(&mut board, depth);
+
+        if !board.make(played.clone()) {
+            return Err(format!("move '{}' leaves the king in check", text));
+        }
+        let loss = if played == best.best_move {
+            0
+        } else {
+            let reply = searcher.search_position(&mut board, depth.saturating_sub(1).max(1));
+            (best.score + reply.score).max(0)
+        };
+
+        annotations.push(Annotation {
+            played: format_move(&played, None, false),
+            best: format_move(&best.best_move, None, false),
+            loss,
+            threats: describe_threats(&board),
+            position: board.classify(),
+        });
+    }
+
+    Ok(annotations)
+}
+
+pub fn format_annotated_game(annotations: &[Annotation]) -> String {
+    let mut output = String::new();
+    let mut phase = Phase::Opening;
+
+    for (ply, annotation) in annotations.iter().enumerate() {
+        if ply % 2 == 0 {
+            if ply > 0 {
+                output.push('\n');
+            }
+            output.push_str(&format!("{}. ", ply / 2 + 1));
+        } else {
+            output.push(' ');
+        }
+
+        output.push_str(&annotation.played);
+        if let Some((glyph, label)) = annotation.label() {
+            output.push_str(&format!("{} {{{}, -{}cp, best {}}}", glyph, label, annotation.loss, annotation.best));
+        }
+        if !annotation.threats.is_empty() {
+            output.push_str(&format!(" {{{}}}", annotation.threats.join("; ")));
+        }
+        // the game's course, one comment where each new phase begins
+        if annotation.position.phase != phase {
+            phase = annotation.position.phase;
+            output.push_str(&format!(" {{{}, {}}}", phase.name(), annotation.position.signature));
+        }
+    }
+
+    output.push('\n');
+    output
+}
+
+#[derive(Args, Debug)]
+pub struct AnnotateArgs {
+    #[arg(value_name = "GAME FILE")]
+    pub game: String,
+    #[arg(long, default_value_t = 3)]
+    pub depth: u8,
+    #[arg(long, value_name = "FILE", help = "write the annotated game here instead of printing it")]
+    pub out: Option<String>,
+}
+
+pub fn run(args: &AnnotateArgs) -> Result<(), String> {
+    let (path, output_path, depth) = (&args.game, &args.out, args.depth);
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let annotations = annotate_game(&game_moves(&text), depth)?;
+    let annotated = format_annotated_game(&annotations);
+
+    match output_path {
+        Some(output_path) => fs::write(output_path, annotated).map_err(|err| format!("{}: {}", output_path, err)),
+        None => {
+            print!("{}", annotated);
+            Ok(())
+        }
+    }
+}
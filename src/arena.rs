@@ -0,0 +1,314 @@
+// src/arena.rs
+use std::time::Instant;
+use clap::{ArgAction, Args};
+use clap::builder::BoolishValueParser;
+use crate::board::Board;
+use crate::cli::GlobalOptions;
+use crate::clock::Clock;
+use crate::rules::RuleSet;
+use crate::export::{self, ExportOptions, GameRecord, MoveRecord};
+use crate::game::{format_result, game_end, Game, GameResult, HistoryPolicy};
+use crate::notation::format_fen;
+use crate::openings::Rng;
+use crate::output;
+use crate::relay;
+use crate::rating::{parse_label, print_ratings, RatingFile};
+use crate::r#move::Move;
+use crate::search::Searcher;
+use crate::tools::legal_moves;
+
+pub const PLAYERS: [&str; 4] = ["random", "greedy", "depth1", "engine"];
+// Games still going after this many plies are scored as draws
+pub const DEFAULT_MAX_PLIES: usize = 300;
+
+// An opponent in a match. The weak ones are fixed reference points for the real searcher:
+// they never change between versions, and the random ones repeat their games from a seed.
+pub enum Player {
+    Random(Rng), // any legal move
+    Greedy(Rng), // the most valuable capture, a random move without one
+    DepthOne, // the move with the best static evaluation, mates first
+    Engine(Box<Searcher>, u8), // the real search to a depth, or to its movetime
+}
+
+impl Player {
+    // Engines search with the command's searcher flags, see GlobalOptions
+    pub fn parse(name: &str, seed: u64, depth: u8, movetime: i32, options: &GlobalOptions) -> Result<Player, String> {
+        match name {
+            "random" => Ok(Player::Random(Rng(seed))),
+            "greedy" => Ok(Player::Greedy(Rng(seed))),
+            "depth1" => Ok(Player::DepthOne),
+            "engine" => {
+                let mut searcher = Searcher::new();
+                searcher.print_info = false;
+                searcher.movetime = movetime;
+                options.apply(&mut searcher);
+                Ok(Player::Engine(Box::new(searcher), if movetime > 0 { u8::MAX } else { depth }))
+            }
+            _ => Err(format!("unknown player '{}', expected one of {}", name, PLAYERS.join(", "))),
+        }
+    }
+
+    pub fn new_game(&mut self) {
+        if let Player::Engine(searcher, _) = self {
+            searcher.new_game();
+        }
+    }
+
+    // None when the side to move has no legal move. Engines also give their score, from the
+    // side to move's point of view, and play on the clock when there is one.
+    pub fn choose(&mut self, board: &Board, clock: Option<&Clock>) -> Option<(Move, Option<i32>)> {
+        let mut board = board.clone_without_history();
+        let moves = legal_moves(&mut board);
+        if moves.is_empty() {
+            return None;
+        }
+        Some(match self {
+            Player::Random(rng) => (moves[rng.below(moves.len())].clone(), None),
+            Player::Greedy(rng) => {
+                let victim = |mv: &Move| match mv {
+                    Move::Normal(_, to) | Move::Promotion(_, to, _) => board.get_piece(to).map_or(0, |piece| board.rules.pieces.def(*piece).value),
+                    Move::EnPassant(_, _, pawn) => board.get_piece(pawn).map_or(0, |piece| board.rules.pieces.def(*piece).value),
+                    _ => 0,
+                };
+                let best = moves.iter().map(victim).max().unwrap_or(0);
+                let captures: Vec<&Move> = moves.iter().filter(|mv| victim(mv) == best).collect();
+                (captures[rng.below(captures.len())].clone(), None)
+            }
+            Player::DepthOne => {
+                let mut best = (i32::MIN, moves[0].clone());
+                for mv in moves {
+                    board.make(mv.clone());
+                    let score = match game_end(&mut board, 0) {
+                        Some((GameResult::Draw, _)) => 0,
+                        Some(_) => i32::MAX,
+                        None => -board.evaluate(),
+                    };
+                    board.unmake(mv.clone());
+                    if score > best.0 {
+                        best = (score, mv);
+                    }
+                }
+                (best.1, None)
+            }
+            Player::Engine(searcher, depth) => {
+                if let Some(clock) = clock {
+                    clock.configure(searcher);
+                }
+                let result = searcher.search_position(&mut board, if clock.is_some() { u8::MAX } else { *depth });
+                (result.best_move, Some(result.score))
+            }
+        })
+    }
+}
+
+// Ends games whose outcome is clear long before mate or a rule draw, which on an infinite board
+// can be thousands of plies away. Decided on the static evaluation after every ply, so every
+// player is judged the same way whether it searches or not.
+#[derive(Debug, Clone)]
+pub struct Adjudication {
+    pub draw_score: i32, // |score| below this...
+    pub draw_plies: usize, // ...for this many plies in a row is a draw
+    pub draw_after: usize, // but not before this ply, the start position is level too
+    pub win_score: i32, // |score| above this...
+    pub win_plies: usize, // ...for this many plies in a row wins for the side ahead
+}
+
+impl Adjudication {
+    pub fn new() -> Self {
+        Adjudication { draw_score: 10, draw_plies: 40, draw_after: 60, win_score: 1000, win_plies: 10 }
+    }
+
+    // `<cp>:<plies>`
+    pub fn parse_rule(text: &str) -> Result<(i32, usize), String> {
+        let invalid = || format!("invalid adjudication rule '{}', expected <cp>:<plies>", text);
+        let (score, plies) = text.split_once(':').ok_or_else(invalid)?;
+        Ok((score.parse().map_err(|_| invalid())?, plies.parse().map_err(|_| invalid())?))
+    }
+}
+
+// With a clock, a player whose flag falls loses on time
+// `translation_repetition` is put on the start position's rules, as the REPL does with its
+// `repetition` setting, so repetitions and the adjudication after them count the same way
+pub fn play_game(white: &mut Player, black: &mut Player, mut start: Board, max_plies: usize, adjudication: Option<&Adjudication>, mut clock: Option<Clock>, translation_repetition: bool) -> GameRecord {
+    start.rules.translation_repetition = translation_repetition;
+    let mut record = GameRecord { start: start.clone_without_history(), moves: Vec::new(), result: (GameResult::Draw, "move limit") };
+    let mut game = Game::new(start);
+    // only repetitions need the past, and those only need its hashes
+    game.policy = HistoryPolicy::Hashes(0);
+    white.new_game();
+    black.new_game();
+
+    let (mut level, mut winning) = (0, 0); // plies in a row inside the draw and win margins
+    for ply in 1..=max_plies {
+        let mover = game.board.side_to_move;
+        let player = if mover { &mut *white } else { &mut *black };
+        if let Some(clock) = &mut clock {
+            clock.start(mover);
+        }
+        let started = Instant::now();
+        let Some((mv, score)) = player.choose(&game.board, clock.as_ref()) else { break };
+        let elapsed = started.elapsed().as_millis();
+        if let Some(clock) = &mut clock {
+            clock.press();
+        }
+        record.moves.push(MoveRecord {
+            mv: mv.clone(),
+            elapsed,
+            clock: clock.as_ref().map(|clock| clock.remaining(mover)),
+            eval: score.map(|score| if mover { score } else { -score }),
+        });
+        if let Some(white_flagged) = clock.as_ref().and_then(Clock::flagged) {
+            record.result = (if white_flagged { GameResult::BlackWins } else { GameResult::WhiteWins }, "time forfeit");
+            return record;
+        }
+        if let Err(err) = game.play(mv) {
+            // the players only pick legal moves, so this is a bug worth seeing
+            panic!("player chose an illegal move in {}: {}", format_fen(&game.board), err);
+        }
+        if output::compact() {
+            let clocks = clock.as_ref().map(|clock| (clock.remaining(true), clock.remaining(false)));
+            println!("{}", relay::summary(&game.board, Some((ply, &record.moves[ply - 1].mv)), record.moves[ply - 1].eval, clocks, false));
+        }
+        if let Some(result) = game.result {
+            record.result = result;
+            return record;
+        }
+
+        let Some(rules) = adjudication else { continue };
+        let score = game.board.evaluate_white_relative();
+        level = if score.abs() < rules.draw_score { level + 1 } else { 0 };
+        // the side ahead has to stay the same for the streak to count
+        winning = match winning {
+            _ if score.abs() <= rules.win_score => 0,
+            streak if (streak > 0) == (score > 0) => streak + score.signum(),
+            _ => score.signum(),
+        };
+        if ply >= rules.draw_after && level >= rules.draw_plies {
+            record.result = (GameResult::Draw, "adjudicated draw");
+            return record;
+        }
+        if winning.unsigned_abs() as usize >= rules.win_plies {
+            record.result = (if winning > 0 { GameResult::WhiteWins } else { GameResult::BlackWins }, "adjudicated win");
+            return record;
+        }
+    }
+    if let Some(result) = game.result {
+        record.result = result;
+    }
+    record
+}
+
+// `<base ms>+<increment ms>`
+pub fn parse_time_control(text: &str) -> Result<Clock, String> {
+    let invalid = || format!("invalid time control '{}', expected <base ms>+<increment ms>", text);
+    let (base, inc) = text.split_once('+').unwrap_or((text, "0"));
+    Ok(Clock::new(base.parse().map_err(|_| invalid())?, inc.parse().map_err(|_| invalid())?))
+}
+
+// Points of the first player, who takes white in the even games
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+// Name of a player's configuration in the ratings file. The weak players don't change, the
+// engine is told apart by its limits and a label for the build.
+pub fn config_name(name: &str, depth: u8, movetime: i32, label: Option<&str>) -> String {
+    if name != "engine" {
+        return name.to_string();
+    }
+    let limit = if movetime > 0 { format!("{}ms", movetime) } else { format!("d{}", depth) };
+    match label {
+        Some(label) => format!("engine-{}@{}", limit, label),
+        None => format!("engine-{}", limit),
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MatchArgs {
+    #[arg(value_parser = PLAYERS, help = "takes white in the odd games")]
+    pub first: String,
+    #[arg(value_parser = PLAYERS)]
+    pub second: String,
+    #[arg(long, default_value_t = 10)]
+    pub games: u32,
+    #[arg(long, default_value_t = 1, help = "seed of the random players")]
+    pub seed: u64,
+    #[arg(long, default_value_t = 3, help = "engine search depth")]
+    pub depth: u8,
+    #[arg(long, value_name = "MS", default_value_t = -1, allow_negative_numbers = true, help = "engine time a move, instead of the depth")]
+    pub movetime: i32,
+    #[arg(long, value_name = "MS+MS", value_parser = parse_time_control, help = "game clock: base and increment")]
+    pub tc: Option<Clock>,
+    #[arg(long, default_value_t = DEFAULT_MAX_PLIES, help = "games still going after this many plies are draws")]
+    pub plies: usize,
+    #[arg(long, value_name = "FILE", help = "record the results for Elo, see ratings")]
+    pub ratings: Option<String>,
+    #[arg(long, value_parser = parse_label, help = "names the engine's build in the ratings file")]
+    pub label: Option<String>,
+    #[arg(long, value_name = "on|off", default_value = "on", action = ArgAction::Set, value_parser = BoolishValueParser::new(), help = "end clearly drawn or decided games early")]
+    pub adjudicate: bool,
+    #[arg(long, value_name = "CP:PLIES", value_parser = Adjudication::parse_rule)]
+    pub adjudicate_draw: Option<(i32, usize)>,
+    #[arg(long, value_name = "CP:PLIES", value_parser = Adjudication::parse_rule)]
+    pub adjudicate_win: Option<(i32, usize)>,
+    #[arg(long, value_name = "exact|translated", default_value = "exact", action = ArgAction::Set, value_parser = RuleSet::parse_repetition, help = "'translated' counts shifted pawnless positions as repeats")]
+    pub repetition: bool,
+    #[arg(long, value_name = "FILE", help = "append the games in PGN style")]
+    pub export: Option<String>,
+    #[arg(long, value_name = "clocks,evals|none", default_value = "clocks,evals", value_parser = ExportOptions::parse)]
+    pub export_comments: ExportOptions,
+}
+
+pub fn run(args: &MatchArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (first, second, depth, movetime, label) = (args.first.as_str(), args.second.as_str(), args.depth, args.movetime, args.label.as_deref());
+    let mut adjudication = Adjudication::new();
+    if let Some((score, plies)) = args.adjudicate_draw {
+        (adjudication.draw_score, adjudication.draw_plies) = (score, plies);
+    }
+    if let Some((score, plies)) = args.adjudicate_win {
+        (adjudication.win_score, adjudication.win_plies) = (score, plies);
+    }
+    let ratings = args.ratings.as_ref().map(|path| RatingFile { path: path.clone() });
+    let configs = [config_name(first, depth, movetime, label), config_name(second, depth, movetime, label)];
+    if ratings.is_some() && configs[0] == configs[1] {
+        return Err("a configuration can't be rated against itself".to_string());
+    }
+
+    let mut players = [Player::parse(first, args.seed, depth, movetime, options)?, Player::parse(second, args.seed ^ 0x9e37_79b9_7f4a_7c15, depth, movetime, options)?];
+    let mut score = MatchScore { wins: 0, draws: 0, losses: 0 };
+    for index in 0..args.games {
+        let swapped = index % 2 == 1;
+        let [a, b] = &mut players;
+        let (white, black) = if swapped { (b, a) } else { (a, b) };
+        let record = play_game(white, black, Board::new(), args.plies, args.adjudicate.then_some(&adjudication), args.tc.clone(), args.repetition);
+        let (result, reason) = record.result;
+
+        let (white_name, black_name) = if swapped { (&configs[1], &configs[0]) } else { (&configs[0], &configs[1]) };
+        if let Some(path) = &args.export {
+            let tags = [("Event", format!("match game {}", index + 1)), ("White", white_name.clone()), ("Black", black_name.clone())];
+            export::append(path, &export::format_game(&record, &tags, args.export_comments))?;
+        }
+        println!("game {}: {} vs {} {} ({})", index + 1, white_name, black_name, format_result(result), reason);
+        if let Some(ratings) = &ratings {
+            ratings.record(white_name, black_name, result)?;
+        }
+        match (result, swapped) {
+            (GameResult::Draw, _) => score.draws += 1,
+            (GameResult::WhiteWins, false) | (GameResult::BlackWins, true) => score.wins += 1,
+            _ => score.losses += 1,
+        }
+    }
+    println!("{} {}/{} against {} (+{} ={} -{})", configs[0], score.points(), args.games, configs[1], score.wins, score.draws, score.losses);
+    if let Some(ratings) = &ratings {
+        print_ratings(ratings)?;
+    }
+    Ok(())
+}
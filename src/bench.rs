@@ -0,0 +1,73 @@
+// src/bench.rs
+use std::time::Instant;
+use clap::Args;
+use crate::board::Board;
+use crate::cli::GlobalOptions;
+use crate::notation::parse_fen;
+use crate::search::{HeuristicStats, MoveStats, Searcher};
+
+// Fixed positions searched by `bench`: the start position plus a few sparse ones where the
+// sliders have open rays, pawns can promote and both sides may still castle
+pub const BENCH_POSITIONS: [&str; 4] = [
+    "k20,20;P0,3;p-4,0;R0,0;n7,0;K-5,-5 w - -",
+    "k9,20;r0,10;Q0,2;K0,0 w - -",
+    "r1,8;k5,8;r8,8;p2,7;P3,7;P6,2;R1,1;K5,1;R8,1;b4,4;N6,5 w KQkq -",
+    "k-10,10;p4,5;p2,2;P3,4;N3,3;B-3,-2;K0,0 b - -",
+];
+
+pub struct BenchResult {
+    pub nodes: u64,
+    pub time: u128, // ms
+    pub move_stats: MoveStats,
+    pub heuristic_stats: Option<HeuristicStats>,
+}
+
+// `verify_every` turns on heuristic debugging when given, see HeuristicStats
+pub fn bench(depth: u8, verify_every: Option<u64>, options: &GlobalOptions) -> Result<BenchResult, String> {
+    let mut boards = vec![Board::new()];
+    for fen in BENCH_POSITIONS {
+        boards.push(parse_fen(fen)?);
+    }
+
+    let mut searcher = Searcher::new();
+    searcher.print_info = false;
+    options.apply(&mut searcher);
+    if let Some(every) = verify_every {
+        searcher.heuristic_stats = Some(HeuristicStats::default());
+        searcher.verify_every = every;
+    }
+    let mut total = BenchResult { nodes: 0, time: 0, move_stats: MoveStats::default(), heuristic_stats: None };
+    for (index, board) in boards.iter_mut().enumerate() {
+        searcher.new_game();
+        let start = Instant::now();
+        searcher.search_position(board, depth);
+        let time = start.elapsed().as_millis();
+        println!("position {}: {} nodes {} ms", index + 1, searcher.nodes, time);
+        total.nodes += searcher.nodes;
+        total.time += time;
+        total.move_stats.add(&searcher.move_stats);
+    }
+    total.heuristic_stats = searcher.heuristic_stats;
+    Ok(total)
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    #[arg(long, default_value_t = 3)]
+    pub depth: u8,
+    #[arg(long, help = "count how often each pruning heuristic fires")]
+    pub heuristics: bool,
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "heuristics", help = "re-search one firing in N to see if it was right")]
+    pub verify: u64,
+}
+
+pub fn run(args: &BenchArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (depth, heuristics, verify_every) = (args.depth, args.heuristics, args.verify);
+    let result = bench(depth, heuristics.then_some(verify_every), options)?;
+    println!("{} nodes {} ms {} nps", result.nodes, result.time, result.nodes as u128 * 1000 / result.time.max(1));
+    println!("moves {}", result.move_stats.summary());
+    if let Some(stats) = result.heuristic_stats {
+        println!("heuristics {}", stats.summary());
+    }
+    Ok(())
+}
@@ -1,11 +1,49 @@
 // src/board.rs
 use std::collections::HashMap;
 use num_bigint::BigInt;
-use num_traits::{Signed, Zero};
-use crate::r#move::{Move, MoveGen, MoveList};
+use num_traits::Signed;
+use crate::r#move::Move;
+use crate::pieces::piece_set;
+use crate::evaluation::{self, PawnHash};
+use crate::hash::repetition_key;
+use crate::geometry::{ranks_between, Direction, KING_OFFSETS, KNIGHT_OFFSETS};
+use crate::rules::RuleSet;
+use crate::notation::{format_relative_coordinate, format_relative_integer};
 
 
 pub const PIECE_VALUES: [i16; 12] = [100, 700, 300, 400, 1200, 0, 100, 700, 300, 400, 1200, 0];
+// Transforms refuse positions whose pawn start ranks would leave the i64 range
+const PAWN_RANK_RANGE: &str = "a pawn start rank would leave the i64 range";
+
+// Share of the classical starting material, pawns and kings left out, that separates the phases
+const OPENING_MATERIAL_PERCENT: i32 = 90;
+const ENDGAME_MATERIAL_PERCENT: i32 = 30;
+const OPENING_PAWNS: usize = 14; // an opening also still has nearly all its pawns
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Phase {
+    Opening,
+    #[default]
+    Middlegame,
+    Endgame,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Opening => "opening",
+            Phase::Middlegame => "middlegame",
+            Phase::Endgame => "endgame",
+        }
+    }
+}
+
+// What kind of position a board holds, see Board::classify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classification {
+    pub phase: Phase,
+    pub signature: String, // material of both sides, "KRR vs K"
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Piece {
@@ -25,17 +63,35 @@ pub enum Piece {
 
 impl Piece {
     pub fn is_white(&self) -> bool {
-        match self {
-            Piece::WhitePawn | Piece::WhiteRook | Piece::WhiteKnight | Piece::WhiteBishop | Piece::WhiteQueen | Piece::WhiteKing => true,
-            _ => false,
-        }
+        matches!(self, Piece::WhitePawn | Piece::WhiteRook | Piece::WhiteKnight | Piece::WhiteBishop | Piece::WhiteQueen | Piece::WhiteKing)
     }
 
     pub fn is_black(&self) -> bool {
-        match self {
-            Piece::BlackPawn | Piece::BlackRook | Piece::BlackKnight | Piece::BlackBishop | Piece::BlackQueen | Piece::BlackKing => true,
-            _ => false,
-        }
+        matches!(self, Piece::BlackPawn | Piece::BlackRook | Piece::BlackKnight | Piece::BlackBishop | Piece::BlackQueen | Piece::BlackKing)
+    }
+
+    // Colourless piece kind, indexes pieces::PIECE_NAMES
+    pub fn kind(&self) -> usize {
+        *self as usize % 6
+    }
+
+    pub fn from_kind(kind: usize, white: bool) -> Piece {
+        const PIECES: [Piece; 12] = [
+            Piece::WhitePawn, Piece::WhiteRook, Piece::WhiteKnight, Piece::WhiteBishop, Piece::WhiteQueen, Piece::WhiteKing,
+            Piece::BlackPawn, Piece::BlackRook, Piece::BlackKnight, Piece::BlackBishop, Piece::BlackQueen, Piece::BlackKing,
+        ];
+        PIECES[kind + if white { 0 } else { 6 }]
+    }
+
+    pub fn glyph(&self) -> &'static str {
+        let def = piece_set().def(*self);
+        if self.is_white() { &def.white_glyph } else { &def.black_glyph }
+    }
+
+    // Notation letter, uppercase for white and lowercase for black
+    pub fn letter(&self) -> char {
+        let letter = piece_set().def(*self).letter;
+        if self.is_white() { letter.to_ascii_uppercase() } else { letter }
     }
 }
 
@@ -47,15 +103,67 @@ impl Coordinate {
     pub fn new(x: i64, y: i64) -> Self {
         Coordinate(BigInt::from(x), BigInt::from(y))
     }
+
+    pub fn relative_to(&self, anchor: &Coordinate) -> Coordinate {
+        let (dx, dy) = anchor.delta(self);
+        Coordinate(dx, dy)
+    }
+}
+
+// Reference point for printing coordinates, so far-from-origin positions stay readable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    Origin,
+    WhiteKing,
+    BlackKing,
+    Square(Coordinate),
+}
+
+impl Anchor {
+    // Resolve the anchor on a board, None means coordinates are printed as they are
+    pub fn resolve(&self, board: &Board) -> Option<Coordinate> {
+        let king = match self {
+            Anchor::Origin => return None,
+            Anchor::Square(coord) => return Some(coord.clone()),
+            Anchor::WhiteKing => Piece::WhiteKing,
+            Anchor::BlackKing => Piece::BlackKing,
+        };
+        board.state.iter().find(|(_, piece)| **piece == king).map(|(coord, _)| coord.clone())
+    }
+}
+
+// A rook that may still castle with its king. Rights are kept per rook rather than per side,
+// so setups with several rooks or shifted back ranks keep exactly the rights they had.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CastlingRight {
+    pub rook: Coordinate,
+    pub white: bool,
+}
+
+impl CastlingRight {
+    pub fn new(x: i64, y: i64, white: bool) -> Self {
+        CastlingRight { rook: Coordinate::new(x, y), white }
+    }
+
+    // The four corners of the classical setup, KQkq in FEN
+    pub fn classical() -> Vec<CastlingRight> {
+        vec![CastlingRight::new(8, 1, true), CastlingRight::new(1, 1, true), CastlingRight::new(8, 8, false), CastlingRight::new(1, 8, false)]
+    }
 }
 
+// Clone copies the move history along with the position, so a late game board drags every
+// earlier position with it. Searches and other throwaway copies want clone_without_history.
 #[derive(Debug, Clone)]
 pub struct Board {
     pub state: HashMap<Coordinate, Piece>,
-    pub castling_rights: u8,
-    pub en_passant: Option<Coordinate>,
+    pub castling_rights: Vec<CastlingRight>,
+    pub en_passant: Vec<Coordinate>, // squares the last move's pawn skipped, each one open to en passant
     pub side_to_move: bool, // true for white, false for black
-    pub history: Vec<Board> // store board history for make and unmake
+    pub last_capture: Option<Coordinate>, // square the previous move captured on
+    pub captured: Option<Piece>, // piece the previous move captured
+    pub rules: RuleSet,
+    pub halfmove_clock: u32, // plies since the last capture or pawn move
+    pub history: Vec<Board> // positions before each move made, without their own history
 }
 
 impl Board {
@@ -90,9 +198,13 @@ impl Board {
 
         Board {
             state,
-            castling_rights: 15, // Both sides can castle initially
-            en_passant: None,
+            castling_rights: CastlingRight::classical(), // Both sides can castle initially
+            en_passant: Vec::new(),
             side_to_move: true, // White starts
+            last_capture: None,
+            captured: None,
+            rules: RuleSet::classic(),
+            halfmove_clock: 0,
             history: Vec::new()
         }
     }
@@ -100,13 +212,32 @@ impl Board {
     pub fn empty() -> Self {
         Board {
             state: HashMap::new(),
-            castling_rights: 15,
-            en_passant: None,
+            castling_rights: CastlingRight::classical(),
+            en_passant: Vec::new(),
             side_to_move: true,
+            last_capture: None,
+            captured: None,
+            rules: RuleSet::classic(),
+            halfmove_clock: 0,
             history: Vec::new()
         }
     }
 
+    // The position alone: unmake has nothing to go back to on the copy
+    pub fn clone_without_history(&self) -> Board {
+        Board {
+            state: self.state.clone(),
+            castling_rights: self.castling_rights.clone(),
+            en_passant: self.en_passant.clone(),
+            side_to_move: self.side_to_move,
+            last_capture: self.last_capture.clone(),
+            captured: self.captured,
+            rules: self.rules.clone(),
+            halfmove_clock: self.halfmove_clock,
+            history: Vec::new(),
+        }
+    }
+
     pub fn get_piece(&self, coord: &Coordinate) -> Option<&Piece> {
         self.state.get(coord)
     }
@@ -119,211 +250,354 @@ impl Board {
         self.state.remove(coord);
     }
 
-    pub fn move_piece(&mut self, from: Coordinate, to: Coordinate) {
-        // Handle captures
-        if let Some(captured_piece) = self.state.remove(&to) {
-            // Handle captured piece logic if needed
+    // The same position with colours swapped. Ranks are mirrored around the classical centre
+    // line (y -> 9 - y) so pawns keep heading for the promotion ranks of their new colour.
+    pub fn flipped(&self) -> Result<Board, String> {
+        let mirror = |coord: &Coordinate| Coordinate(coord.0.clone(), BigInt::from(9) - &coord.1);
+        Ok(Board {
+            state: self.state.iter().map(|(coord, piece)| (mirror(coord), Piece::from_kind(piece.kind(), !piece.is_white()))).collect(),
+            castling_rights: self.castling_rights.iter().map(|right| CastlingRight { rook: mirror(&right.rook), white: !right.white }).collect(),
+            en_passant: self.en_passant.iter().map(mirror).collect(),
+            side_to_move: !self.side_to_move,
+            last_capture: self.last_capture.as_ref().map(mirror),
+            captured: self.captured.map(|piece| Piece::from_kind(piece.kind(), !piece.is_white())),
+            rules: self.rules.flipped().ok_or(PAWN_RANK_RANGE)?,
+            halfmove_clock: self.halfmove_clock,
+            history: Vec::new(),
+        })
+    }
+
+    // The position turned half a turn around the classical centre, (x, y) -> (9 - x, 9 - y).
+    // Pieces keep their colour, so this is a setup helper rather than an equivalent position;
+    // combine with flipped() for that. Castling rights move along with their rooks.
+    pub fn rotate180(&self) -> Result<Board, String> {
+        Ok(self.transformed(|coord| Coordinate(BigInt::from(9) - &coord.0, BigInt::from(9) - &coord.1), self.rules.rotated().ok_or(PAWN_RANK_RANGE)?))
+    }
+
+    // The position shifted by (dx, dy), pawn start ranks included
+    pub fn translate(&self, dx: i64, dy: i64) -> Result<Board, String> {
+        Ok(self.transformed(|coord| Coordinate(&coord.0 + dx, &coord.1 + dy), self.rules.translated(dy).ok_or(PAWN_RANK_RANGE)?))
+    }
+
+    fn transformed(&self, map: impl Fn(&Coordinate) -> Coordinate, rules: RuleSet) -> Board {
+        Board {
+            state: self.state.iter().map(|(coord, piece)| (map(coord), *piece)).collect(),
+            castling_rights: self.castling_rights.iter().map(|right| CastlingRight { rook: map(&right.rook), white: right.white }).collect(),
+            en_passant: self.en_passant.iter().map(&map).collect(),
+            side_to_move: self.side_to_move,
+            last_capture: self.last_capture.as_ref().map(&map),
+            captured: self.captured,
+            rules,
+            halfmove_clock: self.halfmove_clock,
+            history: Vec::new(),
         }
+    }
 
-        // Handle en passant capture
-        if let Piece::WhitePawn | Piece::BlackPawn = self.state.get(&from).unwrap() {
-            if let Some(en_passant_coord) = &self.en_passant {
-                if to == *en_passant_coord {
-                    let capture_coord = Coordinate(to.0.clone(), from.1.clone());
-                    self.state.remove(&capture_coord);
-                }
+    pub fn move_piece(&mut self, from: Coordinate, to: Coordinate) {
+        // Castling is the only king move of two squares along a rank: the rook is the first
+        // piece beyond the king's target and jumps to the square the king passes over
+        let piece = *self.state.get(&from).unwrap();
+        let castling_rook = match from.between(&to) {
+            Some((direction, distance)) if matches!(piece, Piece::WhiteKing | Piece::BlackKing) && from.1 == to.1 && distance == BigInt::from(2) => {
+                self.nearest_piece_in_direction(&from, direction).map(|(_, rook, _)| (rook.clone(), from.step(direction, &BigInt::from(1))))
             }
-        }
+            _ => None,
+        };
+
+        // Handle captures
+        self.state.remove(&to);
 
         // Move the piece
-        let piece = self.state.remove(&from).unwrap();
+        self.state.remove(&from);
         self.state.insert(to.clone(), piece);
 
-        // Handle castling
-        if piece == Piece::WhiteKing && from == Coordinate::new(5, 1) {
-            if to == Coordinate::new(3, 1) {
-                // Long castling for white
-                let rook_from = Coordinate::new(1, 1);
-                let rook_to = Coordinate::new(4, 1);
-                let rook = self.state.remove(&rook_from).unwrap();
-                self.state.insert(rook_to, rook);
-            } else if to == Coordinate::new(7, 1) {
-                // Short castling for white
-                let rook_from = Coordinate::new(8, 1);
-                let rook_to = Coordinate::new(6, 1);
-                let rook = self.state.remove(&rook_from).unwrap();
-                self.state.insert(rook_to, rook);
-            }
-        } else if piece == Piece::BlackKing && from == Coordinate::new(5, 8) {
-            if to == Coordinate::new(3, 8) {
-                // Long castling for black
-                let rook_from = Coordinate::new(1, 8);
-                let rook_to = Coordinate::new(4, 8);
-                let rook = self.state.remove(&rook_from).unwrap();
-                self.state.insert(rook_to, rook);
-            } else if to == Coordinate::new(7, 8) {
-                // Short castling for black
-                let rook_from = Coordinate::new(8, 8);
-                let rook_to = Coordinate::new(6, 8);
-                let rook = self.state.remove(&rook_from).unwrap();
-                self.state.insert(rook_to, rook);
-            }
+        if let Some((rook_from, rook_to)) = castling_rook {
+            let rook = self.state.remove(&rook_from).unwrap();
+            self.state.insert(rook_to, rook);
         }
 
-        // Update castling rights
-        match piece {
-            Piece::WhiteKing => self.castling_rights &= !0b1100, // White king moved
-            Piece::BlackKing => self.castling_rights &= !0b0011, // Black king moved
-            Piece::WhiteRook if from == Coordinate::new(1, 1) => self.castling_rights &= !0b1000, // White rook 1 moved
-            Piece::WhiteRook if from == Coordinate::new(8, 1) => self.castling_rights &= !0b0100, // White rook 2 moved
-            Piece::BlackRook if from == Coordinate::new(1, 8) => self.castling_rights &= !0b0010, // Black rook 1 moved
-            Piece::BlackRook if from == Coordinate::new(8, 8) => self.castling_rights &= !0b0001, // Black rook 2 moved
-            _ => {}
+        // Update castling rights: a rook leaving its square or being captured there loses its
+        // own, a king move gives up all of its side's
+        self.castling_rights.retain(|right| right.rook != from && right.rook != to);
+        if let Piece::WhiteKing | Piece::BlackKing = piece {
+            self.castling_rights.retain(|right| right.white != piece.is_white());
         }
 
-        // Handle en passant
-        self.en_passant = None;
+        // Handle en passant: a pawn pushed several squares can be taken on any square it skipped
+        self.en_passant.clear();
         if let Piece::WhitePawn | Piece::BlackPawn = piece {
-            if (from.1.clone() - to.1.clone()).abs() == BigInt::from(2) {
-                self.en_passant = Some(Coordinate(from.0.clone(), (from.1 + to.1.clone()) / 2));
+            let step = if piece.is_white() { 1 } else { -1 };
+            if from.0 == to.0 && (&to.1 - &from.1) * step > BigInt::from(0) {
+                let skipped = ranks_between(&from.1, &to.1);
+                self.en_passant.extend(skipped.into_iter().map(|rank| Coordinate(from.0.clone(), rank)));
             }
         }
     }
 
+    // The pawn that can be taken en passant: the side to move's opponent pushed it just past
+    // the en passant squares
+    pub fn en_passant_pawn(&self) -> Option<Coordinate> {
+        let first = self.en_passant.first()?;
+        let rank = if self.side_to_move {
+            self.en_passant.iter().map(|square| &square.1).min()? - 1
+        } else {
+            self.en_passant.iter().map(|square| &square.1).max()? + 1
+        };
+        Some(Coordinate(first.0.clone(), rank))
+    }
+
+    // Whether a side can still hope to mate: a pawn may promote, and a rook or queen, or two minor
+    // pieces other than a pair of knights, could at least help. A lone minor piece or two
+    // knights can't mate even on a bounded board. Under royal capture any piece might take the king.
+    pub fn has_mating_material(&self, white: bool) -> bool {
+        if self.rules.royal_capture() {
+            return true;
+        }
+        let (mut knights, mut bishops) = (0, 0);
+        for piece in self.state.values().filter(|piece| piece.is_white() == white) {
+            match piece {
+                Piece::WhitePawn | Piece::BlackPawn | Piece::WhiteRook | Piece::BlackRook | Piece::WhiteQueen | Piece::BlackQueen => return true,
+                Piece::WhiteKnight | Piece::BlackKnight => knights += 1,
+                Piece::WhiteBishop | Piece::BlackBishop => bishops += 1,
+                _ => {}
+            }
+        }
+        bishops >= 2 || (bishops >= 1 && knights >= 1) || knights >= 3
+    }
+
+    // By the material still on the board against the classical start, in the values of the
+    // board's piece set
+    pub fn phase(&self) -> Phase {
+        let value = |piece: Piece| self.rules.pieces.def(piece).value as i32;
+        let start = 2 * (value(Piece::WhiteQueen) + 2 * (value(Piece::WhiteRook) + value(Piece::WhiteBishop) + value(Piece::WhiteKnight)));
+        let (mut material, mut pawns) = (0, 0);
+        for piece in self.state.values() {
+            if piece.kind() == Piece::WhitePawn.kind() {
+                pawns += 1;
+            } else if piece.kind() != Piece::WhiteKing.kind() {
+                material += value(*piece);
+            }
+        }
+        if material * 100 <= start * ENDGAME_MATERIAL_PERCENT {
+            Phase::Endgame
+        } else if material * 100 >= start * OPENING_MATERIAL_PERCENT && pawns >= OPENING_PAWNS {
+            Phase::Opening
+        } else {
+            Phase::Middlegame
+        }
+    }
+
+    // "KRR vs K": each side's pieces by their letters, king first and then by value
+    pub fn material_signature(&self) -> String {
+        let side = |white: bool| {
+            let mut pieces: Vec<Piece> = self.state.values().filter(|piece| piece.is_white() == white).copied().collect();
+            pieces.sort_by_key(|piece| (piece.kind() != Piece::WhiteKing.kind(), std::cmp::Reverse(self.rules.pieces.def(*piece).value), piece.kind()));
+            pieces.iter().map(|piece| piece.letter().to_ascii_uppercase()).collect::<String>()
+        };
+        format!("{} vs {}", side(true), side(false))
+    }
+
+    pub fn classify(&self) -> Classification {
+        Classification { phase: self.phase(), signature: self.material_signature() }
+    }
+
+    // Neither side can mate any more, whatever is played
+    pub fn is_dead_draw(&self) -> bool {
+        !self.has_mating_material(true) && !self.has_mating_material(false)
+    }
+
+    // Score from the side to move's point of view, as negamax wants it
     pub fn evaluate(&self) -> i32 {
+        self.evaluate_cached(&mut PawnHash::default())
+    }
+
+    // The same with pawn structure scores kept in `pawns`, as a searcher does
+    pub fn evaluate_cached(&self, pawns: &mut PawnHash) -> i32 {
+        let score = self.evaluate_white_relative_cached(pawns);
+        if self.side_to_move { score } else { -score }
+    }
+
+    pub fn evaluate_white_relative(&self) -> i32 {
+        self.evaluate_white_relative_cached(&mut PawnHash::default())
+    }
+
+    // Score from white's point of view: positive is good for white whoever is to move. Every
+    // term is computed this way, the side to move only comes in through evaluate().
+    pub fn evaluate_white_relative_cached(&self, pawns: &mut PawnHash) -> i32 {
         let mut score = 0;
 
-        // Efficient insufficient material check
+        if self.is_dead_draw() {
+            return 0;
+        }
+
         let mut white_material = 0;
         let mut black_material = 0;
-        let mut white_minor_pieces = 0;
-        let mut black_minor_pieces = 0;
-        let mut white_has_queen_or_rook = false;
-        let mut black_has_queen_or_rook = false;
-
-        // Early exit if pawns are present
-        let mut white_has_pawn = false;
-        let mut black_has_pawn = false;
-
         for piece in self.state.values() {
-            match piece {
-                Piece::WhitePawn => {
-                    white_material += PIECE_VALUES[*piece as usize];
-                    white_has_pawn = true;
-                }
-                Piece::BlackPawn => {
-                    black_material += PIECE_VALUES[*piece as usize];
-                    black_has_pawn = true;
-                }
-                // Track white's queens and rooks
-                Piece::WhiteQueen | Piece::WhiteRook => {
-                    white_material += PIECE_VALUES[*piece as usize];
-                    white_has_queen_or_rook = true;
-                }
-                // Track black's queens and rooks
-                Piece::BlackQueen | Piece::BlackRook => {
-                    black_material += PIECE_VALUES[*piece as usize];
-                    black_has_queen_or_rook = true;
-                }
-                // Track white's minor pieces (knights, bishops)
-                Piece::WhiteKnight | Piece::WhiteBishop => {
-                    white_material += PIECE_VALUES[*piece as usize];
-                    white_minor_pieces += 1;
-                }
-                // Track black's minor pieces (knights, bishops)
-                Piece::BlackKnight | Piece::BlackBishop => {
-                    black_material += PIECE_VALUES[*piece as usize];
-                    black_minor_pieces += 1;
-                }
-                _ => {}
+            if piece.is_white() {
+                white_material += self.rules.value(*piece);
+            } else {
+                black_material += self.rules.value(*piece);
             }
         }
 
-        if !black_has_pawn && !white_has_pawn {
-            let white_insufficient_material =
-                !white_has_queen_or_rook && (white_material == 0 && white_minor_pieces <= 1);
+        // armies of a variant may start with different material, that difference is level
+        score += white_material - black_material - self.rules.material_baseline;
+        score += evaluation::positional_score(self, pawns);
+        let score = score * evaluation::endgame_scale(self) / evaluation::SCALE_NORMAL;
+        score * evaluation::fortress_percent(self, score) / 100
+    }
 
-            let black_insufficient_material =
-                !black_has_queen_or_rook && (black_material == 0 && black_minor_pieces <= 1);
+    // Whether the piece standing on `from` attacks `target`, checked directly from the piece's
+    // movement pattern instead of generating moves
+    pub fn attacks_square(&self, from: &Coordinate, piece: Piece, target: &Coordinate) -> bool {
+        let dx = &target.0 - &from.0;
+        let dy = &target.1 - &from.1;
+        if dx == BigInt::from(0) && dy == BigInt::from(0) {
+            return false;
+        }
 
-            // Both players have insufficient material for checkmate on infinite board
-            if white_insufficient_material && black_insufficient_material {
-                return 0; // Draw due to insufficient material
+        match piece {
+            Piece::WhitePawn => dy == BigInt::from(1) && dx.abs() == BigInt::from(1),
+            Piece::BlackPawn => dy == BigInt::from(-1) && dx.abs() == BigInt::from(1),
+            Piece::WhiteKnight | Piece::BlackKnight => {
+                let (ax, ay) = (dx.abs(), dy.abs());
+                (ax == BigInt::from(1) && ay == BigInt::from(2)) || (ax == BigInt::from(2) && ay == BigInt::from(1))
             }
+            Piece::WhiteKing | Piece::BlackKing => dx.abs() <= BigInt::from(1) && dy.abs() <= BigInt::from(1),
+            Piece::WhiteRook | Piece::BlackRook => from.between(target).is_some_and(|(direction, _)| direction.is_orthogonal()) && self.path_clear(from, target),
+            Piece::WhiteBishop | Piece::BlackBishop => from.between(target).is_some_and(|(direction, _)| !direction.is_orthogonal()) && self.path_clear(from, target),
+            Piece::WhiteQueen | Piece::BlackQueen => from.aligned_with(target) && self.path_clear(from, target),
         }
+    }
 
-        score += white_material - black_material;
+    // No piece stands strictly between two squares on a common rank, file or diagonal
+    pub fn path_clear(&self, from: &Coordinate, to: &Coordinate) -> bool {
+        let Some((direction, distance)) = from.between(to) else { return true };
+        self.nearest_piece_in_direction(from, direction).is_none_or(|(along, _, _)| along >= distance)
+    }
 
-        // Calculate the score
-        if { self.side_to_move } {
-            // White to move
-            score as i32
+    // The first piece a slider on `from` would run into, the basis of all slider moves
+    pub fn nearest_piece_in_direction(&self, from: &Coordinate, direction: Direction) -> Option<(BigInt, &Coordinate, Piece)> {
+        self.state.iter()
+            .filter_map(|(coord, piece)| match from.between(coord) {
+                Some((ray, distance)) if ray == direction => Some((distance, coord, *piece)),
+                _ => None,
+            })
+            .min_by(|a, b| a.0.cmp(&b.0))
+    }
+
+    pub fn is_square_attacked(&self, target: &Coordinate, by_white: bool) -> bool {
+        self.attackers(target, by_white).next().is_some()
+    }
+
+    // Pieces of one side attacking `target`, lazily: first the leapers on the knight and king
+    // offsets around it, then the nearest piece on each of the 8 rays if it slides that way
+    fn attackers<'a>(&'a self, target: &'a Coordinate, by_white: bool) -> impl Iterator<Item = (Coordinate, Piece)> + 'a {
+        let leapers = KING_OFFSETS.into_iter().chain(KNIGHT_OFFSETS)
+            .map(move |offset| target + offset)
+            .filter_map(move |square| match self.get_piece(&square) {
+                Some(piece) if piece.is_white() == by_white && !evaluation::is_slider(*piece) && self.attacks_square(&square, *piece, target) => Some((square, *piece)),
+                _ => None,
+            });
+        let sliders = Direction::ALL.into_iter()
+            .filter_map(move |direction| match self.nearest_piece_in_direction(target, direction) {
+                Some((_, coord, piece)) if piece.is_white() == by_white && evaluation::slides_along(piece, direction) => Some((coord.clone(), piece)),
+                _ => None,
+            });
+        leapers.chain(sliders)
+    }
+
+    // Every piece of one side attacking `target`, ordered by rank then file. For an occupied
+    // square the occupant's own side are its defenders.
+    pub fn attackers_of(&self, target: &Coordinate, by_white: bool) -> Vec<(Coordinate, Piece)> {
+        let mut attackers: Vec<(Coordinate, Piece)> = self.attackers(target, by_white).collect();
+        attackers.sort_by(|a, b| (&a.0.1, &a.0.0).cmp(&(&b.0.1, &b.0.0)));
+        attackers
+    }
+
+    // Earlier positions of the game the current one repeats, see repetition_key
+    pub fn repetitions(&self) -> usize {
+        let key = repetition_key(self);
+        self.history.iter().filter(|past| repetition_key(past) == key).count()
+    }
+
+    // Why the position is drawn, given how often it was seen before (which the caller may know
+    // beyond Board::history). `claimed` applies the claimable thresholds instead of the automatic ones.
+    pub fn draw_reason(&self, repetitions: usize, claimed: bool) -> Option<&'static str> {
+        let (repeats, plies) = if claimed { (2, 100) } else { (4, 150) };
+        if repetitions >= repeats {
+            Some(if claimed { "threefold repetition" } else { "fivefold repetition" })
+        } else if self.halfmove_clock >= plies {
+            Some(if claimed { "fifty-move rule" } else { "seventy-five-move rule" })
         } else {
-            // Black to move
-            -score as i32
+            None
         }
     }
 
-    pub fn is_attacked(&mut self, coord: Coordinate, by_white: bool) -> bool {
-        let mut move_list = MoveList::new();
-        let side_to_move = self.side_to_move;
-        self.side_to_move = by_white;
-        MoveGen::generate_moves(&self, &mut move_list);
-        let counted = move_list.count;
-
-        for count in 0..counted {
-            let mv = move_list.moves[count as usize].clone();
-            match mv {
-                Move::Normal(from, to) | Move::Promotion(from, to, _) => {
-                    if to == coord {
-                        self.side_to_move = side_to_move;
-                        return true;
-                    }
-                }
-                _ => {}
-            }
-        }
+    // The side to move has lost its king, which ends the game under royal capture
+    pub fn royal_captured(&self) -> bool {
+        self.rules.royal_capture() && evaluation::find_king(self, self.side_to_move).is_none()
+    }
 
-        self.side_to_move = side_to_move;
-        false
+    // None for a side without a king, which studies and royal capture games may have
+    pub fn king_position(&self, is_white: bool) -> Option<Coordinate> {
+        evaluation::find_king(self, is_white).cloned()
     }
 
-    pub fn king_position(&self, is_white: bool) -> Coordinate {
-        for (coord, piece) in &self.state {
-            if (is_white && *piece == Piece::WhiteKing) || (!is_white && *piece == Piece::BlackKing) {
-                return coord.clone();
-            }
-        }
-        self.show(true);
-        panic!("King not found!");
+    // A side without a king is never in check
+    pub fn in_check(&self, white: bool) -> bool {
+        evaluation::find_king(self, white).is_some_and(|king| self.is_square_attacked(king, !white))
     }
 
     pub fn make(&mut self, mv: Move) -> bool {
-        self.history.push((*self).clone());
+        self.history.push(self.clone_without_history());
+        self.captured = match &mv {
+            Move::Normal(_, to) | Move::Promotion(_, to, _) => self.state.get(to).copied(),
+            Move::EnPassant(_, _, pawn) => self.state.get(pawn).copied(),
+            _ => None,
+        };
+        self.last_capture = match &mv {
+            Move::Normal(_, to) | Move::Promotion(_, to, _) | Move::EnPassant(_, to, _) if self.captured.is_some() => Some(to.clone()),
+            _ => None,
+        };
+        let pawn_move = match &mv {
+            Move::Normal(from, _) => matches!(self.state.get(from), Some(Piece::WhitePawn | Piece::BlackPawn)),
+            Move::Promotion(..) | Move::EnPassant(..) => true,
+            _ => false,
+        };
+        self.halfmove_clock = if pawn_move || self.captured.is_some() { 0 } else { self.halfmove_clock + 1 };
         // Make the move
         match mv.clone() {
             Move::Normal(from, to) => self.move_piece(from, to),
             Move::Promotion(from, to, piece) => {
-                self.remove_piece(&from);
+                // move the pawn first so captures, castling rights and en passant are handled
+                // exactly as for normal moves
+                self.move_piece(from, to.clone());
                 self.set_piece(to, piece);
             }
             Move::Castling(from, to) => {
                 self.move_piece(from, to);
             }
-            Move::EnPassant(from, to) => {
+            Move::EnPassant(from, to, captured) => {
+                self.remove_piece(&captured);
                 self.move_piece(from, to);
             }
             _ => {}
         }
 
         self.side_to_move = !self.side_to_move;
+        // Under royal capture leaving the king attacked is allowed, it just loses
+        if self.rules.royal_capture() {
+            return true;
+        }
         // Check if the move leaves the king in check
-        let king_pos = self.king_position(!self.side_to_move);
-        !self.is_attacked(king_pos, self.side_to_move)
+        !self.in_check(!self.side_to_move)
     }
 
-    pub fn unmake(&mut self, mv: Move) {
-        * self = self.history.pop().unwrap();
+    pub fn unmake(&mut self, _mv: Move) {
+        let mut history = std::mem::take(&mut self.history);
+        *self = history.pop().unwrap();
+        self.history = history;
         // let from = Coordinate(Default::default(), Default::default());
         // let to = Coordinate(Default::default(), Default::default());
         // match mv {
@@ -398,6 +672,21 @@ impl Board {
     }
 
     pub fn show(&self, unicode: bool) {
+        self.show_relative(unicode, &Anchor::Origin);
+    }
+
+    // Print the board with rank and file labels measured from the given anchor
+    pub fn show_relative(&self, unicode: bool, anchor: &Anchor) {
+        let anchor = anchor.resolve(self);
+        let label = |value: &BigInt, origin: &BigInt| match &anchor {
+            Some(_) => format_relative_integer(&(value - origin)),
+            None => value.to_string(),
+        };
+        let (origin_x, origin_y) = match &anchor {
+            Some(coord) => (coord.0.clone(), coord.1.clone()),
+            None => (BigInt::from(0), BigInt::from(0)),
+        };
+
         if self.state.is_empty() {
             println!("The board is empty.");
             return;
@@ -414,24 +703,11 @@ impl Board {
             while file <= max_x {
                 let coord = Coordinate(file.clone(), rank.clone());
                 if file == min_x {
-                    print!("{:2} ", rank);
+                    print!("{:>2} ", label(&rank, &origin_y));
                 }
                 if let Some(piece) = self.get_piece(&coord) {
                     if unicode {
-                        print!("{} ", match piece {
-                            Piece::WhitePawn => "♙",
-                            Piece::WhiteRook => "♖",
-                            Piece::WhiteKnight => "♘",
-                            Piece::WhiteBishop => "♗",
-                            Piece::WhiteQueen => "♕",
-                            Piece::WhiteKing => "♔",
-                            Piece::BlackPawn => "♟",
-                            Piece::BlackRook => "♜",
-                            Piece::BlackKnight => "♞",
-                            Piece::BlackBishop => "♝",
-                            Piece::BlackQueen => "♛",
-                            Piece::BlackKing => "♚",
-                        });
+                        print!("{} ", piece.glyph());
                     } else {
                         print!("{:?} ", piece);
                     }
@@ -447,13 +723,75 @@ impl Board {
         print!("   ");
         let mut file = min_x.clone();
         while file <= max_x {
-            print!("{:2} ", file);
+            print!("{:>2} ", label(&file, &origin_x));
             file += 1;
         }
         println!();
 
         println!("Side to move: {}", if self.side_to_move { "White" } else { "Black" });
-        println!("Castling rights: {:?}", self.castling_rights);
-        println!("En passant: {:?}", self.en_passant);
+        println!("Castling rights: {}", crate::notation::format_castling(&self.castling_rights));
+        let en_passant: Vec<String> = self.en_passant.iter()
+            .map(|square| anchor.as_ref().map_or(square.to_string(), |origin| format_relative_coordinate(square, origin)))
+            .collect();
+        println!("En passant: {}", if en_passant.is_empty() { "None".to_string() } else { en_passant.join(" ") });
+        if let Some(origin) = &anchor {
+            println!("Coordinates relative to {}", origin);
+        }
+    }
+
+    // Lowest and highest corner of the squares holding pieces, None on an empty board
+    pub fn bounding_box(&self) -> Option<(Coordinate, Coordinate)> {
+        let min = Coordinate(self.state.keys().map(|coord| coord.0.clone()).min()?, self.state.keys().map(|coord| coord.1.clone()).min()?);
+        let max = Coordinate(self.state.keys().map(|coord| coord.0.clone()).max()?, self.state.keys().map(|coord| coord.1.clone()).max()?);
+        Some((min, max))
+    }
+
+    // Print which squares in the viewport (corners inclusive) each side attacks. A cell is the
+    // piece letter (or '.') followed by the number of white and black attackers ('.' for none).
+    pub fn show_attacks(&self, min: &Coordinate, max: &Coordinate) {
+        let count = |target: &Coordinate, white: bool| {
+            let attackers = self.attackers_of(target, white).len();
+            match attackers {
+                0 => '.',
+                1..=9 => char::from_digit(attackers as u32, 10).unwrap(),
+                _ => '+',
+            }
+        };
+
+        let mut rank = max.1.clone();
+        while rank >= min.1 {
+            print!("{:>4} ", rank);
+            let mut file = min.0.clone();
+            while file <= max.0 {
+                let coord = Coordinate(file.clone(), rank.clone());
+                let piece = self.get_piece(&coord).map_or('.', |piece| piece.letter());
+                print!("{}{}{} ", piece, count(&coord, true), count(&coord, false));
+                file += 1;
+            }
+            println!();
+            rank -= 1;
+        }
+
+        print!("     ");
+        let mut file = min.0.clone();
+        while file <= max.0 {
+            print!("{:<4}", file);
+            file += 1;
+        }
+        println!();
+    }
+
+    // Every piece attacking a square, with the squares they stand on
+    pub fn show_attackers(&self, target: &Coordinate) {
+        for white in [true, false] {
+            println!("{} attackers of {}: {}", if white { "White" } else { "Black" }, target, format_attackers(&self.attackers_of(target, white)));
+        }
     }
-}
\ No newline at end of file
+}
+
+pub fn format_attackers(attackers: &[(Coordinate, Piece)]) -> String {
+    if attackers.is_empty() {
+        return "none".to_string();
+    }
+    attackers.iter().map(|(coord, piece)| format!("{}{}", piece.letter(), coord)).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,50 @@
+// src/capabilities.rs
+use crate::board::Piece;
+use crate::output;
+use crate::pieces::piece_set;
+use crate::tools::MAX_VIEW;
+use crate::uci::MAX_THREADS;
+use crate::validate;
+use crate::variants::VARIANTS;
+
+// What this engine supports, for front-ends that talk to more than one infinite chess engine.
+// Every entry is a name and a list of values, so it reads the same in text and JSON.
+pub fn capabilities() -> Vec<(&'static str, Vec<String>)> {
+    let set = piece_set();
+    vec![
+        ("version", vec![env!("CARGO_PKG_VERSION").to_string()]),
+        ("protocols", vec!["uci".to_string(), "repl".to_string(), "http".to_string()]),
+        ("variants", VARIANTS.iter().map(|variant| variant.name.to_string()).collect()),
+        // letter and value of every kind in the active piece set
+        ("pieces", (0..set.defs.len()).map(|kind| {
+            let piece = Piece::from_kind(kind, true);
+            format!("{}={}", piece.letter(), set.def(piece).value)
+        }).collect()),
+        ("win conditions", vec!["checkmate".to_string(), "royalcapture".to_string()]),
+        ("rules", ["castling", "en passant", "promotion", "pawn start ranks", "coordinate limit", "threefold repetition", "fifty-move rule", "translated repetition"]
+            .iter().map(|rule| rule.to_string()).collect()),
+        ("notation", ["fen", "icn", "snapshot", "x,y>x,y", "x,y>inf:<direction>"].iter().map(|format| format.to_string()).collect()),
+        ("output", vec!["text".to_string(), "json".to_string()]),
+        ("limits", vec![
+            format!("command bytes {}", validate::MAX_COMMAND_LENGTH),
+            format!("number characters {}", validate::MAX_NUMBER_TEXT),
+            format!("coordinate bits {}", validate::MAX_COORDINATE_BITS),
+            format!("pieces {}", validate::MAX_PIECES),
+            format!("moves {}", validate::MAX_MOVES),
+            format!("threads {}", MAX_THREADS),
+            format!("view squares {}", MAX_VIEW),
+        ]),
+    ]
+}
+
+// One line per entry, `name: value, value`. Over UCI they go out as info strings so a GUI
+// that doesn't know the command just sees comments.
+pub fn report(as_info: bool) {
+    for (name, values) in capabilities() {
+        let text = format!("{}: {}", name, values.join(", "));
+        output::emit(&if as_info { format!("info string {}", text) } else { text }, "capability", &[
+            ("name", output::json_string(name)),
+            ("values", output::json_list(&values)),
+        ]);
+    }
+}
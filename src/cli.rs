@@ -0,0 +1,388 @@
+// src/cli.rs
+use std::time::Instant;
+use crate::board::{Anchor, Board};
+use crate::experience::Experience;
+use crate::notation::{self, format_move, parse_fen, parse_icn};
+use crate::search::Searcher;
+use crate::tools::legal_moves;
+use crate::tt::{TranspositionTable, MAX_HASH_MB};
+use crate::uci::{MAX_NPS, MAX_THREADS};
+use crate::evaluation::EvalParams;
+use crate::pieces::PieceSet;
+use crate::output::{self, OutputFormat};
+use std::sync::Arc;
+use clap::{Args, Parser, Subcommand};
+use crate::annotate::AnnotateArgs;
+use crate::arena::MatchArgs;
+use crate::bench::BenchArgs;
+use crate::golden::GoldenArgs;
+use crate::hash::HashStatsArgs;
+use crate::openings::OpeningsArgs;
+use crate::puzzles::PuzzlesArgs;
+use crate::rating::RatingsArgs;
+use crate::repertoire::TrainArgs;
+use crate::selfplay::SelfplayArgs;
+use crate::server::ServeArgs;
+use crate::tools::Tool;
+use crate::tournament::TournamentArgs;
+use crate::tune::TuneArgs;
+
+// The command line. Global flags may come before or after the command; those that configure
+// searchers are refused by commands that don't search, see Command::searches.
+#[derive(Parser, Debug)]
+#[command(name = "Infinity-Chess-Bot", version, about = "Chess engine for the infinite board")]
+pub struct Cli {
+    #[command(flatten)]
+    pub globals: GlobalArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct GlobalArgs {
+    #[arg(long, global = true, value_name = "N", value_parser = GlobalOptions::parse_threads, help = "search threads")]
+    pub threads: Option<usize>,
+    #[arg(long, global = true, value_name = "MB", value_parser = GlobalOptions::parse_hash, help = "transposition table size")]
+    pub hash: Option<usize>,
+    #[arg(long, global = true, value_name = "N", value_parser = GlobalOptions::parse_nps, help = "search at most N nodes a second, for weaker or fairer play")]
+    pub nps: Option<u64>,
+    #[arg(long, global = true, value_name = "FILE", help = "evaluation weights written by tune, for every position searched")]
+    pub params: Option<String>,
+    #[arg(long, global = true, value_name = "FILE", help = "copy the protocol conversation to a file")]
+    pub log_file: Option<String>,
+    #[arg(long, global = true, value_name = "text|json|compact", value_parser = output::parse_format, help = "output format, compact: one summary line per position")]
+    pub output: Option<OutputFormat>,
+    #[arg(long, global = true, value_name = "FILE", help = "piece definitions")]
+    pub pieces: Option<String>,
+    #[arg(long, global = true, help = "play: print files 1 to 8 of moves as a to h (e2>e4), e2e4 and e4 squares are always accepted")]
+    pub algebraic: bool,
+    #[arg(long, global = true, help = "check every generated move list against a reference generator, with --features verify")]
+    pub verify_movegen: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "speak UCI on stdin/stdout")]
+    Uci(UciArgs),
+    #[command(alias = "repl", about = "interactive board")]
+    Play,
+    #[command(about = "evaluate a position and search it for the best move")]
+    Analyze(AnalyzeArgs),
+    #[command(about = "count the leaves of the legal move tree")]
+    Perft(PerftArgs),
+    #[command(about = "fixed search for comparing builds")]
+    Bench(BenchArgs),
+    #[command(name = "match", about = "play games between two players")]
+    Match(MatchArgs),
+    #[command(about = "round robin or gauntlet between several players")]
+    Tournament(TournamentArgs),
+    #[command(about = "Elo of every configuration in a ratings file, random at 0")]
+    Ratings(RatingsArgs),
+    #[command(about = "the engine against itself from randomised starts")]
+    Selfplay(SelfplayArgs),
+    #[command(about = "fit piece values and king safety weights to played games, starting from --params")]
+    Tune(TuneArgs),
+    #[command(about = "HTTP server rendering positions and listing moves")]
+    Serve(ServeArgs),
+    #[command(alias = "about", about = "variants, pieces, rules, notations and limits this engine supports")]
+    Capabilities,
+    #[command(about = "compare every move of a game with the engine's choice")]
+    Annotate(AnnotateArgs),
+    #[command(about = "tactics from played games")]
+    Puzzles(PuzzlesArgs),
+    #[command(about = "practise a repertoire, or check the engine against it")]
+    Train(TrainArgs),
+    #[command(about = "balanced random start positions")]
+    Openings(OpeningsArgs),
+    #[command(about = "collision rates of the position hash")]
+    Hashstats(HashStatsArgs),
+    #[command(about = "write or check the movegen fixtures")]
+    Golden(GoldenArgs),
+    #[command(flatten)]
+    Tool(Tool),
+}
+
+impl Command {
+    // Whether the searcher flags (--threads, --hash, --nps, --params) mean anything to it.
+    // annotate, openings, train and latency search too, but with fixed settings of their own.
+    pub fn searches(&self) -> bool {
+        matches!(self, Command::Uci(_) | Command::Play | Command::Analyze(_) | Command::Bench(_) | Command::Match(_)
+            | Command::Tournament(_) | Command::Puzzles(_) | Command::Selfplay(_))
+    }
+}
+
+// Flags that configure every searcher the command creates
+#[derive(Debug, Clone, Default)]
+pub struct GlobalOptions {
+    pub threads: Option<usize>,
+    pub hash: Option<usize>, // MB
+    pub nps: Option<u64>, // cap on nodes a second, see Searcher::max_nps
+    pub params: Option<Arc<EvalParams>>, // evaluation weights, see Searcher::params
+    pub pieces: Option<Arc<PieceSet>>, // piece values, see Searcher::pieces
+    pub verify_movegen: bool, // see Searcher::verify_movegen
+}
+
+impl GlobalOptions {
+    pub fn parse_threads(value: &str) -> Result<usize, String> {
+        value.parse::<usize>().ok().filter(|threads| (1..=MAX_THREADS).contains(threads))
+            .ok_or_else(|| format!("must be between 1 and {}", MAX_THREADS))
+    }
+
+    pub fn parse_hash(value: &str) -> Result<usize, String> {
+        value.parse::<usize>().ok().filter(|mb| (1..=MAX_HASH_MB).contains(mb))
+            .ok_or_else(|| format!("must be between 1 and {} MB", MAX_HASH_MB))
+    }
+
+    pub fn parse_nps(value: &str) -> Result<u64, String> {
+        value.parse::<u64>().ok().filter(|nps| (1..=MAX_NPS).contains(nps)).ok_or_else(|| format!("must be between 1 and {} nodes a second", MAX_NPS))
+    }
+
+    pub fn apply(&self, searcher: &mut Searcher) {
+        if let Some(nps) = self.nps {
+            searcher.max_nps = Some(nps);
+        }
+        if let Some(threads) = self.threads {
+            searcher.threads = threads;
+        }
+        if let Some(mb) = self.hash {
+            searcher.tt = TranspositionTable::with_mb(mb);
+        }
+        if let Some(params) = &self.params {
+            searcher.params = Some(params.clone());
+        }
+        if let Some(pieces) = &self.pieces {
+            searcher.pieces = Some(pieces.clone());
+        }
+        searcher.verify_movegen = self.verify_movegen;
+    }
+
+    // The same for a board that is used without a searcher
+    pub fn apply_rules(&self, board: &mut Board) {
+        if let Some(params) = &self.params {
+            board.rules.eval = params.clone();
+        }
+        if let Some(pieces) = &self.pieces {
+            board.rules.pieces = pieces.clone();
+        }
+        board.rules.verify_movegen = self.verify_movegen;
+    }
+}
+
+// The searcher flags given to a command that can't use them, and --algebraic anywhere but play.
+// `name` is the command as it was typed.
+pub fn check_globals(globals: &GlobalArgs, command: &Command, name: &str) -> Result<(), String> {
+    let given = [("--threads", globals.threads.is_some()), ("--hash", globals.hash.is_some()), ("--nps", globals.nps.is_some())];
+    for (flag, given) in given {
+        if given && !command.searches() {
+            return Err(format!("{} only applies to commands that search, not to {}", flag, name));
+        }
+    }
+    if globals.params.is_some() && !command.searches() && !matches!(command, Command::Tune(_)) {
+        return Err(format!("--params only applies to tune and to commands that search, not to {}", name));
+    }
+    if globals.verify_movegen && !command.searches() && !matches!(command, Command::Perft(_)) {
+        return Err(format!("--verify-movegen only applies to perft and to commands that search, not to {}", name));
+    }
+    if globals.algebraic && !matches!(command, Command::Play) {
+        return Err(format!("--algebraic only applies to play, not to {}", name));
+    }
+    Ok(())
+}
+
+// A position is --fen TEXT or --icn TEXT, the start position without either
+#[derive(Args, Debug, Clone, Default)]
+pub struct PositionArgs {
+    #[arg(long, conflicts_with = "icn", help = "position in extended FEN")]
+    pub fen: Option<String>,
+    #[arg(long, help = "position in compressed ICN")]
+    pub icn: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct UciArgs {
+    #[arg(long, value_name = "FILE", help = "root results remembered across games, used like a book")]
+    pub experience: Option<String>,
+}
+
+impl PositionArgs {
+    pub fn board(&self) -> Result<Board, String> {
+        match (&self.fen, &self.icn) {
+            (Some(fen), _) => parse_fen(fen),
+            (None, Some(icn)) => parse_icn(icn),
+            (None, None) => Ok(Board::new()),
+        }
+    }
+}
+
+// Leaf count of the legal move tree, each infinite slide counted as the one move movegen makes of it
+pub fn perft(board: &mut Board, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for mv in moves {
+        board.make(mv.clone());
+        nodes += perft(board, depth - 1);
+        board.unmake(mv);
+    }
+    nodes
+}
+
+#[derive(Args, Debug)]
+pub struct PerftArgs {
+    #[command(flatten)]
+    pub position: PositionArgs,
+    #[arg(long, default_value_t = 3)]
+    pub depth: u8,
+    #[arg(long, help = "the count below each root move")]
+    pub divide: bool,
+}
+
+pub fn run_perft(args: &PerftArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (depth, divide) = (args.depth, args.divide);
+    let mut board = args.position.board()?;
+    options.apply_rules(&mut board);
+    let start = Instant::now();
+    let nodes = if divide && depth > 0 {
+        let mut nodes = 0;
+        for mv in legal_moves(&mut board) {
+            board.make(mv.clone());
+            let count = perft(&mut board, depth - 1);
+            board.unmake(mv.clone());
+            println!("{} {}", format_move(&mv, None, false), count);
+            nodes += count;
+        }
+        nodes
+    } else {
+        perft(&mut board, depth)
+    };
+    let time = start.elapsed().as_millis();
+    println!("{} nodes {} ms {} nps", nodes, time, nodes as u128 * 1000 / time.max(1));
+    Ok(())
+}
+
+// whiteking, blackking or a square
+pub fn parse_anchor(text: &str) -> Result<Anchor, String> {
+    match text {
+        "whiteking" => Ok(Anchor::WhiteKing),
+        "blackking" => Ok(Anchor::BlackKing),
+        square => Ok(Anchor::Square(notation::parse_coordinate(square)?)),
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    #[command(flatten)]
+    pub position: PositionArgs,
+    #[arg(long, help = "search depth, 6 without a --movetime")]
+    pub depth: Option<u8>,
+    #[arg(long, value_name = "MS", default_value_t = -1, allow_negative_numbers = true)]
+    pub movetime: i32,
+    #[arg(long, value_name = "SQUARE|whiteking|blackking", value_parser = parse_anchor, help = "print coordinates relative to this square")]
+    pub relative: Option<Anchor>,
+    #[arg(long, value_name = "FILE", help = "experience file answering positions analysed before")]
+    pub cache: Option<String>,
+}
+
+pub fn run_analyze(args: &AnalyzeArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (anchor, movetime) = (args.relative.clone().unwrap_or(Anchor::Origin), args.movetime);
+    // without a limit the search would never return
+    let depth = args.depth.unwrap_or(if movetime < 0 { 6 } else { u8::MAX });
+    let mut cache = args.cache.as_deref().map(Experience::load).transpose()?;
+
+    let mut board = args.position.board()?;
+    // the printed evaluation and the cache go by the weights the search uses
+    options.apply_rules(&mut board);
+    board.show_relative(true, &anchor);
+    println!("Evaluation: {:?} (white {:?})", board.evaluate(), board.evaluate_white_relative());
+
+    // the cache answers positions analysed at least as deep before, by this or an earlier run;
+    // with only a movetime there is no depth to compare, so any cached result does
+    let wanted = if depth == u8::MAX { 1 } else { depth };
+    if let Some((best_move, entry)) = cache.as_ref().and_then(|cache| cache.probe(&board, wanted)) {
+        println!("Best move: {} (cached, depth {} score {})", format_move(&best_move, anchor.resolve(&board).as_ref(), false), entry.depth, entry.score);
+        return Ok(());
+    }
+
+    let mut searcher = Searcher::new();
+    options.apply(&mut searcher);
+    searcher.anchor = anchor;
+    searcher.movetime = movetime;
+    let result = searcher.search_position(&mut board, depth);
+    println!("Best move: {}", format_move(&result.best_move, searcher.anchor.resolve(&board).as_ref(), false));
+    if let Some(cache) = &mut cache {
+        cache.record(&board, &result.best_move, result.score, result.depth);
+        cache.save()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Piece;
+    use crate::evaluation::DEFAULT_EVAL;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn parse(line: &str) -> Result<Cli, String> {
+        let matches = Cli::command().try_get_matches_from(std::iter::once("Infinity-Chess-Bot").chain(line.split_whitespace())).map_err(|err| err.to_string())?;
+        let cli = Cli::from_arg_matches(&matches).map_err(|err| err.to_string())?;
+        check_globals(&cli.globals, cli.command.as_ref().unwrap(), matches.subcommand_name().unwrap_or_default())?;
+        Ok(cli)
+    }
+
+    #[test]
+    fn global_flags_anywhere_and_only_where_they_apply() {
+        let cli = parse("--threads 2 match engine random --hash 64 --games 4 --nps 5000").unwrap();
+        assert!(matches!(cli.command, Some(Command::Match(_))));
+        assert_eq!((cli.globals.threads, cli.globals.hash, cli.globals.nps), (Some(2), Some(64), Some(5000)));
+
+        // the global --output is the format, annotate's file is --out
+        let cli = parse("annotate game.txt --out notes.txt --output json").unwrap();
+        assert_eq!(cli.globals.output, Some(OutputFormat::Json));
+        assert!(matches!(cli.command, Some(Command::Annotate(AnnotateArgs { out: Some(ref out), .. })) if out == "notes.txt"));
+
+        assert!(parse("tune positions.txt --params weights.txt").is_ok());
+        // switches that take a value are set by it, not by being given
+        assert!(matches!(parse("match engine random --adjudicate off").unwrap().command, Some(Command::Match(MatchArgs { adjudicate: false, repetition: false, .. }))));
+        assert!(matches!(parse("match engine random --repetition translated").unwrap().command, Some(Command::Match(MatchArgs { adjudicate: true, repetition: true, .. }))));
+        assert!(parse("selfplay --games 2 --threads 2").is_ok());
+        for (line, error) in [
+            ("perft --threads 2", "--threads only applies to commands that search"),
+            ("ratings r.txt --nps 100", "--nps only applies"),
+            ("tune positions.txt --hash 16", "--hash only applies"),
+            ("golden check f --params p", "--params only applies"),
+            ("uci --algebraic", "--algebraic only applies"),
+            ("bench --hash", "a value is required for '--hash"),
+            ("bench --hash 0", "must be between 1 and"),
+            ("bench --hash 99999999999", "must be between 1 and"),
+            ("bench --threads 100000", "must be between 1 and"),
+            ("bench --nps 0", "must be between 1 and"),
+            ("bench --nps 999999999999", "must be between 1 and"),
+            ("annotate game.txt --output notes.txt", "invalid value 'notes.txt'"),
+        ] {
+            let err = parse(line).err().unwrap_or_else(|| panic!("{} was accepted", line));
+            assert!(err.contains(error), "{}: {}", line, err);
+        }
+    }
+
+    #[test]
+    fn params_files_round_trip() {
+        let set = PieceSet::classic();
+        let params = EvalParams::parse("# weights\nname tuned\nknight 310\nsafe_check 7\n", &set).unwrap();
+        assert_eq!(params.name, "tuned");
+        assert_eq!(params.value(Piece::BlackKnight, &set), 310);
+        assert_eq!(params.value(Piece::WhiteRook, &set), set.def(Piece::WhiteRook).value as i32);
+        assert_eq!((params.king_zone_attack, params.safe_check), (DEFAULT_EVAL.king_zone_attack, 7));
+        assert_eq!(EvalParams::parse(&params.format(), &set).unwrap(), params);
+        assert_eq!(EvalParams::parse(&DEFAULT_EVAL.format(), &set).unwrap().piece_values, None);
+
+        for text in ["knight", "knight 3.5", "unicorn 300", "safe_check 1 2"] {
+            assert!(EvalParams::parse(text, &set).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// src/clock.rs
+use std::time::Instant;
+use crate::search::Searcher;
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub white_time: i64, // remaining time in milliseconds
+    pub black_time: i64,
+    pub white_inc: i64, // increment added after each completed move
+    pub black_inc: i64,
+    pub white_to_move: bool,
+    pub turn_started: Option<Instant>, // None while the clock is paused
+}
+
+impl Clock {
+    pub fn new(base: i64, inc: i64) -> Self {
+        Clock {
+            white_time: base,
+            black_time: base,
+            white_inc: inc,
+            black_inc: inc,
+            white_to_move: true,
+            turn_started: None,
+        }
+    }
+
+    pub fn start(&mut self, white_to_move: bool) {
+        self.white_to_move = white_to_move;
+        self.turn_started = Some(Instant::now());
+    }
+
+    fn elapsed(&self) -> i64 {
+        self.turn_started.map_or(0, |started| started.elapsed().as_millis() as i64)
+    }
+
+    fn time_mut(&mut self, white: bool) -> &mut i64 {
+        if white { &mut self.white_time } else { &mut self.black_time }
+    }
+
+    // Called when the side to move completes its move: charge the time used, add the
+    // increment (unless the flag already fell) and start the opponent's clock.
+    // Returns false if the mover ran out of time.
+    pub fn press(&mut self) -> bool {
+        let white = self.white_to_move;
+        let running = self.turn_started.is_some();
+        let elapsed = self.elapsed();
+        let inc = if white { self.white_inc } else { self.black_inc };
+
+        let remaining = self.time_mut(white);
+        *remaining -= elapsed;
+        let in_time = *remaining > 0;
+        if in_time {
+            *remaining += inc;
+        }
+
+        self.white_to_move = !white;
+        self.turn_started = if running { Some(Instant::now()) } else { None };
+        in_time
+    }
+
+    // Remaining time of a side including the running turn
+    pub fn remaining(&self, white: bool) -> i64 {
+        let stored = if white { self.white_time } else { self.black_time };
+        if white == self.white_to_move { stored - self.elapsed() } else { stored }
+    }
+
+    // Side whose flag has fallen, if any (true for white)
+    pub fn flagged(&self) -> Option<bool> {
+        [true, false].into_iter().find(|&white| self.remaining(white) <= 0)
+    }
+
+    // Hand the mover's remaining time and increment to the searcher's time manager
+    pub fn configure(&self, searcher: &mut Searcher) {
+        let white = self.white_to_move;
+        searcher.playtime = self.remaining(white).max(0) as i32;
+        searcher.inc = (if white { self.white_inc } else { self.black_inc }) as i32;
+        searcher.movetime = -1;
+    }
+}
@@ -0,0 +1,708 @@
+// src/evaluation.rs
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
+use num_bigint::BigInt;
+use num_traits::Signed;
+use crate::board::{Board, Coordinate, Phase, Piece};
+use crate::geometry::{Direction, KING_OFFSETS, KNIGHT_OFFSETS};
+use crate::hash::piece_key;
+use crate::pieces::{PieceSet, PIECE_NAMES};
+use crate::r#move::{Move, MoveGen, MoveList, BLACK_PROMOTION_RANK, WHITE_PROMOTION_RANK};
+
+// Mobility credited for an unobstructed infinite slide
+pub const INFINITE_MOVE_MOBILITY: i32 = 8;
+// Pieces with at most this many moves are considered trapped
+pub const TRAPPED_MOBILITY: i32 = 1;
+// Leapers further than this from every king and pawn no longer take part in the game
+pub const OFFSIDE_LEAPER_DISTANCE: i64 = 8;
+// Sliders act at a distance, so they are only offside when far away and off every king's lines
+pub const OFFSIDE_SLIDER_DISTANCE: i64 = 64;
+
+// Two heavy pieces on adjacent lines on one side of the enemy king (ladder mate)
+pub const LADDER_BONUS: i32 = 40;
+// Extra bonus per line the ladder is closer to the king than LADDER_REACH
+pub const LADDER_CLOSENESS_BONUS: i32 = 6;
+pub const LADDER_REACH: i64 = 8;
+
+// Squares counted by the confinement flood fill before giving up
+pub const CONFINEMENT_CAP: i32 = 48;
+// Bonus per square the enemy king's region is smaller than the cap
+pub const CONFINEMENT_WEIGHT: i32 = 4;
+
+// Passed pawn bonus, plus a bonus per rank closer than PASSED_PAWN_REACH to promotion
+pub const PASSED_PAWN_BONUS: i32 = 20;
+pub const PASSED_PAWN_RANK_BONUS: i32 = 10;
+pub const PASSED_PAWN_REACH: i64 = 6;
+// A pawn the enemy king cannot catch is worth most of a queen
+pub const UNSTOPPABLE_PAWN_BONUS: i32 = 800;
+
+// Pawn structure: passed pawns side by side, pawns defended by a pawn, and pawns left behind
+// by their neighbours whose advance square an enemy pawn controls
+pub const CONNECTED_PASSER_BONUS: i32 = 25;
+pub const PAWN_CHAIN_BONUS: i32 = 8;
+pub const BACKWARD_PAWN_PENALTY: i32 = 12;
+// Pawn structure scores a PawnHash keeps, cleared when full
+pub const PAWN_HASH_ENTRIES: usize = 1 << 16;
+
+// Evaluations are multiplied by scale / SCALE_NORMAL in drawish material configurations
+pub const SCALE_NORMAL: i32 = 64;
+pub const OPPOSITE_BISHOPS_SCALE: i32 = 24;
+// Without pawns and without a corner to drive the king into, neither side can force mate
+pub const ROOK_VS_ROOK_SCALE: i32 = 4;
+// A likely fortress: an endgame this many plies past the last capture or pawn move. Its score is
+// pulled toward a draw by FORTRESS_NUDGE percent and another percent each ply past FORTRESS_PLIES,
+// up to MAX_FORTRESS_NUDGE, and half that while the side ahead still has pawns to make progress with
+pub const FORTRESS_PLIES: u32 = 40;
+pub const FORTRESS_NUDGE: i32 = 25;
+pub const MAX_FORTRESS_NUDGE: i32 = 75;
+
+// Penalty per enemy attack on a square next to a king still on its home square
+pub const KING_ZONE_ATTACK_PENALTY: i32 = 8;
+// Penalty per check the enemy could give next move on a square the king's side doesn't cover
+pub const SAFE_CHECK_PENALTY: i32 = 25;
+
+// Opening terms, applied while a side still has most of its pieces
+pub const DEVELOPED_MINOR_BONUS: i32 = 15;
+pub const CASTLING_RIGHT_BONUS: i32 = 10;
+pub const CASTLED_BONUS: i32 = 40;
+pub const EARLY_ROOK_SORTIE_PENALTY: i32 = 30;
+pub const OPENING_PIECES: usize = 5;
+// The classical setup spans files 1-8 and ranks 1-8; the action region adds this margin
+pub const ACTION_MARGIN: i64 = 2;
+
+// Penalty per square between a side's piece centre of mass and the action region's centre
+pub const DRIFT_WEIGHT: i32 = 2;
+pub const DRIFT_CAP: i64 = 50;
+
+// Weights that differ between variants. A variant's profile is put on the board's rules when
+// its start position is set up, every other position evaluates with DEFAULT_EVAL unless a
+// searcher was given its own (`--params`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    pub name: Cow<'static, str>,
+    pub piece_values: Option<[i16; 6]>, // by kind, None for the values of the piece set
+    pub king_zone_attack: i32, // see KING_ZONE_ATTACK_PENALTY
+    pub safe_check: i32, // see SAFE_CHECK_PENALTY
+}
+
+pub const DEFAULT_EVAL: EvalParams = EvalParams {
+    name: Cow::Borrowed("default"),
+    piece_values: None,
+    king_zone_attack: KING_ZONE_ATTACK_PENALTY,
+    safe_check: SAFE_CHECK_PENALTY,
+};
+
+impl EvalParams {
+    pub fn value(&self, piece: Piece, set: &PieceSet) -> i32 {
+        match self.piece_values {
+            Some(values) => values[piece.kind()] as i32,
+            None => set.def(piece).value as i32,
+        }
+    }
+
+    // MVV-LVA order of a capture in this profile's values, ranked like PieceSet::capture_order
+    // which it falls back to when the profile keeps the piece set's values
+    pub fn capture_order(&self, victim: Piece, attacker: Piece, set: &PieceSet) -> u32 {
+        let Some(values) = self.piece_values else { return set.capture_order(victim, attacker) };
+        let worth = |kind: usize| if kind == Piece::WhiteKing.kind() { i16::MAX } else { values[kind] };
+        let rank = |kind: usize| (0..6).filter(|other| worth(*other) < worth(kind)).count() as u32;
+        rank(victim.kind()) * 6 + 5 - rank(attacker.kind())
+    }
+
+    // Parameter files hold one `<name> <value>` line per weight that differs from DEFAULT_EVAL:
+    // `name <profile>`, a piece name from PIECE_NAMES with its value, `king_zone_attack <cp>`
+    // and `safe_check <cp>`. Blank lines and lines starting with '#' are ignored. Pieces a file
+    // leaves out keep their value in `set`.
+    pub fn parse(text: &str, set: &PieceSet) -> Result<Self, String> {
+        let mut params = EvalParams { name: Cow::Borrowed("custom"), ..DEFAULT_EVAL };
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let [key, value] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err(format!("line {}: expected '<name> <value>'", line_number + 1));
+            };
+            let invalid = || format!("line {}: invalid value '{}' for {}", line_number + 1, value, key);
+            match key {
+                "name" => params.name = Cow::Owned(value.to_string()),
+                "king_zone_attack" => params.king_zone_attack = value.parse().map_err(|_| invalid())?,
+                "safe_check" => params.safe_check = value.parse().map_err(|_| invalid())?,
+                _ => {
+                    let kind = PIECE_NAMES.iter().position(|name| *name == key)
+                        .ok_or_else(|| format!("line {}: unknown parameter '{}'", line_number + 1, key))?;
+                    let values = params.piece_values.get_or_insert_with(|| std::array::from_fn(|kind| set.defs[kind].value));
+                    values[kind] = value.parse().map_err(|_| invalid())?;
+                }
+            }
+        }
+        Ok(params)
+    }
+
+    pub fn load(path: &str, set: &PieceSet) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Self::parse(&text, set).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    // Every weight, in the form parse reads back
+    pub fn format(&self) -> String {
+        let mut text = format!("name {}\n", self.name);
+        if let Some(values) = self.piece_values {
+            for (name, value) in PIECE_NAMES.iter().zip(values) {
+                text.push_str(&format!("{} {}\n", name, value));
+            }
+        }
+        text.push_str(&format!("king_zone_attack {}\nsafe_check {}\n", self.king_zone_attack, self.safe_check));
+        text
+    }
+}
+
+// All positional terms from white's point of view, added on top of material
+pub fn positional_score(board: &Board, pawns: &mut PawnHash) -> i32 {
+    let lines = Lines::new(board);
+    trapped_and_offside(board, &lines)
+        + ladder_score(board, true) - ladder_score(board, false)
+        + confinement_score(board, &lines, true) - confinement_score(board, &lines, false)
+        + passed_pawn_score(board, true) - passed_pawn_score(board, false)
+        + pawn_structure(board, pawns)
+        - king_pressure(board, &lines, true) + king_pressure(board, &lines, false)
+        + development_score(board, true) - development_score(board, false)
+        - drift_penalty(board, true) + drift_penalty(board, false)
+}
+
+pub fn distance(a: &Coordinate, b: &Coordinate) -> BigInt {
+    a.chebyshev(b)
+}
+
+pub fn is_slider(piece: Piece) -> bool {
+    matches!(piece, Piece::WhiteRook | Piece::WhiteBishop | Piece::WhiteQueen | Piece::BlackRook | Piece::BlackBishop | Piece::BlackQueen)
+}
+
+pub fn is_pawn_or_king(piece: Piece) -> bool {
+    matches!(piece, Piece::WhitePawn | Piece::WhiteKing | Piece::BlackPawn | Piece::BlackKing)
+}
+
+// Every piece bucketed by the rank, file or diagonal it stands on and sorted along it, built
+// once per evaluation so the terms below find blockers and attackers with a binary search
+// instead of scanning the whole board for every piece and square
+pub struct Lines<'a> {
+    pub lines: HashMap<(u8, BigInt), Line<'a>>,
+}
+
+// The pieces on one line with their positions along it
+pub type Line<'a> = Vec<(BigInt, &'a Coordinate, Piece)>;
+
+impl<'a> Lines<'a> {
+    pub fn new(board: &'a Board) -> Lines<'a> {
+        let mut lines: HashMap<(u8, BigInt), Line> = HashMap::new();
+        for (coord, piece) in &board.state {
+            for direction in [Direction::Right, Direction::Top, Direction::TopRight, Direction::BottomRight] {
+                let (line, position) = Self::line(coord, direction);
+                lines.entry(line).or_default().push((position, coord, *piece));
+            }
+        }
+        for pieces in lines.values_mut() {
+            pieces.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Lines { lines }
+    }
+
+    // The line through `coord` along `direction`, and where on it `coord` lies. Positions grow
+    // with the file, or with the rank on a file.
+    fn line(coord: &Coordinate, direction: Direction) -> ((u8, BigInt), BigInt) {
+        match direction {
+            Direction::Left | Direction::Right => ((0, coord.1.clone()), coord.0.clone()),
+            Direction::Top | Direction::Bottom => ((1, coord.0.clone()), coord.1.clone()),
+            Direction::TopRight | Direction::BottomLeft => ((2, &coord.0 - &coord.1), coord.0.clone()),
+            Direction::TopLeft | Direction::BottomRight => ((3, &coord.0 + &coord.1), coord.0.clone()),
+        }
+    }
+
+    // Same answer as Board::nearest_piece_in_direction
+    pub fn nearest(&self, from: &Coordinate, direction: Direction) -> Option<(BigInt, &'a Coordinate, Piece)> {
+        let (line, position) = Self::line(from, direction);
+        let pieces = self.lines.get(&line)?;
+        let offset = direction.offset();
+        let forward = if matches!(direction, Direction::Top | Direction::Bottom) { offset.dy > 0 } else { offset.dx > 0 };
+        let found = if forward {
+            pieces.get(pieces.partition_point(|(along, _, _)| *along <= position))
+        } else {
+            pieces.partition_point(|(along, _, _)| *along < position).checked_sub(1).and_then(|index| pieces.get(index))
+        };
+        found.map(|(along, coord, piece)| ((along - &position).abs(), *coord, *piece))
+    }
+
+    // Same answer as Board::attacks_square
+    pub fn attacks_square(&self, board: &Board, from: &Coordinate, piece: Piece, target: &Coordinate) -> bool {
+        if !is_slider(piece) {
+            return board.attacks_square(from, piece, target);
+        }
+        let Some((direction, distance)) = from.between(target) else { return false };
+        slides_along(piece, direction) && self.nearest(from, direction).is_none_or(|(along, _, _)| along >= distance)
+    }
+
+    // How many pieces of one side attack `target`: the nearest piece on each ray if it slides
+    // that way, and the leapers on the squares around it
+    pub fn attackers(&self, board: &Board, target: &Coordinate, by_white: bool) -> i32 {
+        let sliders = Direction::ALL.into_iter()
+            .filter(|direction| self.nearest(target, *direction).is_some_and(|(_, _, piece)| piece.is_white() == by_white && is_slider(piece) && slides_along(piece, *direction)))
+            .count();
+        let leapers = KING_OFFSETS.into_iter().chain(KNIGHT_OFFSETS)
+            .map(|offset| target + offset)
+            .filter(|square| board.get_piece(square).is_some_and(|piece| piece.is_white() == by_white && !is_slider(*piece) && board.attacks_square(square, *piece, target)))
+            .count();
+        (sliders + leapers) as i32
+    }
+
+    // Same as piece_mobility, with the slider rays taken from the lines
+    pub fn mobility(&self, board: &Board, coord: &Coordinate, piece: Piece) -> i32 {
+        if !is_slider(piece) {
+            return piece_mobility(board, coord, piece);
+        }
+        Direction::ALL.into_iter()
+            .filter(|direction| slides_along(piece, *direction))
+            .map(|direction| match self.nearest(coord, direction) {
+                Some((_, _, target)) => (target.is_white() != piece.is_white()) as i32,
+                None => match board.rules.steps_within_limit(coord, direction) {
+                    None => INFINITE_MOVE_MOBILITY,
+                    Some(steps) => steps.is_positive() as i32,
+                },
+            })
+            .sum()
+    }
+
+    // The empty squares a slider can stop on: only the last square before a coordinate limit,
+    // since it otherwise either captures or leaves towards infinity
+    pub fn slide_stops(&self, board: &Board, coord: &Coordinate, piece: Piece) -> Vec<Coordinate> {
+        Direction::ALL.into_iter()
+            .filter(|direction| slides_along(piece, *direction) && self.nearest(coord, *direction).is_none())
+            .filter_map(|direction| board.rules.steps_within_limit(coord, direction).filter(|steps| steps.is_positive()).map(|steps| coord.step(direction, &steps)))
+            .collect()
+    }
+}
+
+pub fn slides_along(piece: Piece, direction: Direction) -> bool {
+    match piece {
+        Piece::WhiteRook | Piece::BlackRook => direction.is_orthogonal(),
+        Piece::WhiteBishop | Piece::BlackBishop => !direction.is_orthogonal(),
+        _ => is_slider(piece),
+    }
+}
+
+// Number of pseudo-legal moves of the piece, an infinite slide counting INFINITE_MOVE_MOBILITY
+pub fn piece_mobility(board: &Board, coord: &Coordinate, piece: Piece) -> i32 {
+    let mut move_list = MoveList::new();
+    MoveGen::generate_piece_moves(board, coord.clone(), piece, &mut move_list);
+    move_list.moves[..move_list.count as usize].iter()
+        .map(|mv| if matches!(mv, Move::InfiniteMove(..)) { INFINITE_MOVE_MOBILITY } else { 1 })
+        .sum()
+}
+
+// Penalise pieces that can barely move, and pieces that wandered so far from both kings and
+// all pawns that they no longer matter even though their material still counts in full
+pub fn trapped_and_offside(board: &Board, lines: &Lines) -> i32 {
+    // kings and pawns by file, then rank, so the ones near a piece are a range lookup
+    let mut anchors: BTreeMap<&BigInt, BTreeSet<&BigInt>> = BTreeMap::new();
+    for (coord, _) in board.state.iter().filter(|(_, piece)| is_pawn_or_king(**piece)) {
+        anchors.entry(&coord.0).or_default().insert(&coord.1);
+    }
+    let kings: Vec<&Coordinate> = board.state.iter()
+        .filter(|(_, piece)| matches!(piece, Piece::WhiteKing | Piece::BlackKing))
+        .map(|(coord, _)| coord)
+        .collect();
+
+    let mut score = 0;
+    for (coord, piece) in &board.state {
+        if is_pawn_or_king(*piece) {
+            continue;
+        }
+        let value = board.rules.value(*piece);
+        let mut penalty = 0;
+
+        if lines.mobility(board, coord, *piece) <= TRAPPED_MOBILITY {
+            penalty += value / 8;
+        }
+
+        let limit = BigInt::from(if is_slider(*piece) { OFFSIDE_SLIDER_DISTANCE } else { OFFSIDE_LEAPER_DISTANCE });
+        let (files, ranks) = (&coord.0 - &limit..=&coord.0 + &limit, &coord.1 - &limit..=&coord.1 + &limit);
+        let far = anchors.range::<BigInt, _>(files).all(|(_, anchor_ranks)| anchor_ranks.range::<BigInt, _>(ranks.clone()).next().is_none());
+        let offside = !anchors.is_empty() && far
+            && !(is_slider(*piece) && kings.iter().any(|king| coord.aligned_with(king)));
+        if offside {
+            penalty += value / 4;
+        }
+
+        score += if piece.is_white() { -penalty } else { penalty };
+    }
+
+    score
+}
+
+pub fn is_heavy(piece: Piece) -> bool {
+    matches!(piece, Piece::WhiteRook | Piece::WhiteQueen | Piece::BlackRook | Piece::BlackQueen)
+}
+
+pub fn find_king(board: &Board, white: bool) -> Option<&Coordinate> {
+    let king = if white { Piece::WhiteKing } else { Piece::BlackKing };
+    board.state.iter().find(|(_, piece)| **piece == king).map(|(coord, _)| coord)
+}
+
+// Bonus for the side's rooks and queens forming the ladder-mate construction against the
+// enemy king: two of them on adjacent ranks (or files), both on the same side of the king,
+// cutting it off from one half of the board
+pub fn ladder_score(board: &Board, white: bool) -> i32 {
+    let Some(king) = find_king(board, !white) else { return 0 };
+    let heavies: Vec<&Coordinate> = board.state.iter()
+        .filter(|(_, piece)| is_heavy(**piece) && piece.is_white() == white)
+        .map(|(coord, _)| coord)
+        .collect();
+
+    let mut best = 0;
+    for (index, first) in heavies.iter().enumerate() {
+        for second in &heavies[index + 1..] {
+            // ranks, then files
+            for (a, b, k) in [(&first.1, &second.1, &king.1), (&first.0, &second.0, &king.0)] {
+                if (a - b).abs() != BigInt::from(1) || (a > k) != (b > k) || a == k || b == k {
+                    continue;
+                }
+                let gap = (a - k).abs().min((b - k).abs());
+                let closeness = (BigInt::from(LADDER_REACH) - gap).max(BigInt::from(0));
+                let closeness: i32 = closeness.try_into().unwrap_or(0);
+                best = best.max(LADDER_BONUS + closeness * LADDER_CLOSENESS_BONUS);
+            }
+        }
+    }
+
+    best
+}
+
+// Number of squares the king of the given side can walk to without stepping onto attacked
+// or own-occupied squares, counting at most `cap`
+pub fn king_region(board: &Board, lines: &Lines, white: bool, cap: i32) -> i32 {
+    let Some(king) = find_king(board, white) else { return cap };
+    let mut seen = HashSet::from([king.clone()]);
+    let mut queue = VecDeque::from([king.clone()]);
+    let mut region = 0;
+
+    while let Some(square) = queue.pop_front() {
+        for offset in KING_OFFSETS {
+            let next = &square + offset;
+            if seen.contains(&next) {
+                continue;
+            }
+            seen.insert(next.clone());
+
+            let own_piece = board.get_piece(&next).is_some_and(|piece| piece.is_white() == white);
+            if own_piece || lines.attackers(board, &next, !white) > 0 {
+                continue;
+            }
+
+            region += 1;
+            if region >= cap {
+                return cap;
+            }
+            queue.push_back(next);
+        }
+    }
+
+    region
+}
+
+// Reward shrinking the area the enemy king can reach: without board edges, confining the
+// king is what wins, and material alone cannot tell a net from scattered pieces
+pub fn confinement_score(board: &Board, lines: &Lines, white: bool) -> i32 {
+    let has_attackers = board.state.values().any(|piece| piece.is_white() == white && is_heavy(*piece));
+    if !has_attackers {
+        return 0;
+    }
+    (CONFINEMENT_CAP - king_region(board, lines, !white, CONFINEMENT_CAP)) * CONFINEMENT_WEIGHT
+}
+
+// No enemy pawn ahead of it on its own or an adjacent file, and it can still promote
+pub fn is_passed_pawn(board: &Board, coord: &Coordinate, white: bool) -> bool {
+    let promotion_rank = BigInt::from(if white { WHITE_PROMOTION_RANK } else { BLACK_PROMOTION_RANK });
+    if (white && coord.1 >= promotion_rank) || (!white && coord.1 <= promotion_rank) {
+        return false;
+    }
+    let enemy_pawn = if white { Piece::BlackPawn } else { Piece::WhitePawn };
+    !board.state.iter().any(|(other, piece)| {
+        *piece == enemy_pawn
+            && (&other.0 - &coord.0).abs() <= BigInt::from(1)
+            && if white { other.1 > coord.1 } else { other.1 < coord.1 }
+    })
+}
+
+// Passed pawns, with the square-of-the-pawn rule deciding races against a lone king: the
+// pawn is unstoppable when it needs fewer moves to promote than the defending king needs
+// to reach the promotion square. Any other defending piece can stop a pawn from any
+// distance on an open infinite board, so the race is only judged against king and pawns.
+pub fn passed_pawn_score(board: &Board, white: bool) -> i32 {
+    let pawn = if white { Piece::WhitePawn } else { Piece::BlackPawn };
+    let start_rank = BigInt::from(if white { 2 } else { 7 });
+    let promotion_rank = BigInt::from(if white { WHITE_PROMOTION_RANK } else { BLACK_PROMOTION_RANK });
+    let defender_king = find_king(board, !white);
+    let lone_defender = board.state.values().all(|piece| piece.is_white() == white || is_pawn_or_king(*piece));
+    let defender_to_move = board.side_to_move != white;
+
+    let mut score = 0;
+    for (coord, _) in board.state.iter().filter(|(_, piece)| **piece == pawn) {
+        if !is_passed_pawn(board, coord, white) {
+            continue;
+        }
+
+        let mut pawn_moves = (&promotion_rank - &coord.1).abs();
+        if coord.1 == start_rank {
+            pawn_moves -= 1;
+        }
+        let closeness = (BigInt::from(PASSED_PAWN_REACH) - &pawn_moves).max(BigInt::from(0));
+        score += PASSED_PAWN_BONUS + i32::try_from(closeness).unwrap_or(0) * PASSED_PAWN_RANK_BONUS;
+
+        if lone_defender {
+            let promotion_square = Coordinate(coord.0.clone(), promotion_rank.clone());
+            let unstoppable = match defender_king {
+                Some(king) => {
+                    let mut king_moves = distance(king, &promotion_square);
+                    if defender_to_move {
+                        king_moves -= 1;
+                    }
+                    pawn_moves < king_moves
+                }
+                None => true,
+            };
+            if unstoppable {
+                score += UNSTOPPABLE_PAWN_BONUS;
+            }
+        }
+    }
+
+    score
+}
+
+// How much of the evaluation to keep, out of SCALE_NORMAL, so the engine doesn't trade into a
+// dead draw while nominally ahead
+pub fn endgame_scale(board: &Board) -> i32 {
+    if board.phase() != Phase::Endgame {
+        return SCALE_NORMAL;
+    }
+    let count = |piece: Piece| board.state.values().filter(|other| **other == piece).count();
+    let pieces = board.state.values().filter(|piece| !is_pawn_or_king(**piece)).count();
+
+    // one bishop each on squares of different colours, nothing else but kings and pawns
+    if pieces == 2 && count(Piece::WhiteBishop) == 1 && count(Piece::BlackBishop) == 1 {
+        let colour = |bishop: Piece| board.state.iter().find(|(_, piece)| **piece == bishop).map(|(coord, _)| coord.diagonals().0 % 2 == BigInt::from(0));
+        if colour(Piece::WhiteBishop) != colour(Piece::BlackBishop) {
+            return OPPOSITE_BISHOPS_SCALE;
+        }
+    }
+
+    let pawns = count(Piece::WhitePawn) + count(Piece::BlackPawn);
+    if pawns == 0 && pieces == 2 && count(Piece::WhiteRook) == 1 && count(Piece::BlackRook) == 1 {
+        return ROOK_VS_ROOK_SCALE;
+    }
+
+    SCALE_NORMAL
+}
+
+// Percent of the evaluation kept after FORTRESS_PLIES without progress. Part of the evaluation
+// rather than the root score so the search prefers lines that reset the clock to ones that
+// shuffle on. `score` is white's, it decides which side is ahead.
+pub fn fortress_percent(board: &Board, score: i32) -> i32 {
+    if board.halfmove_clock < FORTRESS_PLIES || score == 0 || board.phase() != Phase::Endgame {
+        return 100;
+    }
+    let nudge = (FORTRESS_NUDGE + board.halfmove_clock.saturating_sub(FORTRESS_PLIES).min(MAX_FORTRESS_NUDGE as u32) as i32).min(MAX_FORTRESS_NUDGE);
+    let pawn = if score > 0 { Piece::WhitePawn } else { Piece::BlackPawn };
+    if board.state.values().any(|piece| *piece == pawn) {
+        100 - nudge / 2
+    } else {
+        100 - nudge
+    }
+}
+
+// Pawn structure scores by pawn key. Each searcher keeps its own, so searches never share one.
+#[derive(Debug, Clone, Default)]
+pub struct PawnHash {
+    pub scores: HashMap<u64, i32>,
+}
+
+// Pawn structure from white's point of view. It only depends on where the pawns stand, so it
+// is cached by a key over the pawns alone.
+pub fn pawn_structure(board: &Board, hash: &mut PawnHash) -> i32 {
+    let pawns: Vec<(&Coordinate, &Piece)> = board.state.iter().filter(|(_, piece)| matches!(piece, Piece::WhitePawn | Piece::BlackPawn)).collect();
+    let key = pawns.iter().fold(0, |key, (coord, piece)| key ^ piece_key(**piece, coord));
+    if let Some(score) = hash.scores.get(&key) {
+        return *score;
+    }
+
+    let white_pawns: HashSet<&Coordinate> = pawns.iter().filter(|(_, piece)| piece.is_white()).map(|(coord, _)| *coord).collect();
+    let black_pawns: HashSet<&Coordinate> = pawns.iter().filter(|(_, piece)| !piece.is_white()).map(|(coord, _)| *coord).collect();
+    let score = pawn_structure_score(board, &white_pawns, &black_pawns, true) - pawn_structure_score(board, &black_pawns, &white_pawns, false);
+
+    if hash.scores.len() >= PAWN_HASH_ENTRIES {
+        hash.scores.clear();
+    }
+    hash.scores.insert(key, score);
+    score
+}
+
+// Connected passers, chain links and backward pawns of one side. Every test looks at a few
+// neighbouring squares, so pawns any distance from the origin cost the same.
+pub fn pawn_structure_score(board: &Board, own: &HashSet<&Coordinate>, enemy: &HashSet<&Coordinate>, white: bool) -> i32 {
+    let forward: i64 = if white { 1 } else { -1 };
+    let square = |coord: &Coordinate, dx: i64, dy: i64| Coordinate(&coord.0 + dx, &coord.1 + dy * forward);
+    let passed: HashSet<&Coordinate> = own.iter().filter(|coord| is_passed_pawn(board, coord, white)).copied().collect();
+
+    let mut score = 0;
+    for coord in own {
+        // a passer with another passer beside it or one rank away on an adjacent file
+        if passed.contains(coord) && [(-1, -1), (-1, 0), (-1, 1), (1, -1), (1, 0), (1, 1)].iter().any(|(dx, dy)| passed.contains(&square(coord, *dx, *dy))) {
+            score += CONNECTED_PASSER_BONUS;
+        }
+
+        if own.contains(&square(coord, -1, -1)) || own.contains(&square(coord, 1, -1)) {
+            score += PAWN_CHAIN_BONUS;
+            continue;
+        }
+
+        // no neighbour level or behind that could ever support it, and it cannot advance safely
+        let supportable = own.iter().any(|other| (&other.0 - &coord.0).abs() == BigInt::from(1) && (&other.1 - &coord.1) * forward <= BigInt::from(0));
+        let stop_attacked = enemy.contains(&square(coord, -1, 2)) || enemy.contains(&square(coord, 1, 2));
+        if !supportable && stop_attacked {
+            score -= BACKWARD_PAWN_PENALTY;
+        }
+    }
+
+    score
+}
+
+// Danger to the given side's king from enemy pressure: attacks on the squares around an
+// uncastled king and safe checks available to the enemy next move. Without piece-square
+// tables this is what keeps the king out of harm in the opening and middlegame, so the term
+// only applies while the attacker still has a queen.
+pub fn king_pressure(board: &Board, lines: &Lines, white: bool) -> i32 {
+    let Some(king) = find_king(board, white) else { return 0 };
+    let attacker_queen = if white { Piece::BlackQueen } else { Piece::WhiteQueen };
+    if !board.state.values().any(|piece| *piece == attacker_queen) {
+        return 0;
+    }
+
+    let mut penalty = 0;
+
+    let home = if white { Coordinate::new(5, 1) } else { Coordinate::new(5, 8) };
+    if *king == home {
+        for offset in KING_OFFSETS {
+            let square = king + offset;
+            penalty += lines.attackers(board, &square, !white) * board.rules.eval.king_zone_attack;
+        }
+    }
+
+    let mut safe_checks = 0;
+    for (coord, piece) in board.state.iter().filter(|(_, piece)| piece.is_white() != white) {
+        // quiet moves only: sliders just stop short of a coordinate limit, and kings are kept
+        // out of move generation, whose castling check scans the whole board
+        let destinations: Vec<Coordinate> = if is_slider(*piece) {
+            lines.slide_stops(board, coord, *piece)
+        } else if matches!(piece, Piece::WhiteKing | Piece::BlackKing) {
+            KING_OFFSETS.into_iter().map(|offset| coord + offset).collect()
+        } else {
+            let mut move_list = MoveList::new();
+            MoveGen::generate_piece_moves(board, coord.clone(), *piece, &mut move_list);
+            move_list.moves[..move_list.count as usize].iter()
+                .filter_map(|mv| if let Move::Normal(_, to) = mv { Some(to.clone()) } else { None })
+                .collect()
+        };
+        for to in &destinations {
+            if board.get_piece(to).is_none() && lines.attacks_square(board, to, *piece, king) && lines.attackers(board, to, white) == 0 {
+                safe_checks += 1;
+            }
+        }
+    }
+    penalty += safe_checks * board.rules.eval.safe_check;
+
+    penalty
+}
+
+pub fn in_action_region(coord: &Coordinate) -> bool {
+    let low = BigInt::from(1 - ACTION_MARGIN);
+    let high = BigInt::from(8 + ACTION_MARGIN);
+    coord.0 >= low && coord.0 <= high && coord.1 >= low && coord.1 <= high
+}
+
+// Opening guidance for the classical start: develop knights and bishops off their home
+// squares into the region around the setup, keep or use the castling rights, and don't send
+// rooks off into empty space before the minor pieces are out
+pub fn development_score(board: &Board, white: bool) -> i32 {
+    let pieces = board.state.values().filter(|piece| piece.is_white() == white && !is_pawn_or_king(**piece)).count();
+    if pieces < OPENING_PIECES {
+        return 0;
+    }
+
+    let back_rank = if white { 1 } else { 8 };
+    let minor_homes = [2, 3, 6, 7].map(|file| Coordinate::new(file, back_rank));
+    let rook_homes = [1, 8].map(|file| Coordinate::new(file, back_rank));
+    let mut score = 0;
+    let mut undeveloped = 0;
+
+    for (coord, piece) in board.state.iter().filter(|(_, piece)| piece.is_white() == white) {
+        match piece {
+            Piece::WhiteKnight | Piece::WhiteBishop | Piece::BlackKnight | Piece::BlackBishop => {
+                if minor_homes.contains(coord) {
+                    undeveloped += 1;
+                } else if in_action_region(coord) {
+                    score += DEVELOPED_MINOR_BONUS;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (coord, piece) in board.state.iter().filter(|(_, piece)| piece.is_white() == white) {
+        if matches!(piece, Piece::WhiteRook | Piece::BlackRook) && undeveloped > 0 && !rook_homes.contains(coord) && !in_action_region(coord) {
+            score -= EARLY_ROOK_SORTIE_PENALTY;
+        }
+    }
+
+    let rights = board.castling_rights.iter().filter(|right| right.white == white).count();
+    score += rights as i32 * CASTLING_RIGHT_BONUS;
+
+    // king on a castled square with the rook next to it on the inside
+    let (king, rook) = if white { (Piece::WhiteKing, Piece::WhiteRook) } else { (Piece::BlackKing, Piece::BlackRook) };
+    for (king_file, rook_file) in [(7, 6), (3, 4)] {
+        if board.get_piece(&Coordinate::new(king_file, back_rank)) == Some(&king)
+            && board.get_piece(&Coordinate::new(rook_file, back_rank)) == Some(&rook) {
+            score += CASTLED_BONUS;
+        }
+    }
+
+    score
+}
+
+pub fn center_of_mass<'a>(squares: impl Iterator<Item = &'a Coordinate>) -> Option<Coordinate> {
+    let mut count = 0;
+    let mut sum = Coordinate::new(0, 0);
+    for square in squares {
+        sum.0 += &square.0;
+        sum.1 += &square.1;
+        count += 1;
+    }
+    (count > 0).then(|| Coordinate(sum.0 / count, sum.1 / count))
+}
+
+// Penalise a side whose pieces (other than king and pawns) drift away, on average, from
+// where the game is played: the centre of both kings and all pawns. Scattering pieces to
+// distant coordinates can raise mobility counts without doing anything useful.
+pub fn drift_penalty(board: &Board, white: bool) -> i32 {
+    let action = center_of_mass(board.state.iter().filter(|(_, piece)| is_pawn_or_king(**piece)).map(|(coord, _)| coord));
+    let pieces = center_of_mass(board.state.iter()
+        .filter(|(_, piece)| piece.is_white() == white && !is_pawn_or_king(**piece))
+        .map(|(coord, _)| coord));
+
+    match (action, pieces) {
+        (Some(action), Some(pieces)) => {
+            let drift = distance(&action, &pieces).min(BigInt::from(DRIFT_CAP));
+            i32::try_from(drift).unwrap_or(0) * DRIFT_WEIGHT
+        }
+        _ => 0,
+    }
+}
@@ -0,0 +1,98 @@
+// src/experience.rs
+use std::collections::HashMap;
+use std::fs;
+use crate::board::Board;
+use crate::evaluation::DEFAULT_EVAL;
+use crate::hash::position_key;
+use crate::notation::{format_move, parse_move};
+use crate::r#move::Move;
+
+// Root results remembered across games, keyed by position hash. Moves are kept in text
+// form so the file stays readable and survives changes to the move representation.
+#[derive(Debug, Clone)]
+pub struct ExperienceEntry {
+    pub best_move: String,
+    pub score: i32,
+    pub depth: u8,
+    pub profile: String, // name of the evaluation profile the score was searched with
+}
+
+#[derive(Debug, Clone)]
+pub struct Experience {
+    pub path: String,
+    pub entries: HashMap<u64, ExperienceEntry>,
+    pub dirty: bool, // changed since the last save
+}
+
+impl Experience {
+    // Experience files hold one entry per line: `<hash hex> <depth> <score> <move> <profile>`.
+    // Entries without a profile were searched with the default one. A missing file is an empty
+    // experience, it gets created on the first save.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut experience = Experience { path: path.to_string(), entries: HashMap::new(), dirty: false };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(experience),
+            Err(err) => return Err(format!("{}: {}", path, err)),
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let entry = match fields[..] {
+                [key, depth, score, best_move] | [key, depth, score, best_move, _] => u64::from_str_radix(key, 16).ok().zip(depth.parse().ok()).zip(score.parse().ok())
+                    .map(|((key, depth), score)| {
+                        let profile = fields.get(4).copied().unwrap_or(&DEFAULT_EVAL.name).to_string();
+                        (key, ExperienceEntry { best_move: best_move.to_string(), score, depth, profile })
+                    }),
+                _ => None,
+            };
+            let (key, entry) = entry.ok_or_else(|| format!("{}:{}: expected '<hash> <depth> <score> <move> [<profile>]'", path, number + 1))?;
+            experience.entries.insert(key, entry);
+        }
+
+        Ok(experience)
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let mut keys: Vec<&u64> = self.entries.keys().collect();
+        keys.sort();
+        let text: String = keys.into_iter()
+            .map(|key| {
+                let entry = &self.entries[key];
+                format!("{:016x} {} {} {} {}\n", key, entry.depth, entry.score, entry.best_move, entry.profile)
+            })
+            .collect();
+        fs::write(&self.path, text).map_err(|err| format!("{}: {}", self.path, err))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    // A remembered result at least as deep as asked for under the board's evaluation profile, if
+    // its move is still legal here (which also weeds out hash collisions)
+    pub fn probe(&self, board: &Board, depth: u8) -> Option<(Move, &ExperienceEntry)> {
+        let entry = self.entries.get(&position_key(board))?;
+        if entry.depth < depth || entry.profile != board.rules.eval.name {
+            return None;
+        }
+        let mv = parse_move(board, &entry.best_move).ok()?;
+        let mut board = board.clone_without_history();
+        board.make(mv.clone()).then_some((mv, entry))
+    }
+
+    // Deeper results replace shallower ones, and any result replaces one of another profile
+    pub fn record(&mut self, board: &Board, best_move: &Move, score: i32, depth: u8) {
+        let key = position_key(board);
+        let profile = &board.rules.eval.name;
+        if depth == 0 || self.entries.get(&key).is_some_and(|entry| entry.depth > depth && entry.profile == *profile) {
+            return;
+        }
+        self.entries.insert(key, ExperienceEntry { best_move: format_move(best_move, None, false), score, depth, profile: profile.to_string() });
+        self.dirty = true;
+    }
+}
@@ -0,0 +1,105 @@
+// src/export.rs
+use std::fs::OpenOptions;
+use std::io::Write;
+use crate::board::Board;
+use crate::game::{format_result, GameResult};
+use crate::notation::{format_fen, format_move};
+use crate::r#move::Move;
+use crate::search::{MATE_SCORE, MATE_VALUE};
+
+// What was known when a move was played, for the comments of an exported game
+#[derive(Debug, Clone)]
+pub struct MoveRecord {
+    pub mv: Move,
+    pub elapsed: u128, // ms the mover thought
+    pub clock: Option<i64>, // ms the mover had left after the move, with a time control
+    pub eval: Option<i32>, // the mover's search score from white's point of view, engines only
+}
+
+pub struct GameRecord {
+    pub start: Board,
+    pub moves: Vec<MoveRecord>,
+    pub result: (GameResult, &'static str),
+}
+
+// Which comments go after each move, in the [%clk] / [%emt] / [%eval] style of PGN
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    pub clocks: bool,
+    pub evals: bool,
+}
+
+impl ExportOptions {
+    // `clocks`, `evals`, `clocks,evals` or `none`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut options = ExportOptions::default();
+        for part in text.split(',').filter(|part| *part != "none") {
+            match part {
+                "clocks" => options.clocks = true,
+                "evals" => options.evals = true,
+                _ => return Err(format!("unknown export comment '{}', expected clocks, evals or none", part)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// h:mm:ss.t
+pub fn format_time(ms: i64) -> String {
+    let tenths = ms.max(0) / 100;
+    format!("{}:{:02}:{:02}.{}", tenths / 36000, tenths / 600 % 60, tenths / 10 % 60, tenths % 10)
+}
+
+// Pawns with two decimals, or #N / #-N for a mate in N moves
+pub fn format_eval(score: i32) -> String {
+    if score.abs() > MATE_SCORE {
+        let moves = (MATE_VALUE - score.abs() + 1) / 2;
+        format!("#{}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("{:.2}", score as f64 / 100.0)
+    }
+}
+
+// Tag pairs, the start position and numbered moves with a comment each, readable back by
+// annotate::game_moves
+pub fn format_game(record: &GameRecord, tags: &[(&str, String)], options: ExportOptions) -> String {
+    let mut text = String::new();
+    for (name, value) in tags {
+        text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "'")));
+    }
+    text.push_str(&format!("[Result \"{}\"]\n[Termination \"{}\"]\n[Position \"{}\"]\n", format_result(record.result.0), record.result.1, format_fen(&record.start)));
+
+    let black_first = !record.start.side_to_move;
+    for (ply, played) in record.moves.iter().enumerate() {
+        let ply = ply + black_first as usize;
+        if ply.is_multiple_of(2) {
+            text.push_str(&format!("{}{}. ", if ply > 0 { "\n" } else { "" }, ply / 2 + 1));
+        } else if ply == 1 && black_first {
+            text.push_str("1... ");
+        } else {
+            text.push(' ');
+        }
+        text.push_str(&format_move(&played.mv, None, false));
+
+        let mut comments = Vec::new();
+        if options.clocks {
+            if let Some(clock) = played.clock {
+                comments.push(format!("[%clk {}]", format_time(clock)));
+            }
+            comments.push(format!("[%emt {}]", format_time(played.elapsed as i64)));
+        }
+        if let (true, Some(eval)) = (options.evals, played.eval) {
+            comments.push(format!("[%eval {}]", format_eval(eval)));
+        }
+        if !comments.is_empty() {
+            text.push_str(&format!(" {{{}}}", comments.join(" ")));
+        }
+    }
+    text.push_str(&format!("\n{}\n\n", format_result(record.result.0)));
+    text
+}
+
+pub fn append(path: &str, text: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|err| format!("{}: {}", path, err))?;
+    file.write_all(text.as_bytes()).map_err(|err| format!("{}: {}", path, err))
+}
@@ -0,0 +1,211 @@
+// src/game.rs
+use crate::board::{Board, Coordinate, Piece};
+use crate::hash::repetition_key;
+use crate::r#move::Move;
+use crate::snapshot;
+use crate::tools::legal_moves;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+// Hooks for whatever follows a game as it is played (a GUI, a logger, a bridge to a server).
+// Each one does nothing by default, so observers only implement what they care about.
+pub trait Observer {
+    fn on_move_made(&mut self, _board: &Board, _mv: &Move) {}
+    fn on_capture(&mut self, _square: &Coordinate, _piece: Piece) {}
+    fn on_promotion(&mut self, _square: &Coordinate, _piece: Piece) {}
+    fn on_game_end(&mut self, _result: GameResult, _reason: &str) {}
+}
+
+// How much of a long game's past stays in memory. Board::history keeps a full board per ply,
+// which adds up over hundreds of moves on a board of big coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryPolicy {
+    Full, // every earlier board
+    Snapshots(usize), // boards for the last n plies, compact snapshots before that, decoded again on undo
+    Hashes(usize), // boards for the last n plies, only repetition keys before that, so no undo past them
+}
+
+impl HistoryPolicy {
+    // `full`, `snapshots <n>` or `hashes <n>`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let usage = || format!("invalid history policy '{}', expected 'full', 'snapshots <plies>' or 'hashes <plies>'", text);
+        let mut words = text.split_whitespace();
+        let kind = words.next().ok_or_else(usage)?;
+        let plies = words.next().map(|plies| plies.parse::<usize>().map_err(|_| usage())).transpose()?;
+        match (kind, plies, words.next()) {
+            ("full", None, None) => Ok(HistoryPolicy::Full),
+            ("snapshots", Some(plies), None) => Ok(HistoryPolicy::Snapshots(plies)),
+            ("hashes", Some(plies), None) => Ok(HistoryPolicy::Hashes(plies)),
+            _ => Err(usage()),
+        }
+    }
+}
+
+// A position moved out of Board::history, with its repetition key kept either way
+enum Archived {
+    Snapshot(Vec<u8>, u64),
+    Key(u64),
+}
+
+impl Archived {
+    fn key(&self) -> u64 {
+        match self {
+            Archived::Snapshot(_, key) | Archived::Key(key) => *key,
+        }
+    }
+}
+
+// A board that is being played on rather than searched: moves go through play() and the
+// observers hear about them. The search makes and takes back moves on its own copies, which
+// fire no events.
+pub struct Game {
+    pub board: Board,
+    pub result: Option<(GameResult, &'static str)>,
+    pub policy: HistoryPolicy,
+    observers: Vec<Box<dyn Observer>>,
+    archive: Vec<Archived>, // positions older than Board::history, oldest first
+}
+
+impl Game {
+    pub fn new(board: Board) -> Self {
+        Game { board, result: None, policy: HistoryPolicy::Full, observers: Vec::new(), archive: Vec::new() }
+    }
+
+    pub fn observe(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    // Start over from another position, the observers stay
+    pub fn set_board(&mut self, board: Board) {
+        self.board = board;
+        self.result = None;
+        self.archive.clear();
+    }
+
+    pub fn play(&mut self, mv: Move) -> Result<(), String> {
+        if self.result.is_some() {
+            return Err("the game is over".to_string());
+        }
+        if !self.board.make(mv.clone()) {
+            self.board.unmake(mv);
+            return Err("the move leaves the king in check".to_string());
+        }
+
+        for observer in &mut self.observers {
+            observer.on_move_made(&self.board, &mv);
+        }
+        if let (Some(piece), Some(square)) = (self.board.captured, &self.board.last_capture) {
+            for observer in &mut self.observers {
+                observer.on_capture(square, piece);
+            }
+        }
+        if let Move::Promotion(_, square, piece) = &mv {
+            for observer in &mut self.observers {
+                observer.on_promotion(square, *piece);
+            }
+        }
+
+        self.retain_history();
+        let repetitions = self.repetitions();
+        self.result = game_end(&mut self.board, repetitions);
+        self.announce_end();
+        Ok(())
+    }
+
+    // The position's earlier occurrences, archived ones included
+    pub fn repetitions(&self) -> usize {
+        let key = repetition_key(&self.board);
+        self.board.repetitions() + self.archive.iter().filter(|position| position.key() == key).count()
+    }
+
+    pub fn can_claim_draw(&self) -> bool {
+        self.result.is_none() && self.board.draw_reason(self.repetitions(), true).is_some()
+    }
+
+    // The side to move claims a draw, which only ends the game when the rules allow it
+    pub fn claim_draw(&mut self) -> Result<(), String> {
+        if self.result.is_some() {
+            return Err("the game is over".to_string());
+        }
+        let reason = self.board.draw_reason(self.repetitions(), true).ok_or("no draw to claim: the position has not repeated three times and the last capture or pawn move is less than 50 moves back")?;
+        self.result = Some((GameResult::Draw, reason));
+        self.announce_end();
+        Ok(())
+    }
+
+    fn announce_end(&mut self) {
+        if let Some((result, reason)) = self.result {
+            for observer in &mut self.observers {
+                observer.on_game_end(result, reason);
+            }
+        }
+    }
+
+    pub fn undo(&mut self) -> Result<(), String> {
+        if self.board.history.is_empty() {
+            match self.archive.pop() {
+                // the rules don't change during a game, and the capture that led to the
+                // position is not part of a snapshot
+                Some(Archived::Snapshot(bytes, _)) => {
+                    let mut previous = snapshot::decode(&bytes)?;
+                    previous.rules = self.board.rules.clone();
+                    self.board.history.push(previous);
+                }
+                Some(archived) => {
+                    self.archive.push(archived);
+                    return Err("positions that far back are no longer kept".to_string());
+                }
+                None => return Err("no move to take back".to_string()),
+            }
+        }
+        self.board.unmake(Move::None);
+        self.result = None;
+        Ok(())
+    }
+
+    // Move the positions the policy doesn't keep as boards out of Board::history
+    fn retain_history(&mut self) {
+        let plies = match self.policy {
+            HistoryPolicy::Full => return,
+            HistoryPolicy::Snapshots(plies) | HistoryPolicy::Hashes(plies) => plies,
+        };
+        let excess = self.board.history.len().saturating_sub(plies);
+        for old in self.board.history.drain(..excess) {
+            let key = repetition_key(&old);
+            self.archive.push(match self.policy {
+                HistoryPolicy::Snapshots(_) => Archived::Snapshot(snapshot::encode(&old), key),
+                _ => Archived::Key(key),
+            });
+        }
+    }
+}
+
+// Whether the game is over for the side to move: its king was taken, it is mated, it has no
+// move at all or the position is drawn without a claim. `repetitions` counts the earlier
+// occurrences of the position, including those no longer in the board's history. Draws that
+// have to be claimed are left to Game::claim_draw.
+pub fn game_end(board: &mut Board, repetitions: usize) -> Option<(GameResult, &'static str)> {
+    let opponent_wins = if board.side_to_move { GameResult::BlackWins } else { GameResult::WhiteWins };
+    if board.royal_captured() {
+        return Some((opponent_wins, "royal capture"));
+    }
+    if !legal_moves(board).is_empty() {
+        return board.draw_reason(repetitions, false).map(|reason| (GameResult::Draw, reason));
+    }
+    let white = board.side_to_move;
+    let in_check = board.in_check(white);
+    Some(if in_check { (opponent_wins, "checkmate") } else { (GameResult::Draw, "stalemate") })
+}
+
+pub fn format_result(result: GameResult) -> &'static str {
+    match result {
+        GameResult::WhiteWins => "1-0",
+        GameResult::BlackWins => "0-1",
+        GameResult::Draw => "1/2-1/2",
+    }
+}
@@ -0,0 +1,261 @@
+// src/geometry.rs
+use std::ops::Add;
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+use crate::board::Coordinate;
+
+// A fixed step between squares, as used by leapers and as the unit step of a ray
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Offset {
+    pub dx: i64,
+    pub dy: i64,
+}
+
+pub const KING_OFFSETS: [Offset; 8] = [
+    Offset::new(1, 0), Offset::new(1, 1), Offset::new(0, 1), Offset::new(-1, 1),
+    Offset::new(-1, 0), Offset::new(-1, -1), Offset::new(0, -1), Offset::new(1, -1),
+];
+
+pub const KNIGHT_OFFSETS: [Offset; 8] = [
+    Offset::new(2, 1), Offset::new(2, -1), Offset::new(-2, 1), Offset::new(-2, -1),
+    Offset::new(1, 2), Offset::new(1, -2), Offset::new(-1, 2), Offset::new(-1, -2),
+];
+
+impl Offset {
+    pub const fn new(dx: i64, dy: i64) -> Self {
+        Offset { dx, dy }
+    }
+}
+
+// The eight ray directions, in the order used for encoding moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::TopLeft, Direction::TopRight, Direction::BottomLeft, Direction::BottomRight,
+        Direction::Top, Direction::Bottom, Direction::Left, Direction::Right,
+    ];
+    pub const ORTHOGONAL: [Direction; 4] = [Direction::Top, Direction::Bottom, Direction::Right, Direction::Left];
+    pub const DIAGONAL: [Direction; 4] = [Direction::TopRight, Direction::BottomRight, Direction::TopLeft, Direction::BottomLeft];
+
+    pub fn offset(self) -> Offset {
+        match self {
+            Direction::TopLeft => Offset::new(-1, 1),
+            Direction::TopRight => Offset::new(1, 1),
+            Direction::BottomLeft => Offset::new(-1, -1),
+            Direction::BottomRight => Offset::new(1, -1),
+            Direction::Top => Offset::new(0, 1),
+            Direction::Bottom => Offset::new(0, -1),
+            Direction::Left => Offset::new(-1, 0),
+            Direction::Right => Offset::new(1, 0),
+        }
+    }
+
+    pub fn from_offset(offset: Offset) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|direction| direction.offset() == offset)
+    }
+
+    pub fn is_orthogonal(self) -> bool {
+        let offset = self.offset();
+        offset.dx == 0 || offset.dy == 0
+    }
+
+    pub fn opposite(self) -> Direction {
+        let offset = self.offset();
+        Direction::from_offset(Offset::new(-offset.dx, -offset.dy)).unwrap()
+    }
+}
+
+impl Add<Offset> for &Coordinate {
+    type Output = Coordinate;
+
+    fn add(self, offset: Offset) -> Coordinate {
+        Coordinate(&self.0 + offset.dx, &self.1 + offset.dy)
+    }
+}
+
+fn sign(value: &BigInt) -> i64 {
+    if value.is_positive() { 1 } else if value.is_negative() { -1 } else { 0 }
+}
+
+impl Coordinate {
+    // The square `distance` steps away in a direction
+    pub fn step(&self, direction: Direction, distance: &BigInt) -> Coordinate {
+        let offset = direction.offset();
+        Coordinate(&self.0 + distance * offset.dx, &self.1 + distance * offset.dy)
+    }
+
+    // Direction and distance (in steps) to another square on a common rank, file or diagonal.
+    // Squares whose coordinates all fit an i64 take the machine-integer path, with the
+    // differences in i128 so even i64::MIN to i64::MAX can't wrap; the rest fall back to BigInt.
+    pub fn between(&self, other: &Coordinate) -> Option<(Direction, BigInt)> {
+        let small = match (self.0.to_i64(), self.1.to_i64(), other.0.to_i64(), other.1.to_i64()) {
+            (Some(x1), Some(y1), Some(x2), Some(y2)) => Some(between_small(x1, y1, x2, y2)),
+            _ => None,
+        };
+        small.unwrap_or_else(|| self.between_big(other))
+    }
+
+    pub fn between_big(&self, other: &Coordinate) -> Option<(Direction, BigInt)> {
+        let dx = &other.0 - &self.0;
+        let dy = &other.1 - &self.1;
+        if !(dx.is_zero() || dy.is_zero() || dx.abs() == dy.abs()) {
+            return None;
+        }
+        let direction = Direction::from_offset(Offset::new(sign(&dx), sign(&dy)))?;
+        Some((direction, dx.abs().max(dy.abs())))
+    }
+
+    pub fn aligned_with(&self, other: &Coordinate) -> bool {
+        self.between(other).is_some()
+    }
+
+    // `other - self` per axis, in i128 when both squares fit an i64
+    pub fn delta(&self, other: &Coordinate) -> (BigInt, BigInt) {
+        let small = match (self.0.to_i64(), self.1.to_i64(), other.0.to_i64(), other.1.to_i64()) {
+            (Some(x1), Some(y1), Some(x2), Some(y2)) => Some((BigInt::from(x2 as i128 - x1 as i128), BigInt::from(y2 as i128 - y1 as i128))),
+            _ => None,
+        };
+        small.unwrap_or_else(|| self.delta_big(other))
+    }
+
+    pub fn delta_big(&self, other: &Coordinate) -> (BigInt, BigInt) {
+        (&other.0 - &self.0, &other.1 - &self.1)
+    }
+
+    // King steps between two squares, the larger of the two axis distances
+    pub fn chebyshev(&self, other: &Coordinate) -> BigInt {
+        let (dx, dy) = self.delta(other);
+        dx.abs().max(dy.abs())
+    }
+
+    // `x + y` and `x - y`, constant along each diagonal, in i128 when the square fits an i64
+    pub fn diagonals(&self) -> (BigInt, BigInt) {
+        let small = match (self.0.to_i64(), self.1.to_i64()) {
+            (Some(x), Some(y)) => Some((BigInt::from(x as i128 + y as i128), BigInt::from(x as i128 - y as i128))),
+            _ => None,
+        };
+        small.unwrap_or_else(|| self.diagonals_big())
+    }
+
+    pub fn diagonals_big(&self) -> (BigInt, BigInt) {
+        (&self.0 + &self.1, &self.0 - &self.1)
+    }
+}
+
+// Ranks strictly between two ranks of a file, nearest to `from` first: the squares a pawn push
+// skips, each open to en passant. In i128 when both fit an i64.
+pub fn ranks_between(from: &BigInt, to: &BigInt) -> Vec<BigInt> {
+    let small = match (from.to_i64(), to.to_i64()) {
+        (Some(from), Some(to)) => {
+            let (from, to) = (from as i128, to as i128);
+            let step = (to - from).signum();
+            Some(if step == 0 { Vec::new() } else { (1..(to - from).abs()).map(|offset| BigInt::from(from + offset * step)).collect() })
+        }
+        _ => None,
+    };
+    small.unwrap_or_else(|| ranks_between_big(from, to))
+}
+
+pub fn ranks_between_big(from: &BigInt, to: &BigInt) -> Vec<BigInt> {
+    let step = BigInt::from(sign(&(to - from)));
+    let mut ranks = Vec::new();
+    if step.is_zero() {
+        return ranks;
+    }
+    let mut rank = from + &step;
+    while rank != *to {
+        ranks.push(rank.clone());
+        rank += &step;
+    }
+    ranks
+}
+
+// Under --verify-movegen, aborts when a machine-integer result for two squares differs from
+// the BigInt one
+#[cfg(feature = "verify")]
+pub fn cross_check(a: &Coordinate, b: &Coordinate) {
+    fn check<T: PartialEq + std::fmt::Debug>(name: &str, a: &Coordinate, b: &Coordinate, small: T, big: T) {
+        if small != big {
+            eprintln!("coordinate arithmetic mismatch in {} of {} and {}: i128 {:?}, BigInt {:?}", name, a, b, small, big);
+            std::process::abort();
+        }
+    }
+    check("between", a, b, a.between(b), a.between_big(b));
+    check("delta", a, b, a.delta(b), a.delta_big(b));
+    check("diagonals", a, a, a.diagonals(), a.diagonals_big());
+    // a pawn push skips a few ranks, longer lists only cost memory
+    if (&b.1 - &a.1).abs() <= BigInt::from(8) {
+        check("ranks_between", a, b, ranks_between(&a.1, &b.1), ranks_between_big(&a.1, &b.1));
+    }
+}
+
+fn between_small(x1: i64, y1: i64, x2: i64, y2: i64) -> Option<(Direction, BigInt)> {
+    let (dx, dy) = (x2 as i128 - x1 as i128, y2 as i128 - y1 as i128);
+    if !(dx == 0 || dy == 0 || dx.abs() == dy.abs()) {
+        return None;
+    }
+    let direction = Direction::from_offset(Offset::new(dx.signum() as i64, dy.signum() as i64))?;
+    Some((direction, BigInt::from(dx.abs().max(dy.abs()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openings::Rng;
+
+    // Values clustered where the i128 intermediates are widest: around 0, the i64 limits, and
+    // just past them where the BigInt fallback takes over
+    fn value(rng: &mut Rng) -> BigInt {
+        let edges = [BigInt::zero(), BigInt::from(i64::MIN), BigInt::from(i64::MAX), BigInt::from(i64::MIN) - 1, BigInt::from(i64::MAX) + 1, BigInt::from(i128::MAX), BigInt::from(i128::MIN)];
+        let edge = &edges[rng.below(edges.len())];
+        let jitter = rng.next() % 64;
+        if rng.below(2) == 0 { edge + jitter } else { edge - jitter }
+    }
+
+    fn square(rng: &mut Rng) -> Coordinate {
+        Coordinate(value(rng), value(rng))
+    }
+
+    // Another square on a common line with `from` half the time, so `between` has work to do
+    fn target(rng: &mut Rng, from: &Coordinate) -> Coordinate {
+        if rng.below(2) == 0 {
+            return square(rng);
+        }
+        let direction = Direction::ALL[rng.below(8)];
+        let distance = if rng.below(2) == 0 { BigInt::from(rng.next() % 100) } else { value(rng).abs() };
+        from.step(direction, &distance)
+    }
+
+    #[test]
+    fn fast_paths_match_bigint() {
+        let mut rng = Rng(0x1234_5678);
+        for _ in 0..20_000 {
+            let a = square(&mut rng);
+            let b = target(&mut rng, &a);
+            assert_eq!(a.between(&b), a.between_big(&b), "between {} {}", a, b);
+            assert_eq!(a.delta(&b), a.delta_big(&b), "delta {} {}", a, b);
+            assert_eq!(a.diagonals(), a.diagonals_big(), "diagonals {}", a);
+        }
+    }
+
+    #[test]
+    fn skipped_ranks_match_bigint() {
+        let mut rng = Rng(0x9abc_def0);
+        for _ in 0..20_000 {
+            let from = value(&mut rng);
+            let to = &from + (rng.next() % 7) as i64 - 3;
+            assert_eq!(ranks_between(&from, &to), ranks_between_big(&from, &to), "ranks between {} and {}", from, to);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// src/golden.rs
+use std::fs;
+use clap::Args;
+use crate::notation::{format_fen, format_move, parse_fen};
+use crate::tools::legal_moves;
+
+// Golden files hold named positions with their expected legal moves:
+//   [name]
+//   fen <extended fen>
+//   legal <move> <move> ...
+// Moves are sorted so files diff cleanly. Blank lines and lines starting with '#' are ignored.
+pub struct Fixture {
+    pub name: String,
+    pub fen: String,
+    pub legal: Option<Vec<String>>, // None until the file has been written once
+}
+
+pub fn parse_fixtures(text: &str) -> Result<Vec<Fixture>, String> {
+    let mut fixtures: Vec<Fixture> = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+
+        if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            fixtures.push(Fixture { name: name.to_string(), fen: String::new(), legal: None });
+            continue;
+        }
+        let fixture = fixtures.last_mut().ok_or_else(|| error("expected a [name] header first"))?;
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "fen" => fixture.fen = value.trim().to_string(),
+            "legal" => fixture.legal = Some(value.split_whitespace().map(str::to_string).collect()),
+            _ => return Err(error(&format!("unknown key '{}'", key))),
+        }
+    }
+
+    match fixtures.iter().find(|fixture| fixture.fen.is_empty()) {
+        Some(fixture) => Err(format!("fixture '{}' has no fen", fixture.name)),
+        None => Ok(fixtures),
+    }
+}
+
+pub fn format_fixtures(fixtures: &[Fixture]) -> String {
+    let mut text = String::new();
+    for fixture in fixtures {
+        text.push_str(&format!("[{}]\nfen {}\n", fixture.name, fixture.fen));
+        if let Some(legal) = &fixture.legal {
+            text.push_str(&format!("legal {}\n", legal.join(" ")));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// Legal moves the generator currently produces for a fixture, sorted
+pub fn current_moves(fixture: &Fixture) -> Result<Vec<String>, String> {
+    let mut board = parse_fen(&fixture.fen).map_err(|err| format!("{}: {}", fixture.name, err))?;
+    let mut moves: Vec<String> = legal_moves(&mut board).iter().map(|mv| format_move(mv, None, false)).collect();
+    moves.sort();
+    Ok(moves)
+}
+
+// Differences against the golden list, as lines of `+move` (new) and `-move` (missing)
+pub fn compare(fixture: &Fixture) -> Result<Vec<String>, String> {
+    let expected = fixture.legal.as_ref().ok_or_else(|| format!("{}: no golden moves, run 'golden write' first", fixture.name))?;
+    let actual = current_moves(fixture)?;
+
+    let mut differences: Vec<String> = actual.iter().filter(|mv| !expected.contains(mv)).map(|mv| format!("+{}", mv)).collect();
+    differences.extend(expected.iter().filter(|mv| !actual.contains(mv)).map(|mv| format!("-{}", mv)));
+    Ok(differences)
+}
+
+#[derive(Args, Debug)]
+pub struct GoldenArgs {
+    #[arg(value_parser = ["write", "check"], help = "write records the current move lists, check compares with them")]
+    pub mode: String,
+    pub file: String,
+}
+
+pub fn run(args: &GoldenArgs) -> Result<(), String> {
+    let (command, path) = (args.mode.as_str(), &args.file);
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut fixtures = parse_fixtures(&text)?;
+
+    match command {
+        "write" => {
+            for fixture in fixtures.iter_mut() {
+                fixture.legal = Some(current_moves(fixture)?);
+                // store the FEN normalised, so reordered pieces do not show up as changes
+                fixture.fen = format_fen(&parse_fen(&fixture.fen)?);
+            }
+            fs::write(path, format_fixtures(&fixtures)).map_err(|err| format!("{}: {}", path, err))?;
+            println!("wrote {} fixtures to {}", fixtures.len(), path);
+            Ok(())
+        }
+        "check" => {
+            let mut failed = 0;
+            for fixture in &fixtures {
+                let differences = compare(fixture)?;
+                if !differences.is_empty() {
+                    failed += 1;
+                    println!("{}: {}", fixture.name, differences.join(" "));
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} of {} fixtures differ", failed, fixtures.len()));
+            }
+            println!("all {} fixtures match", fixtures.len());
+            Ok(())
+        }
+        _ => Err(format!("unknown golden command '{}', expected write or check", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The checked-in fixtures, so a slider or blocker regression fails `cargo test` and not
+    // only a manual `golden check`
+    #[test]
+    fn movegen_matches_the_golden_file() {
+        let fixtures = parse_fixtures(include_str!("../testdata/movegen.golden")).unwrap();
+        assert!(!fixtures.is_empty());
+        for fixture in &fixtures {
+            let differences = compare(fixture).unwrap();
+            assert!(differences.is_empty(), "{}: {}", fixture.name, differences.join(" "));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// src/hash.rs
+use std::collections::HashMap;
+use num_bigint::{BigInt, Sign};
+use clap::Args;
+use crate::board::{Board, Coordinate, Piece};
+use crate::notation::format_fen;
+use crate::openings::Rng;
+
+const SIDE_KEY: u64 = 0x9E37_79B9_7F4A_7C15;
+const CASTLING_SEED: u64 = 0xD1B5_4A32_D192_ED03;
+const EN_PASSANT_SEED: u64 = 0x8CB9_2BA7_2F3D_8DD7;
+const RECAPTURE_SEED: u64 = 0x5851_F42D_4C95_7F2D;
+
+pub fn mix(mut value: u64) -> u64 {
+    // splitmix64 finaliser
+    value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+// Every byte of the value goes in, so coordinates far beyond 64 bits still get distinct keys
+pub fn hash_bigint(value: &BigInt) -> u64 {
+    let bytes = value.to_signed_bytes_le();
+    let mut hash = mix(bytes.len() as u64);
+    for chunk in bytes.chunks(8) {
+        let mut word = [0u8; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash = mix(hash ^ u64::from_le_bytes(word));
+    }
+    hash
+}
+
+pub fn square_key(coord: &Coordinate) -> u64 {
+    mix(hash_bigint(&coord.0) ^ hash_bigint(&coord.1).rotate_left(32))
+}
+
+pub fn piece_key(piece: Piece, coord: &Coordinate) -> u64 {
+    mix(square_key(coord) ^ (piece as u64 + 1).wrapping_mul(0xA24B_AED4_963E_E407))
+}
+
+// Zobrist-style key of a position: piece placements are combined with xor, so the key does
+// not depend on the HashMap's iteration order
+pub fn position_key(board: &Board) -> u64 {
+    let mut key = board.state.iter().fold(0, |key, (coord, piece)| key ^ piece_key(*piece, coord));
+    if board.side_to_move {
+        key ^= SIDE_KEY;
+    }
+    for right in &board.castling_rights {
+        key ^= mix(CASTLING_SEED ^ square_key(&right.rook) ^ right.white as u64);
+    }
+    for square in &board.en_passant {
+        key ^= mix(EN_PASSANT_SEED ^ square_key(square));
+    }
+    key
+}
+
+// Key of a quiescence node: which recaptures it tries depends on the square the previous move
+// captured on, so its result is only reused for the same square
+pub fn quiescence_key(board: &Board) -> u64 {
+    position_key(board) ^ board.last_capture.as_ref().map_or(0, |square| mix(RECAPTURE_SEED ^ square_key(square)))
+}
+
+// Key under which repetitions are counted. With translation repetition on and nothing left that
+// is tied to absolute squares (pawns, castling rights, en passant, a coordinate limit), the
+// pieces are first shifted so the lowest file and rank are zero: kings shuffling across the
+// empty board keep meeting the same key instead of drifting forever.
+pub fn repetition_key(board: &Board) -> u64 {
+    let anchored = board.rules.coordinate_limit.is_some() || !board.castling_rights.is_empty() || !board.en_passant.is_empty()
+        || board.state.values().any(|piece| matches!(piece, Piece::WhitePawn | Piece::BlackPawn));
+    if !board.rules.translation_repetition || anchored || board.state.is_empty() {
+        return position_key(board);
+    }
+    let min_x = board.state.keys().map(|coord| &coord.0).min().unwrap();
+    let min_y = board.state.keys().map(|coord| &coord.1).min().unwrap();
+    let key = board.state.iter().fold(0, |key, (coord, piece)| key ^ piece_key(*piece, &Coordinate(&coord.0 - min_x, &coord.1 - min_y)));
+    if board.side_to_move { key ^ SIDE_KEY } else { key }
+}
+
+// How well position keys separate random positions spread over huge coordinates. Every
+// position gets a twin with one piece moved by a multiple of 2^64, which a hash of truncated
+// coordinates would map to the same key.
+pub struct CollisionStats {
+    pub positions: u64,
+    pub collisions: u64, // different positions sharing a key
+    pub twin_collisions: u64, // of which a position and its 2^64-shifted twin
+}
+
+fn random_bigint(rng: &mut Rng, bits: u32) -> BigInt {
+    let words: Vec<u32> = (0..bits.div_ceil(32)).map(|_| rng.next() as u32).collect();
+    let magnitude = BigInt::from_slice(Sign::Plus, &words) % (BigInt::from(1) << bits);
+    if rng.next() & 1 == 0 { magnitude } else { -magnitude }
+}
+
+pub fn collision_stats(count: u64, bits: u32, seed: u64) -> CollisionStats {
+    let mut rng = Rng(seed);
+    let mut seen: HashMap<u64, String> = HashMap::new();
+    let mut stats = CollisionStats { positions: 0, collisions: 0, twin_collisions: 0 };
+    let mut record = |board: &Board, stats: &mut CollisionStats| -> u64 {
+        let key = position_key(board);
+        let fen = format_fen(board);
+        stats.positions += 1;
+        if seen.get(&key).is_some_and(|other| *other != fen) {
+            stats.collisions += 1;
+        }
+        seen.insert(key, fen);
+        key
+    };
+
+    for _ in 0..count {
+        let mut board = Board::empty();
+        board.side_to_move = rng.next() & 1 == 0;
+        for kind in [Piece::WhiteKing, Piece::BlackKing, Piece::WhiteRook, Piece::BlackQueen, Piece::WhiteKnight] {
+            board.state.insert(Coordinate(random_bigint(&mut rng, bits), random_bigint(&mut rng, bits)), kind);
+        }
+        let key = record(&board, &mut stats);
+
+        let mut twin = board.clone();
+        let (coord, piece) = twin.state.iter().next().map(|(coord, piece)| (coord.clone(), *piece)).unwrap();
+        twin.state.remove(&coord);
+        let shift = BigInt::from(rng.below(3) + 1) << 64;
+        twin.state.insert(Coordinate(&coord.0 + shift, coord.1), piece);
+        if twin.state.len() == board.state.len() && record(&twin, &mut stats) == key {
+            stats.twin_collisions += 1;
+        }
+    }
+
+    stats
+}
+
+#[derive(Args, Debug)]
+pub struct HashStatsArgs {
+    #[arg(long, default_value_t = 100000, help = "random positions hashed")]
+    pub count: u64,
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u32).range(1..), help = "spread of the coordinates")]
+    pub bits: u32,
+    #[arg(long, default_value_t = 1)]
+    pub seed: u64,
+}
+
+pub fn run(args: &HashStatsArgs) -> Result<(), String> {
+    let (count, bits, seed) = (args.count, args.bits, args.seed);
+    let stats = collision_stats(count, bits, seed);
+    println!("positions {} collisions {} twin collisions {} rate {:.2e}", stats.positions, stats.collisions, stats.twin_collisions, stats.collisions as f64 / stats.positions.max(1) as f64);
+    Ok(())
+}
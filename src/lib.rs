@@ -0,0 +1,42 @@
+// src/lib.rs
+// The library only exists for the binary and benches/, not as an API to keep conventional
+#![allow(clippy::new_without_default, clippy::should_implement_trait)]
+
+pub mod board;
+pub mod r#move;
+pub mod evaluation;
+pub mod search;
+pub mod pieces;
+pub mod notation;
+pub mod clock;
+pub mod annotate;
+pub mod uci;
+pub mod hash;
+pub mod tt;
+pub mod tools;
+pub mod output;
+pub mod openings;
+pub mod golden;
+pub mod rules;
+pub mod geometry;
+pub mod snapshot;
+pub mod experience;
+pub mod repl;
+pub mod repertoire;
+pub mod puzzles;
+pub mod game;
+pub mod variants;
+pub mod bench;
+pub mod validate;
+pub mod cli;
+pub mod threats;
+pub mod arena;
+pub mod rating;
+pub mod svg;
+pub mod server;
+pub mod capabilities;
+pub mod export;
+pub mod tournament;
+pub mod relay;
+pub mod selfplay;
+pub mod tune;
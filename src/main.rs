@@ -1,55 +1,106 @@
-mod board;
-mod r#move;
-mod evaluation;
-mod search;
-
-use num_bigint::BigInt;
-use board::{Board, Coordinate, Piece};
-use r#move::movegen::{MoveGen, Move};
-use crate::r#move::MoveList;
-use crate::search::Searcher;
+use std::sync::Arc;
+use clap::{CommandFactory, FromArgMatches};
+use infinity_chess_bot::{annotate, arena, bench, capabilities, cli, golden, hash, openings, output, pieces, puzzles, rating, repertoire, repl, selfplay, server, tools, tournament, tune, uci};
+use infinity_chess_bot::cli::{Cli, Command, GlobalOptions};
+use infinity_chess_bot::evaluation::EvalParams;
+use infinity_chess_bot::pieces::PieceSet;
+use infinity_chess_bot::experience::Experience;
 
 fn main() {
-    // let mut board = Board::new();
-    // board.make(
-    //     Move::Normal(
-    //         Coordinate::new(4, 1),
-    //         Coordinate::new(9, -4),
-    //     )
-    // );
-    // board.make(
-    //     Move::Normal(
-    //         Coordinate::new(4, 8),
-    //         Coordinate::new(0, 12),
-    //     )
-    // );
-    // board.make(
-    //     Move::Normal(
-    //         Coordinate::new(9, -4),
-    //         Coordinate::new(9, 12),
-    //     )
-    // );
-    let mut board = Board::empty();
-
-    board.set_piece(Coordinate::new(4, 1), Piece::BlackKing);
-    board.set_piece(Coordinate::new(4, 8), Piece::WhiteKing);
-    board.set_piece(Coordinate::new(3, -5), Piece::WhiteRook);
-    board.set_piece(Coordinate::new(6, -5), Piece::WhiteRook);
-    board.set_piece(Coordinate::new(0, -10), Piece::WhiteRook);
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let globals = &cli.globals;
+    let Some(command) = &cli.command else {
+        Cli::command().print_help().ok();
+        return;
+    };
+    if let Err(err) = cli::check_globals(globals, command, matches.subcommand_name().unwrap_or_default()) {
+        output::fatal(&err);
+    }
 
-    board.show(true);
+    if let Some(format) = globals.output {
+        output::install(format);
+    }
+    let mut options = GlobalOptions { threads: globals.threads, hash: globals.hash, nps: globals.nps, params: None, pieces: None, verify_movegen: false };
+    // the piece set goes first, parameter files are read in its values
+    if let Some(path) = &globals.pieces {
+        match PieceSet::load(path) {
+            Ok(set) => {
+                options.pieces = Some(Arc::new(set.clone()));
+                pieces::install(set);
+            }
+            Err(err) => output::fatal(&format!("Failed to load piece definitions: {}", err)),
+        }
+    }
+    if let Some(path) = &globals.params {
+        let classic = PieceSet::classic();
+        match EvalParams::load(path, options.pieces.as_deref().unwrap_or(&classic)) {
+            Ok(params) => options.params = Some(Arc::new(params)),
+            Err(err) => output::fatal(&format!("Failed to load evaluation parameters: {}", err)),
+        }
+    }
+    if let Some(path) = &globals.log_file {
+        if let Err(err) = output::install_log(path) {
+            output::fatal(&err);
+        }
+    }
+    if globals.verify_movegen {
+        if !cfg!(feature = "verify") {
+            output::fatal("--verify-movegen needs a build with --features verify");
+        }
+        options.verify_movegen = true;
+    }
 
-    let mut move_list = MoveList::new();
-    MoveGen::generate_moves(&board, &mut move_list);
-    let counted = move_list.count;
-    for count in 0..counted {
-        let mv = move_list.moves[count as usize].clone();
-        println!("{:?}", mv);
-    }
-    board.show(true);
-    println!("Evaluation: {:?}", board.evaluate());
+    if let Err(err) = run(command, &options, globals.algebraic) {
+        output::fatal(&err);
+    }
+}
 
-    let mut searcher = Searcher::new();
-    let best_move = searcher.search_position(&mut board, 10);
-    println!("Best move: {:?}", best_move);
-}
\ No newline at end of file
+fn run(command: &Command, options: &GlobalOptions, algebraic: bool) -> Result<(), String> {
+    match command {
+        Command::Uci(args) => {
+            let mut uci = uci::Uci::new();
+            if let Some(path) = &args.experience {
+                let experience = Experience::load(path).map_err(|err| format!("Failed to load experience: {}", err))?;
+                uci.searcher.as_mut().unwrap().experience = Some(experience);
+            }
+            options.apply(uci.searcher.as_mut().unwrap());
+            if let Some(threads) = options.threads {
+                uci.options.threads = threads;
+            }
+            if let Some(mb) = options.hash {
+                uci.options.hash = mb;
+            }
+            uci.options.max_nps = options.nps;
+            uci.run();
+            Ok(())
+        }
+        Command::Play => {
+            let mut repl = repl::Repl::new();
+            repl.algebraic = algebraic;
+            options.apply(&mut repl.searcher);
+            repl.run();
+            Ok(())
+        }
+        Command::Analyze(args) => cli::run_analyze(args, options),
+        Command::Perft(args) => cli::run_perft(args, options),
+        Command::Bench(args) => bench::run(args, options),
+        Command::Match(args) => arena::run(args, options),
+        Command::Tournament(args) => tournament::run(args, options),
+        Command::Ratings(args) => rating::run(args),
+        Command::Selfplay(args) => selfplay::run(args, options),
+        Command::Tune(args) => tune::run(args, options),
+        Command::Serve(args) => server::run(args),
+        Command::Capabilities => {
+            capabilities::report(false);
+            Ok(())
+        }
+        Command::Annotate(args) => annotate::run(args),
+        Command::Puzzles(args) => puzzles::run(args, options),
+        Command::Train(args) => repertoire::run(args),
+        Command::Openings(args) => openings::run(args),
+        Command::Hashstats(args) => hash::run(args),
+        Command::Golden(args) => golden::run(args),
+        Command::Tool(tool) => tools::run(tool),
+    }
+}
@@ -1,86 +1,54 @@
-// use num_bigint::BigInt;
-// use num_traits::ToPrimitive;
-//
-// pub fn encode_move(
-//     source_x: BigInt,
-//     source_y: BigInt,
-//     target_x: BigInt,
-//     target_y: BigInt,
-//     piece: u8,
-//     promoted: u8,
-//     capture: u8,
-//     double: u8,
-//     enpassant: u8,
-//     castling: u8,
-//     infinite: u8,
-// ) -> BigInt {
-//     let mut result = BigInt::from(0);
-//
-//     result |= source_x & BigInt::from(0xFFFF);
-//     result |= (source_y & BigInt::from(0xFFFF)) << 16;
-//     result |= (target_x & BigInt::from(0xFFFF)) << 32;
-//     result |= (target_y & BigInt::from(0xFFFF)) << 48;
-//     result |= BigInt::from(piece) << 56;
-//     result |= BigInt::from(promoted) << 60;
-//     result |= BigInt::from(capture) << 61;
-//     result |= BigInt::from(double) << 62;
-//     result |= BigInt::from(enpassant) << 63;
-//     result |= BigInt::from(castling) << 64;
-//     result |= BigInt::from(infinite) << 65;
-//
-//     result
-// }
-//
-// // Extract source x coordinate
-// pub fn source_x(move_: BigInt) -> BigInt {
-//     return move_ & BigInt::from(0xFFFF);
-// }
-//
-// // Extract source y coordinate
-// pub fn source_y(move_: BigInt) -> BigInt {
-//     return (move_ >> 16) & BigInt::from(0xFFFF);
-// }
-//
-// // Extract target x coordinate
-// pub fn target_x(move_: BigInt) -> BigInt {
-//     return (move_ >> 32) & BigInt::from(0xFFFF);
-// }
-//
-// // Extract target y coordinate
-// pub fn target_y(move_: BigInt) -> BigInt {
-//     return (move_ >> 48) & BigInt::from(0xFFFF);
-// }
-//
-// // Extract piece
-// pub fn get_piece(move_: BigInt) -> u8 {
-//     return ((move_ >> 56) & BigInt::from(0xF)).to_u64().unwrap() as u8;}
-//
-// // Extract promoted piece
-// pub fn promoted(move_: BigInt) -> u8 {
-//     return ((move_ >> 60) & 0x1).to_u8().unwrap();
-// }
-//
-// // Extract capture flag
-// pub fn capture(move_: BigInt) -> u8 {
-//     return ((move_ >> 61) & 0x1).to_u8().unwrap();
-// }
-//
-// // Extract double pawn push flag
-// pub fn double(move_: BigInt) -> u8 {
-//     return ((move_ >> 62) & 0x1).to_u8().unwrap();
-// }
-//
-// // Extract enpassant flag
-// pub fn enpassant(move_: BigInt) -> u8 {
-//     return ((move_ >> 63) & 0x1).to_u8().unwrap();
-// }
-//
-// // Extract castling flag
-// pub fn castling(move_: BigInt) -> u8 {
-//     return ((move_ >> 64) & 0x1).to_u8().unwrap();
-// }
-//
-// // Extract infinite flag
-// pub fn infinite(move_: BigInt) -> u8 {
-//     return ((move_ >> 65) & 0x1).to_u8().unwrap();
-// }
\ No newline at end of file
+// src/move/encode.rs
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use crate::board::Coordinate;
+use crate::hash::square_key;
+use super::movegen::Move;
+
+// Compact keys for tables that only need to recognise a move again (killers, history),
+// so move ordering does not clone or compare BigInts. Coordinates within +-2^27 are packed
+// exactly, anything further out falls back to a hash tagged so it never equals an exact key.
+pub type EncodedMove = u128;
+
+pub const NO_MOVE: EncodedMove = 0;
+
+const COORDINATE_BITS: u32 = 28;
+const SQUARE_BITS: u32 = 60;
+const SQUARE_MASK: u64 = (1 << SQUARE_BITS) - 1;
+const HASHED_SQUARE: u64 = 1 << (SQUARE_BITS - 1);
+
+const NORMAL: u128 = 1;
+const CASTLING: u128 = 2;
+const EN_PASSANT: u128 = 3;
+const PROMOTION: u128 = 4;
+const INFINITE: u128 = 5;
+
+fn pack_coordinate(value: &BigInt) -> Option<u64> {
+    let value = value.to_i32()?;
+    let limit = 1 << (COORDINATE_BITS - 1);
+    if value < -limit || value >= limit {
+        return None;
+    }
+    Some(value as u64 & ((1 << COORDINATE_BITS) - 1))
+}
+
+// 60 bit key of a square
+pub fn encode_square(coord: &Coordinate) -> u64 {
+    match (pack_coordinate(&coord.0), pack_coordinate(&coord.1)) {
+        (Some(x), Some(y)) => x | y << COORDINATE_BITS,
+        _ => (square_key(coord) & SQUARE_MASK) | HASHED_SQUARE,
+    }
+}
+
+// from square | to square << 60 | move type << 120 | promoted piece or direction << 123
+pub fn encode_move(mv: &Move) -> EncodedMove {
+    let squares = |from: &Coordinate, to: &Coordinate| encode_square(from) as u128 | (encode_square(to) as u128) << SQUARE_BITS;
+    match mv {
+        Move::Normal(from, to) => squares(from, to) | NORMAL << 120,
+        Move::Castling(from, to) => squares(from, to) | CASTLING << 120,
+        Move::EnPassant(from, to, _) => squares(from, to) | EN_PASSANT << 120,
+        Move::Promotion(from, to, piece) => squares(from, to) | PROMOTION << 120 | (piece.kind() as u128) << 123,
+        Move::InfiniteMove(from, direction) => encode_square(from) as u128 | INFINITE << 120 | (*direction as u128) << 123,
+        Move::None => NO_MOVE,
+    }
+}
@@ -2,4 +2,6 @@ pub mod movegen;
 pub use movegen::*;
 
 pub mod encode;
-pub use encode::*;
+
+#[cfg(feature = "verify")]
+pub mod reference;
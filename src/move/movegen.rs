@@ -1,47 +1,39 @@
 use crate::board::{Board, Coordinate, Piece};
-use std::collections::HashMap;
+pub use crate::geometry::Direction;
+use crate::geometry::{Offset, KING_OFFSETS, KNIGHT_OFFSETS};
 use num_bigint::BigInt;
 use num_traits::Signed;
-use crate::r#move::encode::*;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Move {
     Normal(Coordinate, Coordinate),
     Castling(Coordinate, Coordinate),
-    EnPassant(Coordinate, Coordinate),
+    EnPassant(Coordinate, Coordinate, Coordinate), // from, to, square of the captured pawn
     Promotion(Coordinate, Coordinate, Piece),
     InfiniteMove(Coordinate, Direction),
     None,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Direction {
-    TopLeft,
-    TopRight,
-    BottomLeft,
-    BottomRight,
-    Top,
-    Bottom,
-    Left,
-    Right,
-}
+pub const WHITE_PROMOTION_RANK: i64 = 8;
+pub const BLACK_PROMOTION_RANK: i64 = 1;
 
+// Grows as needed: a crowded infinite board can have far more moves than any fixed array
 pub struct MoveList {
-    pub moves: [Move; 256],
+    pub moves: Vec<Move>,
     pub count: i32,
 }
 
-const ARRAY_REPEAT_VALUE: Move = Move::None;
 impl MoveList {
     pub fn new() -> MoveList {
         MoveList {
-            moves: [ARRAY_REPEAT_VALUE; 256],
+            moves: Vec::with_capacity(256),
             count: 0,
         }
     }
     pub fn add(&mut self, move_: Move) {
         // store move
-        self.moves[self.count as usize] = move_;
+        self.moves.push(move_);
         // increment move count
         self.count += 1;
     }
@@ -51,175 +43,153 @@ pub struct MoveGen;
 
 impl MoveGen {
     pub fn generate_moves(board: &Board, move_list: &mut MoveList) {
+        #[cfg(feature = "verify")]
+        let start = move_list.moves.len();
         for (coord, piece) in &board.state {
             if (board.side_to_move && piece.is_white()) || (!board.side_to_move && piece.is_black()) {
-                match piece {
-                    Piece::WhitePawn | Piece::BlackPawn => {
-                        Self::generate_pawn_moves(board, coord.clone(), *piece, move_list);
-                    }
-                    Piece::WhiteRook | Piece::BlackRook => {
-                        Self::generate_rook_moves(board, coord.clone(), *piece, move_list);
-                    }
-                    Piece::WhiteKnight | Piece::BlackKnight => {
-                        Self::generate_knight_moves(board, coord.clone(), *piece, move_list);
-                    }
-                    Piece::WhiteBishop | Piece::BlackBishop => {
-                        Self::generate_bishop_moves(board, coord.clone(), *piece, move_list);
-                    }
-                    Piece::WhiteQueen | Piece::BlackQueen => {
-                        Self::generate_queen_moves(board, coord.clone(), *piece, move_list);
-                    }
-                    Piece::WhiteKing | Piece::BlackKing => {
-                        Self::generate_king_moves(board, coord.clone(), *piece, move_list);
-                    }
+                Self::generate_piece_moves(board, coord.clone(), *piece, move_list);
+            }
+        }
+
+        // nothing may land beyond the coordinate limit, when the rules have one
+        if board.rules.coordinate_limit.is_some() {
+            let mut kept = 0;
+            for index in 0..move_list.count as usize {
+                let within = match &move_list.moves[index] {
+                    Move::Normal(_, to) | Move::Castling(_, to) | Move::EnPassant(_, to, _) | Move::Promotion(_, to, _) => board.rules.within_limit(to),
+                    _ => true,
+                };
+                if within {
+                    move_list.moves.swap(kept, index);
+                    kept += 1;
                 }
             }
+            move_list.moves.truncate(kept);
+            move_list.count = kept as i32;
+        }
+
+        #[cfg(feature = "verify")]
+        if board.rules.verify_movegen {
+            super::reference::verify(board, &move_list.moves[start..]);
+        }
+    }
+
+    // Moves of a single piece, regardless of the side to move
+    pub fn generate_piece_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
+        match piece {
+            Piece::WhitePawn | Piece::BlackPawn => {
+                Self::generate_pawn_moves(board, coord, piece, move_list);
+            }
+            Piece::WhiteRook | Piece::BlackRook => {
+                Self::generate_rook_moves(board, coord, piece, move_list);
+            }
+            Piece::WhiteKnight | Piece::BlackKnight => {
+                Self::generate_knight_moves(board, coord, piece, move_list);
+            }
+            Piece::WhiteBishop | Piece::BlackBishop => {
+                Self::generate_bishop_moves(board, coord, piece, move_list);
+            }
+            Piece::WhiteQueen | Piece::BlackQueen => {
+                Self::generate_queen_moves(board, coord, piece, move_list);
+            }
+            Piece::WhiteKing | Piece::BlackKing => {
+                Self::generate_king_moves(board, coord, piece, move_list);
+            }
         }
     }
 
     fn generate_pawn_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
         let direction = if piece == Piece::WhitePawn { 1 } else { -1 };
-        let start_row = if piece == Piece::WhitePawn { 2 } else { 7 };
-        let promotion_row = if piece == Piece::WhitePawn { 8 } else { 1 };
+        let forward_step = Offset::new(0, direction);
+        let promotion_row = if piece == Piece::WhitePawn { WHITE_PROMOTION_RANK } else { BLACK_PROMOTION_RANK };
 
         // Single move forward
-        let forward = Coordinate(coord.0.clone(), coord.1.clone() + direction);
+        let forward = &coord + forward_step;
         if board.get_piece(&forward).is_none() {
             if forward.1 == BigInt::from(promotion_row) {
-                move_list.add(Move::Promotion(coord.clone(), forward.clone(), Piece::WhiteQueen));
-                move_list.add(Move::Promotion(coord.clone(), forward.clone(), Piece::WhiteRook));
-                move_list.add(Move::Promotion(coord.clone(), forward.clone(), Piece::WhiteKnight));
-                move_list.add(Move::Promotion(coord.clone(), forward.clone(), Piece::WhiteBishop));
+                Self::add_promotions(coord.clone(), forward.clone(), piece.is_white(), move_list);
             } else {
                 move_list.add(Move::Normal(coord.clone(), forward.clone()));
             }
         }
 
-        // Double move forward
-        if coord.1 == BigInt::from(start_row) {
-            let double_forward = Coordinate(coord.0.clone(), coord.1.clone() + 2 * direction);
-            if board.get_piece(&double_forward).is_none() && board.get_piece(&forward).is_none() {
-                move_list.add(Move::Normal(coord.clone(), double_forward.clone()));
+        // Longer first moves from a start rank, as far as the rules allow and nothing is in the way
+        if board.rules.is_pawn_start(piece.is_white(), &coord.1) {
+            let mut target = forward.clone();
+            for _ in 2..=board.rules.pawn_push {
+                if board.get_piece(&target).is_some() || target.1 == BigInt::from(promotion_row) {
+                    break;
+                }
+                target = &target + forward_step;
+                if board.get_piece(&target).is_some() {
+                    break;
+                }
+                if target.1 == BigInt::from(promotion_row) {
+                    Self::add_promotions(coord.clone(), target.clone(), piece.is_white(), move_list);
+                } else {
+                    move_list.add(Move::Normal(coord.clone(), target.clone()));
+                }
             }
         }
 
         // Capture moves
-        let capture_directions = [1, -1];
-        for &dx in &capture_directions {
-            let capture = Coordinate(coord.0.clone() + BigInt::from(dx), coord.1.clone() + BigInt::from(direction));
+        for dx in [1, -1] {
+            let capture = &coord + Offset::new(dx, direction);
             if let Some(target_piece) = board.get_piece(&capture) {
                 if Self::is_opponent_piece(piece, *target_piece) {
                     if capture.1 == BigInt::from(promotion_row) {
-                        move_list.add(Move::Promotion(coord.clone(), capture.clone(), Piece::WhiteQueen));
-                        move_list.add(Move::Promotion(coord.clone(), capture.clone(), Piece::WhiteRook));
-                        move_list.add(Move::Promotion(coord.clone(), capture.clone(), Piece::WhiteKnight));
-                        move_list.add(Move::Promotion(coord.clone(), capture.clone(), Piece::WhiteBishop));
+                        Self::add_promotions(coord.clone(), capture.clone(), piece.is_white(), move_list);
                     } else {
                         move_list.add(Move::Normal(coord.clone(), capture.clone()));
                     }
                 }
-            } else if let Some(en_passant) = board.en_passant.clone() {
-                if capture == en_passant {
-                    move_list.add(Move::EnPassant(coord.clone(), capture.clone()));
+            } else if board.en_passant.contains(&capture) {
+                let Some(captured) = board.en_passant_pawn() else { continue };
+                if board.get_piece(&captured).is_some_and(|target_piece| target_piece.kind() == Piece::WhitePawn.kind() && Self::is_opponent_piece(piece, *target_piece)) {
+                    move_list.add(Move::EnPassant(coord.clone(), capture, captured));
                 }
             }
         }
     }
 
-    fn generate_rook_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
-        let directions = [
-            (0, 1),  // Up
-            (0, -1), // Down
-            (1, 0),  // Right
-            (-1, 0), // Left
-        ];
-
-        for &(dx, dy) in &directions {
-            let mut path_clear = true;
-            let mut closest_piece: Option<(&Coordinate, &Piece)> = None;
-
-            for (target_coord, target_piece) in &board.state {
-                if (dx == 0 && target_coord.0 == coord.0 && ((dy > 0 && target_coord.1 > coord.1) || (dy < 0 && target_coord.1 < coord.1))) ||
-                    (dy == 0 && target_coord.1 == coord.1 && ((dx > 0 && target_coord.0 > coord.0) || (dx < 0 && target_coord.0 < coord.0))) {
-                    if closest_piece.is_none() ||
-                        ((dx == 0 && (target_coord.1.clone() - coord.1.clone()).abs() < (closest_piece.unwrap().0.1.clone() - coord.1.clone()).abs()) ||
-                            (dy == 0 && (target_coord.0.clone() - coord.0.clone()).abs() < (closest_piece.unwrap().0.0.clone() - coord.0.clone()).abs())) {
-                        closest_piece = Some((target_coord, target_piece));
-                    }
-                }
-            }
-
-            if let Some((target_coord, target_piece)) = closest_piece {
-                if Self::is_opponent_piece(piece, *target_piece) {
-                    move_list.add(Move::Normal(coord.clone(), target_coord.clone()));
-                }
-                path_clear = false;
-            }
-
-            if path_clear {
-                let infinite_move = match (dx, dy) {
-                    (0, 1) => Direction::Top,
-                    (0, -1) => Direction::Bottom,
-                    (1, 0) => Direction::Right,
-                    (-1, 0) => Direction::Left,
-                    _ => unsafe { std::hint::unreachable_unchecked() },
-                };
-                move_list.add(Move::InfiniteMove(coord.clone(), infinite_move));
-            }
+    // Promotions to the pawn's own colour
+    fn add_promotions(from: Coordinate, to: Coordinate, white: bool, move_list: &mut MoveList) {
+        for kind in [Piece::WhiteQueen, Piece::WhiteRook, Piece::WhiteKnight, Piece::WhiteBishop] {
+            move_list.add(Move::Promotion(from.clone(), to.clone(), Piece::from_kind(kind.kind(), white)));
         }
     }
 
-    fn generate_bishop_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
-        let directions = [
-            (1, 1),   // Top-right
-            (1, -1),  // Bottom-right
-            (-1, 1),  // Top-left
-            (-1, -1), // Bottom-left
-        ];
-
-        for &(dx, dy) in &directions {
-            let mut path_clear = true;
-            let mut closest_piece: Option<(&Coordinate, &Piece)> = None;
-
-            for (target_coord, target_piece) in &board.state {
-                if (target_coord.0.clone() - coord.0.clone()).abs() == (target_coord.1.clone() - coord.1.clone()).abs() &&
-                    ((dx > 0 && target_coord.0 > coord.0) || (dx < 0 && target_coord.0 < coord.0)) &&
-                    ((dy > 0 && target_coord.1 > coord.1) || (dy < 0 && target_coord.1 < coord.1)) {
-                    if closest_piece.is_none() ||
-                        ((target_coord.0.clone() - coord.0.clone()).abs() < (closest_piece.unwrap().0.0.clone() - coord.0.clone()).abs()) {
-                        closest_piece = Some((target_coord, target_piece));
+    // Sliders capture the nearest piece on each ray if it is an enemy, and leave towards
+    // infinity along rays that are empty. Under a coordinate limit they go to the last square
+    // before it instead.
+    fn generate_slider_moves(board: &Board, coord: Coordinate, piece: Piece, directions: &[Direction], move_list: &mut MoveList) {
+        for &direction in directions {
+            match board.nearest_piece_in_direction(&coord, direction) {
+                Some((_, target, target_piece)) => {
+                    if Self::is_opponent_piece(piece, target_piece) {
+                        move_list.add(Move::Normal(coord.clone(), target.clone()));
                     }
                 }
+                None => match board.rules.steps_within_limit(&coord, direction) {
+                    None => move_list.add(Move::InfiniteMove(coord.clone(), direction)),
+                    Some(steps) if steps.is_positive() => move_list.add(Move::Normal(coord.clone(), coord.step(direction, &steps))),
+                    Some(_) => {}
+                },
             }
+        }
+    }
 
-            if let Some((target_coord, target_piece)) = closest_piece {
-                if Self::is_opponent_piece(piece, *target_piece) {
-                    move_list.add(Move::Normal(coord.clone(), target_coord.clone()));
-                }
-                path_clear = false;
-            }
+    fn generate_rook_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
+        Self::generate_slider_moves(board, coord, piece, &Direction::ORTHOGONAL, move_list);
+    }
 
-            if path_clear {
-                let infinite_move = match (dx, dy) {
-                    (1, 1) => Direction::TopRight,
-                    (1, -1) => Direction::BottomRight,
-                    (-1, 1) => Direction::TopLeft,
-                    (-1, -1) => Direction::BottomLeft,
-                    _ => unsafe { std::hint::unreachable_unchecked() },
-                };
-                move_list.add(Move::InfiniteMove(coord.clone(), infinite_move));
-            }
-        }
+    fn generate_bishop_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
+        Self::generate_slider_moves(board, coord, piece, &Direction::DIAGONAL, move_list);
     }
 
     fn generate_knight_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
-        let knight_moves = [
-            (2, 1), (2, -1), (-2, 1), (-2, -1),
-            (1, 2), (1, -2), (-1, 2), (-1, -2),
-        ];
-
-        for &(dx, dy) in &knight_moves {
-            let next_coord = Coordinate(coord.0.clone() + dx, coord.1.clone() + dy);
+        for offset in KNIGHT_OFFSETS {
+            let next_coord = &coord + offset;
             if let Some(target_piece) = board.get_piece(&next_coord) {
                 if Self::is_opponent_piece(piece, *target_piece) {
                     move_list.add(Move::Normal(coord.clone(), next_coord.clone()));
@@ -236,13 +206,8 @@ impl MoveGen {
     }
 
     fn generate_king_moves(board: &Board, coord: Coordinate, piece: Piece, move_list: &mut MoveList) {
-        let king_moves = [
-            (1, 0), (1, 1), (0, 1), (-1, 1),
-            (-1, 0), (-1, -1), (0, -1), (1, -1),
-        ];
-
-        for &(dx, dy) in &king_moves {
-            let next_coord = Coordinate(coord.0.clone() + BigInt::from(dx), coord.1.clone() + BigInt::from(dy));
+        for offset in KING_OFFSETS {
+            let next_coord = &coord + offset;
             if let Some(target_piece) = board.get_piece(&next_coord) {
                 if Self::is_opponent_piece(piece, *target_piece) {
                     move_list.add(Move::Normal(coord.clone(), next_coord.clone()));
@@ -252,20 +217,23 @@ impl MoveGen {
             }
         }
 
-        // Castling logic
-        if piece == Piece::WhiteKing && coord == Coordinate::new(5, 1) {
-            if board.castling_rights & 0b1000 != 0 && board.get_piece(&Coordinate::new(6, 1)).is_none() && board.get_piece(&Coordinate::new(7, 1)).is_none() {
-                move_list.add(Move::Castling(coord.clone(), Coordinate::new(7, 1)));
-            }
-            if board.castling_rights & 0b0100 != 0 && board.get_piece(&Coordinate::new(4, 1)).is_none() && board.get_piece(&Coordinate::new(3, 1)).is_none() && board.get_piece(&Coordinate::new(2, 1)).is_none() {
-                move_list.add(Move::Castling(coord.clone(), Coordinate::new(3, 1)));
-            }
-        } else if piece == Piece::BlackKing && coord == Coordinate::new(5, 8) {
-            if board.castling_rights & 0b0010 != 0 && board.get_piece(&Coordinate::new(6, 8)).is_none() && board.get_piece(&Coordinate::new(7, 8)).is_none() {
-                move_list.add(Move::Castling(coord.clone(), Coordinate::new(7, 8)));
-            }
-            if board.castling_rights & 0b0001 != 0 && board.get_piece(&Coordinate::new(4, 8)).is_none() && board.get_piece(&Coordinate::new(3, 8)).is_none() && board.get_piece(&Coordinate::new(2, 8)).is_none() {
-                move_list.add(Move::Castling(coord.clone(), Coordinate::new(3, 8)));
+        // Castling: the king moves two squares towards a rook on its rank that still has its
+        // right, the rook jumps to the square the king passes over. Everything between them must
+        // be empty and the king may not castle out of, through or into check.
+        let white = piece.is_white();
+        let rook = Piece::from_kind(Piece::WhiteRook.kind(), white);
+        let mut rights = board.castling_rights.iter()
+            .filter(|right| right.white == white && right.rook.1 == coord.1 && board.get_piece(&right.rook) == Some(&rook))
+            .peekable();
+        if rights.peek().is_none() || board.is_square_attacked(&coord, !white) {
+            return;
+        }
+        for right in rights {
+            let Some((direction, distance)) = coord.between(&right.rook) else { continue };
+            if distance >= BigInt::from(3)
+                && board.path_clear(&coord, &right.rook)
+                && !(1..=2).any(|step| board.is_square_attacked(&coord.step(direction, &BigInt::from(step)), !white)) {
+                move_list.add(Move::Castling(coord.clone(), coord.step(direction, &BigInt::from(2))));
             }
         }
     }
@@ -0,0 +1,177 @@
+// src/move/reference.rs
+use std::collections::HashMap;
+use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
+use crate::board::{Board, Coordinate, Piece};
+use crate::notation::format_fen;
+use super::{Direction, Move, BLACK_PROMOTION_RANK, WHITE_PROMOTION_RANK};
+
+// A move generator written to be obviously right rather than fast: every piece looks at every
+// other piece directly and nothing is shared with MoveGen except the rules themselves.
+pub fn reference_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for (coord, piece) in &board.state {
+        if piece.is_white() != board.side_to_move {
+            continue;
+        }
+        let kind = piece.kind();
+        if kind == Piece::WhitePawn.kind() {
+            pawn_moves(board, coord, *piece, &mut moves);
+        } else if kind == Piece::WhiteKnight.kind() {
+            for (dx, dy) in [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)] {
+                step_move(board, coord, *piece, dx, dy, &mut moves);
+            }
+        } else if kind == Piece::WhiteKing.kind() {
+            for (dx, dy) in [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)] {
+                step_move(board, coord, *piece, dx, dy, &mut moves);
+            }
+            castling_moves(board, coord, *piece, &mut moves);
+        } else {
+            let rook = kind == Piece::WhiteRook.kind() || kind == Piece::WhiteQueen.kind();
+            let bishop = kind == Piece::WhiteBishop.kind() || kind == Piece::WhiteQueen.kind();
+            for direction in Direction::ALL {
+                if (direction.is_orthogonal() && rook) || (!direction.is_orthogonal() && bishop) {
+                    ray_moves(board, coord, *piece, direction, &mut moves);
+                }
+            }
+        }
+    }
+    moves.retain(|mv| match mv {
+        Move::Normal(_, to) | Move::Castling(_, to) | Move::EnPassant(_, to, _) | Move::Promotion(_, to, _) => board.rules.within_limit(to),
+        _ => true,
+    });
+    moves
+}
+
+fn enemy(piece: Piece, target: Piece) -> bool {
+    piece.is_white() != target.is_white()
+}
+
+fn step_move(board: &Board, from: &Coordinate, piece: Piece, dx: i64, dy: i64, moves: &mut Vec<Move>) {
+    let to = Coordinate(&from.0 + dx, &from.1 + dy);
+    if board.get_piece(&to).is_none_or(|target| enemy(piece, *target)) {
+        moves.push(Move::Normal(from.clone(), to));
+    }
+}
+
+fn pawn_moves(board: &Board, from: &Coordinate, piece: Piece, moves: &mut Vec<Move>) {
+    let white = piece.is_white();
+    let dy = if white { 1 } else { -1 };
+    let promotion = BigInt::from(if white { WHITE_PROMOTION_RANK } else { BLACK_PROMOTION_RANK });
+    let push = |to: Coordinate, moves: &mut Vec<Move>| {
+        if to.1 == promotion {
+            for kind in [Piece::WhiteQueen, Piece::WhiteRook, Piece::WhiteBishop, Piece::WhiteKnight] {
+                moves.push(Move::Promotion(from.clone(), to.clone(), Piece::from_kind(kind.kind(), white)));
+            }
+        } else {
+            moves.push(Move::Normal(from.clone(), to));
+        }
+    };
+
+    // forward while the squares are empty, one step or up to pawn_push from a start rank,
+    // never past the promotion rank
+    let steps = if board.rules.is_pawn_start(white, &from.1) { board.rules.pawn_push.max(1) } else { 1 };
+    for step in 1..=steps {
+        let to = Coordinate(from.0.clone(), &from.1 + dy * step as i64);
+        if board.get_piece(&to).is_some() {
+            break;
+        }
+        let last = to.1 == promotion;
+        push(to, moves);
+        if last {
+            break;
+        }
+    }
+
+    for dx in [-1, 1] {
+        let to = Coordinate(&from.0 + dx, &from.1 + dy);
+        match board.get_piece(&to) {
+            Some(target) if enemy(piece, *target) => push(to, moves),
+            Some(_) => {}
+            None if board.en_passant.contains(&to) => {
+                if let Some(pawn) = board.en_passant_pawn() {
+                    if board.get_piece(&pawn).is_some_and(|target| target.kind() == Piece::WhitePawn.kind() && enemy(piece, *target)) {
+                        moves.push(Move::EnPassant(from.clone(), to, pawn));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+// Distance along a ray to a square, None when the square isn't on it
+fn on_ray(from: &Coordinate, to: &Coordinate, direction: Direction) -> Option<BigInt> {
+    let offset = direction.offset();
+    let (dx, dy) = (&to.0 - &from.0, &to.1 - &from.1);
+    let distance = if offset.dx != 0 { &dx * offset.dx } else { &dy * offset.dy };
+    (distance.is_positive() && dx == &distance * offset.dx && dy == &distance * offset.dy).then_some(distance)
+}
+
+fn ray_moves(board: &Board, from: &Coordinate, piece: Piece, direction: Direction, moves: &mut Vec<Move>) {
+    let nearest = board.state.iter()
+        .filter_map(|(coord, target)| Some((on_ray(from, coord, direction)?, coord, *target)))
+        .min_by(|a, b| a.0.cmp(&b.0));
+    match nearest {
+        Some((_, to, target)) => {
+            if enemy(piece, target) {
+                moves.push(Move::Normal(from.clone(), to.clone()));
+            }
+        }
+        None => match board.rules.steps_within_limit(from, direction) {
+            None => moves.push(Move::InfiniteMove(from.clone(), direction)),
+            Some(steps) if steps.is_positive() => moves.push(Move::Normal(from.clone(), from.step(direction, &steps))),
+            Some(_) => {}
+        },
+    }
+}
+
+fn castling_moves(board: &Board, king: &Coordinate, piece: Piece, moves: &mut Vec<Move>) {
+    let white = piece.is_white();
+    if board.is_square_attacked(king, !white) {
+        return;
+    }
+    for right in board.castling_rights.iter().filter(|right| right.white == white && right.rook.1 == king.1) {
+        if board.get_piece(&right.rook) != Some(&Piece::from_kind(Piece::WhiteRook.kind(), white)) {
+            continue;
+        }
+        let dx: i64 = if right.rook.0 > king.0 { 1 } else { -1 };
+        let gap = (&right.rook.0 - &king.0).abs();
+        if gap < BigInt::from(3) {
+            continue;
+        }
+        let empty = board.state.keys().all(|coord| coord.1 != king.1 || (&coord.0 - &king.0) * dx <= BigInt::zero() || (&coord.0 - &king.0) * dx >= gap);
+        let safe = (1..=2).all(|step| !board.is_square_attacked(&Coordinate(&king.0 + dx * step, king.1.clone()), !white));
+        if empty && safe {
+            moves.push(Move::Castling(king.clone(), Coordinate(&king.0 + 2 * dx, king.1.clone())));
+        }
+    }
+}
+
+// Compares a generated list with the reference, as multisets, and the coordinate arithmetic
+// between every pair of pieces with its BigInt version. A mismatch is a bug in the generator the
+// search can't be trusted past, so the position is dumped and the process aborted.
+pub fn verify(board: &Board, generated: &[Move]) {
+    for a in board.state.keys() {
+        for b in board.state.keys() {
+            crate::geometry::cross_check(a, b);
+        }
+    }
+    let mut counts: HashMap<&Move, i32> = HashMap::new();
+    for mv in generated {
+        *counts.entry(mv).or_default() += 1;
+    }
+    let expected = reference_moves(board);
+    for mv in &expected {
+        *counts.entry(mv).or_default() -= 1;
+    }
+    if counts.values().all(|count| *count == 0) {
+        return;
+    }
+    let list = |wanted: fn(i32) -> bool| counts.iter().filter(|(_, count)| wanted(**count)).map(|(mv, count)| format!("{:?} x{}", mv, count.abs())).collect::<Vec<_>>().join(", ");
+    eprintln!("move generator mismatch in {}", format_fen(board));
+    eprintln!("generated {} moves, reference {}", generated.len(), expected.len());
+    eprintln!("extra: {}", list(|count| count > 0));
+    eprintln!("missing: {}", list(|count| count < 0));
+    std::process::abort();
+}
@@ -0,0 +1,524 @@
+// src/notation.rs
+use std::fmt;
+use num_bigint::BigInt;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+use crate::board::{Board, CastlingRight, Coordinate, Piece};
+use crate::pieces::piece_set;
+use crate::rules::{RuleSet, WinCondition};
+use crate::r#move::{Direction, Move, MoveGen, MoveList};
+
+// Numbers up to this many digits are always printed in plain decimal
+const PLAIN_DIGITS: usize = 12;
+// Largest mantissa/remainder (in digits) still worth printing in shorthand
+const SHORT_DIGITS: usize = 6;
+// Refuse exponents that would allocate absurd amounts of memory
+const MAX_EXPONENT: u32 = 100_000;
+// Files 1 to 8 as in classical chess, accepted everywhere a square is and printed in moves
+// where the caller asks for them, see format_square
+const FILE_LETTERS: &str = "abcdefgh";
+
+// Parse an integer written in decimal or shorthand notation. Accepted forms are sums and
+// differences of terms, where each term is a plain integer, `<mantissa>e<exponent>` or
+// `<base>^<exponent>`, e.g. `42`, `-7`, `1e12`, `25e3`, `10^100+5`, `-2^64-1`.
+pub fn parse_integer(text: &str) -> Result<BigInt, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("empty number".to_string());
+    }
+
+    let mut total = BigInt::zero();
+    let mut rest = text;
+    let mut negative = false;
+
+    if let Some(stripped) = rest.strip_prefix('-') {
+        negative = true;
+        rest = stripped;
+    } else if let Some(stripped) = rest.strip_prefix('+') {
+        rest = stripped;
+    }
+
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = parse_term(&rest[..end]).map_err(|err| format!("{} in '{}'", err, text))?;
+        if negative {
+            total -= term;
+        } else {
+            total += term;
+        }
+
+        if end == rest.len() {
+            break;
+        }
+        negative = rest[end..].starts_with('-');
+        rest = &rest[end + 1..];
+    }
+
+    Ok(total)
+}
+
+fn parse_term(term: &str) -> Result<BigInt, String> {
+    if let Some((base, exponent)) = term.split_once('^') {
+        return Ok(parse_digits(base)?.pow(parse_exponent(exponent)?));
+    }
+    if let Some((mantissa, exponent)) = term.split_once(['e', 'E']) {
+        return Ok(parse_digits(mantissa)? * BigInt::from(10).pow(parse_exponent(exponent)?));
+    }
+    parse_digits(term)
+}
+
+fn parse_digits(digits: &str) -> Result<BigInt, String> {
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(format!("invalid digits '{}'", digits));
+    }
+    digits.parse::<BigInt>().map_err(|err| err.to_string())
+}
+
+fn parse_exponent(exponent: &str) -> Result<u32, String> {
+    match exponent.parse::<u32>() {
+        Ok(value) if value <= MAX_EXPONENT => Ok(value),
+        Ok(_) => Err(format!("exponent {} is too large", exponent)),
+        Err(_) => Err(format!("invalid exponent '{}'", exponent)),
+    }
+}
+
+// Print an integer, switching to shorthand when the decimal expansion would be unreadable.
+// The output always parses back to the same value with `parse_integer`.
+pub fn format_integer(value: &BigInt) -> String {
+    let digits = value.abs().to_string();
+    if digits.len() <= PLAIN_DIGITS {
+        return value.to_string();
+    }
+    let sign = if value.is_negative() { "-" } else { "" };
+
+    // mantissa * 10^zeros, e.g. 1e12 or 25e30
+    let mantissa = digits.trim_end_matches('0');
+    if mantissa.len() <= SHORT_DIGITS {
+        return format!("{}{}e{}", sign, mantissa, digits.len() - mantissa.len());
+    }
+
+    // nearest power of ten plus a short remainder, e.g. 10^100+5 or 10^100-3
+    let magnitude = value.abs();
+    for exponent in [digits.len() - 1, digits.len()] {
+        let remainder = &magnitude - BigInt::from(10).pow(exponent as u32);
+        if remainder.abs().to_string().len() <= SHORT_DIGITS {
+            let offset = if remainder.is_negative() {
+                format!("{}", remainder)
+            } else {
+                format!("+{}", remainder)
+            };
+            // a leading minus applies to the first term only, so flip the remainder sign too
+            return if value.is_negative() {
+                format!("-10^{}{}", exponent, negate_offset(&offset))
+            } else {
+                format!("10^{}{}", exponent, offset)
+            };
+        }
+    }
+
+    value.to_string()
+}
+
+fn negate_offset(offset: &str) -> String {
+    match offset.strip_prefix('-') {
+        Some(stripped) => format!("+{}", stripped),
+        None => format!("-{}", &offset[1..]),
+    }
+}
+
+// `x,y`, optionally in parentheses, or a file letter and a rank such as `e4` or `h-20`
+pub fn parse_coordinate(text: &str) -> Result<Coordinate, String> {
+    if let Some(coord) = parse_algebraic(text.trim()) {
+        return Ok(coord);
+    }
+    let (x, y) = text.trim().trim_start_matches('(').trim_end_matches(')').split_once(',')
+        .ok_or_else(|| format!("expected 'x,y' coordinate, found '{}'", text))?;
+    Ok(Coordinate(parse_integer(x)?, parse_integer(y)?))
+}
+
+pub fn format_coordinate(coord: &Coordinate) -> String {
+    format!("{},{}", format_integer(&coord.0), format_integer(&coord.1))
+}
+
+fn parse_algebraic(text: &str) -> Option<Coordinate> {
+    let file = FILE_LETTERS.find(text.chars().next()?)? as i64 + 1;
+    let rank = &text[1..];
+    if rank.is_empty() || rank.contains(',') {
+        return None;
+    }
+    Some(Coordinate(BigInt::from(file), parse_integer(rank).ok()?))
+}
+
+// Squares of moves, with a file letter when asked for and the file is 1 to 8. Only what people
+// read asks for them; the protocol, experience files and exports stay numeric.
+pub fn format_square(coord: &Coordinate, algebraic: bool) -> String {
+    match coord.0.to_usize().filter(|file| algebraic && (1..=8).contains(file)) {
+        Some(file) => format!("{}{}", &FILE_LETTERS[file - 1..file], format_integer(&coord.1)),
+        None => format_coordinate(coord),
+    }
+}
+
+// `e2e4` or `e7e8q`, both squares with a file letter and a plain rank. Anything else, and
+// all non-ASCII text, is left to the `from>to` parser.
+fn split_compact_move(text: &str) -> Option<(&str, &str, Option<char>)> {
+    if !text.is_ascii() {
+        return None;
+    }
+    let second = text.char_indices().skip(1).find(|(_, letter)| FILE_LETTERS.contains(*letter))?.0;
+    let (from, rest) = text.split_at(second);
+    let (to, promotion) = match rest.chars().last() {
+        Some(letter) if "qrbnQRBN".contains(letter) => (rest[..rest.len() - 1].trim_end_matches('='), Some(letter.to_ascii_lowercase())),
+        _ => (rest, None),
+    };
+    let plain = |square: &str| square.len() > 1 && square[1..].trim_start_matches('-').chars().all(|digit| digit.is_ascii_digit()) && !square[1..].trim_start_matches('-').is_empty();
+    (plain(from) && plain(to)).then_some((from, to, promotion))
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_coordinate(self))
+    }
+}
+
+// Offsets from an anchor always carry an explicit sign, e.g. +3 or -10^20
+pub fn format_relative_integer(offset: &BigInt) -> String {
+    if offset.is_negative() {
+        format_integer(offset)
+    } else {
+        format!("+{}", format_integer(offset))
+    }
+}
+
+pub fn format_relative_coordinate(coord: &Coordinate, anchor: &Coordinate) -> String {
+    let offset = coord.relative_to(anchor);
+    format!("{},{}", format_relative_integer(&offset.0), format_relative_integer(&offset.1))
+}
+
+pub fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::TopLeft => "topleft",
+        Direction::TopRight => "topright",
+        Direction::BottomLeft => "bottomleft",
+        Direction::BottomRight => "bottomright",
+        Direction::Top => "top",
+        Direction::Bottom => "bottom",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+// Moves are written as `from>to`, promotions append `=<letter>` and infinite slides name
+// their direction, e.g. `5,2>5,4`, `1,7>1,8=Q`, `3,-5>inf:top`. With an anchor every
+// square is printed as a signed offset from it.
+pub fn format_move(mv: &Move, anchor: Option<&Coordinate>, algebraic: bool) -> String {
+    let square = |coord: &Coordinate| match anchor {
+        Some(anchor) => format_relative_coordinate(coord, anchor),
+        None => format_square(coord, algebraic),
+    };
+
+    match mv {
+        Move::Normal(from, to) | Move::Castling(from, to) | Move::EnPassant(from, to, _) => {
+            format!("{}>{}", square(from), square(to))
+        }
+        Move::Promotion(from, to, piece) => format!("{}>{}={}", square(from), square(to), piece.letter()),
+        Move::InfiniteMove(from, direction) => format!("{}>inf:{}", square(from), direction_name(*direction)),
+        Move::None => "none".to_string(),
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_move(self, None, false))
+    }
+}
+
+pub fn parse_direction(name: &str) -> Result<Direction, String> {
+    Direction::ALL.into_iter().find(|direction| direction_name(*direction) == name.trim().to_ascii_lowercase())
+        .ok_or_else(|| format!("unknown direction '{}'", name))
+}
+
+// Parse a move written by `format_move`, or as `e2e4` near the origin, and resolve it against
+// the moves generated on the board, so the result carries the right move kind (castling, en passant, ...).
+pub fn parse_move(board: &Board, text: &str) -> Result<Move, String> {
+    if let Some((from, to, promotion)) = split_compact_move(text.trim()) {
+        let promotion = promotion.map_or(String::new(), |letter| format!("={}", letter));
+        return parse_move(board, &format!("{}>{}{}", from, to, promotion));
+    }
+    let (from, target) = text.trim().split_once('>')
+        .ok_or_else(|| format!("expected 'from>to' or 'e2e4' move, found '{}'", text))?;
+    let from = parse_coordinate(from)?;
+
+    let mut move_list = MoveList::new();
+    MoveGen::generate_moves(board, &mut move_list);
+    let moves = &move_list.moves[..move_list.count as usize];
+
+    let found = if let Some(direction) = target.strip_prefix("inf:") {
+        let direction = parse_direction(direction)?;
+        if board.rules.coordinate_limit.is_some() {
+            return Err(format!("move '{}' leaves the coordinate limit", text));
+        }
+        moves.iter().find(|mv| matches!(mv, Move::InfiniteMove(source, dir) if *source == from && *dir == direction))
+    } else {
+        let (to, promotion) = match target.split_once('=') {
+            Some((to, letter)) => {
+                let mut letters = letter.chars();
+                match (letters.next(), letters.next()) {
+                    (Some(letter), None) => (to, Some(letter.to_ascii_lowercase())),
+                    _ => return Err(format!("invalid promotion '{}'", letter)),
+                }
+            }
+            None => (target, None),
+        };
+        let to = parse_coordinate(to)?;
+        if !board.rules.within_limit(&to) {
+            return Err(format!("move '{}' leaves the coordinate limit", text));
+        }
+        moves.iter().find(|mv| match mv {
+            Move::Normal(source, dest) | Move::Castling(source, dest) | Move::EnPassant(source, dest, _) => {
+                promotion.is_none() && *source == from && *dest == to
+            }
+            Move::Promotion(source, dest, piece) => {
+                *source == from && *dest == to && promotion == Some(piece.letter().to_ascii_lowercase())
+            }
+            _ => false,
+        })
+    };
+
+    found.cloned().ok_or_else(|| format!("illegal move '{}'", text))
+}
+
+// Extended FEN for unbounded boards: `<pieces> <side> <castling> <en passant> [<pawn ranks> [<win condition>]]`,
+// where the pieces are `;`-separated `<letter><x>,<y>` entries, e.g. `K5,1;k5,8;R1e6,-3 w - -`.
+// Castling rights are `-`, KQkq as in classical FEN when only the classical corners have them,
+// or otherwise the rooks that may still castle, e.g. `R-2,1|R8,1|r1,8`. The en passant field
+// is `-` or the squares the last pawn push skipped, `|`-separated when it skipped several. The
+// optional next field lists the pawn start ranks as `<white>/<black>`, e.g. `2,3/7,6`, with
+// `:<n>` added when pawns may push n squares from them instead of 2. It is only written when
+// it differs from the classical 2/7. After it `royalcapture` may follow, checkmate being the
+// default.
+pub fn parse_fen(text: &str) -> Result<Board, String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if !(4..=6).contains(&fields.len()) {
+        return Err(format!("expected '<pieces> <side> <castling> <en passant> [<pawn ranks> [<win condition>]]', found '{}'", text));
+    }
+
+    let mut board = Board::empty();
+    for entry in fields[0].split(';').filter(|entry| !entry.is_empty()) {
+        let letter = entry.chars().next().unwrap();
+        let piece = piece_set().piece_for_letter(letter)
+            .ok_or_else(|| format!("unknown piece letter '{}'", letter))?;
+        let coord = parse_coordinate(&entry[letter.len_utf8()..])?;
+        if board.state.insert(coord.clone(), piece).is_some() {
+            return Err(format!("square {} is occupied twice", coord));
+        }
+    }
+
+    board.side_to_move = match fields[1] {
+        "w" => true,
+        "b" => false,
+        side => return Err(format!("invalid side to move '{}'", side)),
+    };
+
+    board.castling_rights = parse_castling(fields[2])?;
+
+    board.en_passant = match fields[3] {
+        "-" => Vec::new(),
+        squares => squares.split('|').map(parse_coordinate).collect::<Result<_, _>>()?,
+    };
+
+    if let Some(ranks) = fields.get(4) {
+        board.rules = RuleSet::parse_pawn_ranks(ranks)?;
+    }
+    if let Some(condition) = fields.get(5) {
+        board.rules.win_condition = WinCondition::parse(condition).ok_or_else(|| format!("unknown win condition '{}'", condition))?;
+    }
+
+    Ok(board)
+}
+
+pub fn parse_castling(text: &str) -> Result<Vec<CastlingRight>, String> {
+    if text == "-" {
+        return Ok(Vec::new());
+    }
+    if !text.contains(',') {
+        let classical = CastlingRight::classical();
+        return text.chars()
+            .map(|letter| "KQkq".find(letter).map(|index| classical[index].clone()).ok_or_else(|| format!("invalid castling rights '{}'", text)))
+            .collect();
+    }
+    text.split('|')
+        .map(|entry| {
+            let mut letters = entry.chars();
+            let white = match letters.next().and_then(|letter| piece_set().piece_for_letter(letter)) {
+                Some(Piece::WhiteRook) => true,
+                Some(Piece::BlackRook) => false,
+                _ => return Err(format!("invalid castling right '{}', expected a rook and its square", entry)),
+            };
+            Ok(CastlingRight { rook: parse_coordinate(letters.as_str())?, white })
+        })
+        .collect()
+}
+
+// KQkq order for the classical corners, rooks from the top rank down and left to right otherwise
+pub fn format_castling(rights: &[CastlingRight]) -> String {
+    if rights.is_empty() {
+        return "-".to_string();
+    }
+    let classical = CastlingRight::classical();
+    if rights.iter().all(|right| classical.contains(right)) {
+        return classical.iter().zip("KQkq".chars()).filter(|(right, _)| rights.contains(right)).map(|(_, letter)| letter).collect();
+    }
+    let mut rights: Vec<&CastlingRight> = rights.iter().collect();
+    rights.sort_by(|a, b| b.rook.1.cmp(&a.rook.1).then(a.rook.0.cmp(&b.rook.0)));
+    rights.iter()
+        .map(|right| format!("{}{}", Piece::from_kind(Piece::WhiteRook.kind(), right.white).letter(), right.rook))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+// Pieces are listed from the top rank down and left to right, so equal positions always
+// give the same text
+pub fn format_fen(board: &Board) -> String {
+    let mut pieces: Vec<(&Coordinate, &Piece)> = board.state.iter().collect();
+    pieces.sort_by(|(a, _), (b, _)| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let placement: Vec<String> = pieces.iter().map(|(coord, piece)| format!("{}{}", piece.letter(), coord)).collect();
+
+    let castling = format_castling(&board.castling_rights);
+    let en_passant = match board.en_passant.is_empty() {
+        true => "-".to_string(),
+        false => board.en_passant.iter().map(Coordinate::to_string).collect::<Vec<_>>().join("|"),
+    };
+    let mut fen = format!("{} {} {} {}", placement.join(";"), if board.side_to_move { "w" } else { "b" }, castling, en_passant);
+    if !board.rules.has_classic_pawn_rules() || board.rules.royal_capture() {
+        fen.push_str(&format!(" {}", board.rules.format_pawn_ranks()));
+    }
+    if board.rules.royal_capture() {
+        fen.push_str(&format!(" {}", board.rules.win_condition.name()));
+    }
+    fen
+}
+
+// Positions pasted from infinitechess.org in its compressed ICN form, e.g.
+// `[Event "Casual"] w 0/100 1 (8|1) P1,2+|P2,2+|...|K5,1+|k5,8+ 5,2>5,4|5,7>5,5`.
+// Metadata in brackets, the move rule, move number and promotion ranks are skipped, of the
+// win conditions only royal capture changes how the engine plays. A `+` marks pieces with
+// special rights: pawns that may still double-step (their ranks become the pawn start ranks)
+// and kings and rooks that may still castle. Moves are replayed on top, and as the site's moves are trusted, slides the move generator does not
+// produce (it only makes captures and infinite slides) are played as plain moves.
+pub fn parse_icn(text: &str) -> Result<Board, String> {
+    let mut board = Board::empty();
+    let mut special = Vec::new();
+    let mut moves = Vec::new();
+    let mut in_metadata = false;
+    let mut win_condition = WinCondition::Checkmate;
+
+    for token in text.split_whitespace() {
+        if in_metadata || token.starts_with('[') {
+            in_metadata = !token.ends_with(']');
+            continue;
+        }
+        match token {
+            "w" => board.side_to_move = true,
+            "b" => board.side_to_move = false,
+            _ if WinCondition::parse(token).is_some() => win_condition = WinCondition::parse(token).unwrap(),
+            _ if token.starts_with('(') || token.contains('/') || token.parse::<u64>().is_ok() => {}
+            _ if token.contains('>') => moves.extend(token.split('|').filter(|mv| !mv.is_empty())),
+            _ if token.starts_with(|c: char| c.is_ascii_digit() || c == '-') => board.en_passant = vec![parse_coordinate(token)?],
+            _ if token.contains(',') => {
+                for entry in token.split('|').filter(|entry| !entry.is_empty()) {
+                    let (entry, rights) = match entry.strip_suffix('+') {
+                        Some(entry) => (entry, true),
+                        None => (entry, false),
+                    };
+                    let split = entry.find(|c: char| c.is_ascii_digit() || c == '-').ok_or_else(|| format!("invalid ICN piece '{}'", entry))?;
+                    let mut letters = entry[..split].chars();
+                    let piece = match (letters.next(), letters.next()) {
+                        (Some(letter), None) => piece_set().piece_for_letter(letter),
+                        _ => None,
+                    }.ok_or_else(|| format!("unsupported ICN piece '{}'", &entry[..split]))?;
+                    let coord = parse_coordinate(&entry[split..])?;
+                    if rights {
+                        special.push((coord.clone(), piece));
+                    }
+                    if board.state.insert(coord.clone(), piece).is_some() {
+                        return Err(format!("square {} is occupied twice", coord));
+                    }
+                }
+            }
+            _ => {} // win conditions and other fields the engine has no use for
+        }
+    }
+
+    let mut rules = RuleSet { white_pawn_ranks: Vec::new(), black_pawn_ranks: Vec::new(), win_condition, ..RuleSet::classic() };
+    board.castling_rights.clear();
+    for (coord, piece) in &special {
+        match piece {
+            Piece::WhitePawn | Piece::BlackPawn => {
+                let ranks = if piece.is_white() { &mut rules.white_pawn_ranks } else { &mut rules.black_pawn_ranks };
+                let rank = coord.1.to_i64().ok_or_else(|| format!("pawn rank {} out of range", coord.1))?;
+                if !ranks.contains(&rank) {
+                    ranks.push(rank);
+                }
+            }
+            Piece::WhiteRook | Piece::BlackRook => {
+                // castling needs both the king and the rook to keep their rights
+                let king = Piece::from_kind(Piece::WhiteKing.kind(), piece.is_white());
+                if special.iter().any(|(square, special_piece)| *special_piece == king && square.1 == coord.1) {
+                    board.castling_rights.push(CastlingRight { rook: coord.clone(), white: piece.is_white() });
+                }
+            }
+            _ => {}
+        }
+    }
+    rules.white_pawn_ranks.sort();
+    rules.black_pawn_ranks.sort();
+    board.rules = rules;
+
+    for text in moves {
+        let mv = match parse_move(&board, text) {
+            Ok(mv) => mv,
+            Err(_) if !text.contains('=') && !text.contains("inf:") => {
+                let (from, to) = text.split_once('>').unwrap();
+                Move::Normal(parse_coordinate(from)?, parse_coordinate(to)?)
+            }
+            Err(err) => return Err(err),
+        };
+        if let Move::Normal(from, to) = &mv {
+            match board.get_piece(from) {
+                Some(piece) if piece.is_white() == board.side_to_move => {}
+                _ => return Err(format!("no piece of the side to move on {} for '{}'", from, text)),
+            }
+            if board.get_piece(to).is_some_and(|piece| piece.is_white() == board.side_to_move) {
+                return Err(format!("move '{}' captures its own piece", text));
+            }
+        }
+        if !board.make(mv.clone()) {
+            board.unmake(mv);
+            return Err(format!("move '{}' leaves the king in check", text));
+        }
+    }
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_ascii_moves_are_rejected_without_panicking() {
+        let board = Board::new();
+        for text in ["é2e4", "eé2e4", "e2é4", "e2e4é", "ё", "e2>é4", "é"] {
+            assert!(split_compact_move(text).is_none() || text.is_ascii(), "{}", text);
+            assert!(parse_move(&board, text).is_err(), "{}", text);
+        }
+        assert!(parse_move(&board, "e2e4").is_ok());
+    }
+
+    #[test]
+    fn file_letters_only_when_asked_for() {
+        let mv = parse_move(&Board::new(), "e2e4").unwrap();
+        assert_eq!(format_move(&mv, None, false), "5,2>5,4");
+        assert_eq!(format_move(&mv, None, true), "e2>e4");
+        assert_eq!(mv.to_string(), "5,2>5,4");
+    }
+}
@@ -0,0 +1,129 @@
+// src/openings.rs
+use std::time::{SystemTime, UNIX_EPOCH};
+use clap::{Args, Subcommand};
+use crate::board::{Board, Coordinate, Piece};
+use crate::hash::mix;
+use crate::notation::format_fen;
+use crate::search::Searcher;
+use crate::tools::legal_moves;
+
+// Back rank pieces besides the king, rooks, knights and bishops twice as likely as queens
+const BACK_RANK_KINDS: [Piece; 7] = [
+    Piece::WhiteRook, Piece::WhiteRook, Piece::WhiteKnight, Piece::WhiteKnight,
+    Piece::WhiteBishop, Piece::WhiteBishop, Piece::WhiteQueen,
+];
+// Give up after this many rejected positions per requested opening
+const MAX_ATTEMPTS: usize = 100;
+
+pub struct OpeningOptions {
+    pub count: usize,
+    pub width: i64, // files 1..=width are filled
+    pub depth: u8, // depth of the balance check
+    pub margin: i32, // largest accepted score, in centipawns
+    pub seed: u64,
+}
+
+// splitmix64 stream, enough to shuffle back ranks reproducibly from a seed
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(1);
+        mix(self.0)
+    }
+
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+// White gets a random back rank with one king on rank 1 and a pawn in front of every piece,
+// black the mirrored copy, so both armies are identical
+pub fn random_position(rng: &mut Rng, width: i64) -> Board {
+    let mut board = Board::empty();
+    let king_file = 1 + rng.below(width as usize) as i64;
+    for file in 1..=width {
+        let piece = if file == king_file { Piece::WhiteKing } else { BACK_RANK_KINDS[rng.below(BACK_RANK_KINDS.len())] };
+        board.set_piece(Coordinate::new(file, 1), piece);
+        board.set_piece(Coordinate::new(file, 2), Piece::WhitePawn);
+    }
+
+    let black = board.flipped().expect("classical pawn ranks mirror");
+    board.state.extend(black.state);
+    board.castling_rights.clear();
+    board
+}
+
+// Both sides must have moves, nobody may be in check and a quick search has to find the
+// position roughly level
+pub fn is_balanced(board: &mut Board, searcher: &mut Searcher, options: &OpeningOptions) -> bool {
+    if board.in_check(true) || board.in_check(false) {
+        return false;
+    }
+    let mut other_side = board.clone();
+    other_side.side_to_move = !board.side_to_move;
+    if legal_moves(board).is_empty() || legal_moves(&mut other_side).is_empty() {
+        return false;
+    }
+
+    let result = searcher.search_position(board, options.depth);
+    result.score.abs() <= options.margin
+}
+
+pub fn generate_openings(options: &OpeningOptions) -> Result<Vec<Board>, String> {
+    let mut rng = Rng(options.seed);
+    let mut searcher = Searcher::new();
+    searcher.print_info = false;
+    let mut openings = Vec::new();
+
+    for _ in 0..options.count {
+        let opening = (0..MAX_ATTEMPTS).map(|_| random_position(&mut rng, options.width))
+            .find(|board| is_balanced(&mut board.clone(), &mut searcher, options))
+            .ok_or_else(|| format!("no balanced position found in {} attempts, try a larger --margin", MAX_ATTEMPTS))?;
+        openings.push(opening);
+    }
+
+    Ok(openings)
+}
+
+#[derive(Args, Debug)]
+pub struct OpeningsArgs {
+    #[command(subcommand)]
+    pub command: OpeningsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OpeningsCommand {
+    #[command(about = "random back ranks, each checked to be level by a quick search")]
+    Gen(GenArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct GenArgs {
+    #[arg(long, default_value_t = 10)]
+    pub count: usize,
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(i64).range(1..), help = "files of the back rank")]
+    pub width: i64,
+    #[arg(long, default_value_t = 2, help = "depth of the balance check")]
+    pub depth: u8,
+    #[arg(long, value_name = "CP", default_value_t = 100, help = "largest accepted score")]
+    pub margin: i32,
+    #[arg(long, help = "the time by default")]
+    pub seed: Option<u64>,
+}
+
+pub fn run(args: &OpeningsArgs) -> Result<(), String> {
+    let OpeningsCommand::Gen(args) = &args.command;
+    let options = OpeningOptions {
+        count: args.count,
+        width: args.width,
+        depth: args.depth,
+        margin: args.margin,
+        seed: args.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_nanos() as u64),
+    };
+
+    for opening in generate_openings(&options)? {
+        println!("{}", format_fen(&opening));
+    }
+    Ok(())
+}
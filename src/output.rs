@@ -0,0 +1,173 @@
+// src/output.rs
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text, // plain UCI style lines
+    Json, // one JSON object per line, tagged with a "type" field
+    Compact, // text without search chatter, positions as one summary line, for chat relays
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+pub fn parse_format(name: &str) -> Result<OutputFormat, String> {
+    match name {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        "compact" => Ok(OutputFormat::Compact),
+        _ => Err(format!("unknown output format '{}', expected text, json or compact", name)),
+    }
+}
+
+// Like the piece set, the format is fixed for the whole process before anything is printed
+pub fn install(format: OutputFormat) -> bool {
+    OUTPUT_FORMAT.set(format).is_ok()
+}
+
+pub fn format() -> OutputFormat {
+    *OUTPUT_FORMAT.get_or_init(|| OutputFormat::Text)
+}
+
+pub fn compact() -> bool {
+    format() == OutputFormat::Compact
+}
+
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+// `--log-file`: a copy of everything said over the protocol, input prefixed with "> "
+pub fn install_log(path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("cannot create log file {}: {}", path, err))?;
+    LOG_FILE.set(Mutex::new(file)).map_err(|_| "log file already set".to_string())
+}
+
+pub fn log(line: &str) {
+    if let Some(file) = LOG_FILE.get() {
+        let _ = writeln!(file.lock().unwrap(), "{}", line);
+    }
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub fn json_list(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+    format!("[{}]", items.join(","))
+}
+
+// Field values must already be JSON, e.g. from json_string or a number's to_string()
+pub fn json_object(kind: &str, fields: &[(&str, String)]) -> String {
+    let mut object = format!("{{\"type\":{}", json_string(kind));
+    for (key, value) in fields {
+        object.push_str(&format!(",{}:{}", json_string(key), value));
+    }
+    object.push('}');
+    object
+}
+
+// Print a line in the active format
+pub fn emit(text: &str, kind: &str, fields: &[(&str, String)]) {
+    let line = match format() {
+        OutputFormat::Text | OutputFormat::Compact => text.to_string(),
+        OutputFormat::Json => json_object(kind, fields),
+    };
+    println!("{}", line);
+    log(&line);
+}
+
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32), // moves to mate, negative when getting mated
+}
+
+// The text form of search_info, one UCI line with the PV moves inline
+pub fn info_line(score: &Score, depth: u8, seldepth: u8, nodes: u64, nps: u64, time: u128, pv: &[String]) -> String {
+    let (unit, value) = match score {
+        Score::Centipawns(cp) => ("cp", *cp),
+        Score::Mate(moves) => ("mate", *moves),
+    };
+    format!("info score {} {} depth {} seldepth {} nodes {} nps {} time {} pv {}", unit, value, depth, seldepth, nodes, nps, time, pv.join(" "))
+}
+
+pub fn search_info(score: &Score, depth: u8, seldepth: u8, nodes: u64, nps: u64, time: u128, pv: &[String]) {
+    if compact() {
+        return;
+    }
+    let (unit, value) = match score {
+        Score::Centipawns(cp) => ("cp", *cp),
+        Score::Mate(moves) => ("mate", *moves),
+    };
+    let text = info_line(score, depth, seldepth, nodes, nps, time, pv);
+
+    emit(&text, "info", &[
+        (unit, value.to_string()),
+        ("depth", depth.to_string()),
+        ("seldepth", seldepth.to_string()),
+        ("nodes", nodes.to_string()),
+        ("nps", nps.to_string()),
+        ("time", time.to_string()),
+        ("pv", json_list(pv)),
+    ]);
+}
+
+// Node count of all search threads while an iteration is still running
+pub fn progress(nodes: u64, nps: u64, time: u128) {
+    if compact() {
+        return;
+    }
+    emit(&format!("info nodes {} nps {} time {}", nodes, nps, time), "progress", &[
+        ("nodes", nodes.to_string()),
+        ("nps", nps.to_string()),
+        ("time", time.to_string()),
+    ]);
+}
+
+// Progress within a root iteration, numbered from 1 in search order
+pub fn current_move(depth: u8, mv: &str, number: u32) {
+    if compact() {
+        return;
+    }
+    emit(&format!("info depth {} currmove {} currmovenumber {}", depth, mv, number), "currmove", &[
+        ("depth", depth.to_string()),
+        ("currmove", json_string(mv)),
+        ("currmovenumber", number.to_string()),
+    ]);
+}
+
+pub fn info_string(text: &str) {
+    emit(&format!("info string {}", text), "message", &[("text", json_string(text))]);
+}
+
+// Errors while the engine keeps running, reported as UCI info strings in text mode
+pub fn error(text: &str) {
+    emit(&format!("info string {}", text), "error", &[("message", json_string(text))]);
+}
+
+// Errors that end the program go to stderr, unless the caller is reading JSON from stdout
+pub fn fatal(text: &str) -> ! {
+    match format() {
+        OutputFormat::Text | OutputFormat::Compact => eprintln!("{}", text),
+        OutputFormat::Json => println!("{}", json_object("error", &[("message", json_string(text))])),
+    }
+    std::process::exit(1);
+}
+
+pub fn bestmove(mv: &str) {
+    emit(&format!("bestmove {}", mv), "bestmove", &[("move", json_string(mv))]);
+}
@@ -0,0 +1,138 @@
+// src/pieces.rs
+use std::fs;
+use std::sync::OnceLock;
+use crate::board::{Piece, PIECE_VALUES};
+
+pub const PIECE_NAMES: [&str; 6] = ["pawn", "rook", "knight", "bishop", "queen", "king"];
+
+static NOTATION_SET: OnceLock<PieceSet> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceDef {
+    pub value: i16,
+    pub white_glyph: String,
+    pub black_glyph: String,
+    pub letter: char,
+}
+
+// Values and capture order of a set travel with the board in RuleSet::pieces, so positions
+// with different sets can be searched side by side. The installed set only decides the
+// letters and glyphs pieces are read and written with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceSet {
+    pub defs: [PieceDef; 6],
+    pub mvv_lva: [[u32; 6]; 6], // capture order by victim and attacker kind, see with_mvv_lva
+}
+
+impl PieceSet {
+    pub fn classic() -> Self {
+        let def = |kind: usize, white_glyph: &str, black_glyph: &str, letter: char| PieceDef {
+            value: PIECE_VALUES[kind],
+            white_glyph: white_glyph.to_string(),
+            black_glyph: black_glyph.to_string(),
+            letter,
+        };
+
+        PieceSet {
+            defs: [
+                def(0, "♙", "♟", 'p'),
+                def(1, "♖", "♜", 'r'),
+                def(2, "♘", "♞", 'n'),
+                def(3, "♗", "♝", 'b'),
+                def(4, "♕", "♛", 'q'),
+                def(5, "♔", "♚", 'k'),
+            ],
+            mvv_lva: [[0; 6]; 6],
+        }.with_mvv_lva()
+    }
+
+    // Most valuable victim first, and among equal victims the least valuable attacker. Kinds
+    // are ranked by the values of this set, with the king worth the most, so redefined pieces
+    // order by what they are worth here rather than by their classic slot.
+    fn with_mvv_lva(mut self) -> Self {
+        let worth = |kind: usize| if kind == Piece::WhiteKing.kind() { i16::MAX } else { self.defs[kind].value };
+        let rank = |kind: usize| (0..6).filter(|other| worth(*other) < worth(kind)).count() as u32;
+        let mut mvv_lva = [[0; 6]; 6];
+        for (victim, row) in mvv_lva.iter_mut().enumerate() {
+            for (attacker, order) in row.iter_mut().enumerate() {
+                *order = rank(victim) * 6 + 5 - rank(attacker);
+            }
+        }
+        self.mvv_lva = mvv_lva;
+        self
+    }
+
+    // Piece definition files contain one line per piece kind:
+    //   <name> <value> <white glyph> <black glyph> <letter>
+    // e.g. "rook 700 ♖ ♜ r". Kinds that are not listed keep their classic definition,
+    // blank lines and lines starting with '#' are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut set = PieceSet::classic();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 5 {
+                return Err(format!("line {}: expected 5 fields, found {}", line_number + 1, fields.len()));
+            }
+
+            let kind = PIECE_NAMES.iter().position(|name| name.eq_ignore_ascii_case(fields[0]))
+                .ok_or_else(|| format!("line {}: unknown piece '{}'", line_number + 1, fields[0]))?;
+            let value = fields[1].parse::<i16>()
+                .map_err(|_| format!("line {}: invalid value '{}'", line_number + 1, fields[1]))?;
+            let mut letter_chars = fields[4].chars();
+            let letter = match (letter_chars.next(), letter_chars.next()) {
+                (Some(letter), None) if letter.is_ascii_alphabetic() => letter.to_ascii_lowercase(),
+                _ => return Err(format!("line {}: notation letter must be a single ASCII letter", line_number + 1)),
+            };
+
+            set.defs[kind] = PieceDef {
+                value,
+                white_glyph: fields[2].to_string(),
+                black_glyph: fields[3].to_string(),
+                letter,
+            };
+        }
+
+        for (kind, def) in set.defs.iter().enumerate() {
+            if set.defs[kind + 1..].iter().any(|other| other.letter == def.letter) {
+                return Err(format!("notation letter '{}' is used by more than one piece", def.letter));
+            }
+        }
+
+        Ok(set.with_mvv_lva())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Self::parse(&text)
+    }
+
+    pub fn def(&self, piece: Piece) -> &PieceDef {
+        &self.defs[piece.kind()]
+    }
+
+    // Both colours share the kind's entry
+    pub fn capture_order(&self, victim: Piece, attacker: Piece) -> u32 {
+        self.mvv_lva[victim.kind()][attacker.kind()]
+    }
+
+    pub fn piece_for_letter(&self, letter: char) -> Option<Piece> {
+        let kind = self.defs.iter().position(|def| def.letter == letter.to_ascii_lowercase())?;
+        Some(Piece::from_kind(kind, letter.is_ascii_uppercase()))
+    }
+}
+
+// Install the notation of a piece set for the rest of the process. Must happen before the
+// first lookup, returns false if the active set was already fixed.
+pub fn install(set: PieceSet) -> bool {
+    NOTATION_SET.set(set).is_ok()
+}
+
+pub fn piece_set() -> &'static PieceSet {
+    NOTATION_SET.get_or_init(PieceSet::classic)
+}
@@ -0,0 +1,104 @@
+// src/puzzles.rs
+use std::fs;
+use clap::Args;
+use crate::annotate::game_moves;
+use crate::board::Board;
+use crate::cli::GlobalOptions;
+use crate::notation::{format_fen, format_move, parse_move};
+use crate::r#move::Move;
+use crate::search::Searcher;
+
+pub struct Puzzle {
+    pub fen: String,
+    pub solution: String,
+    pub source: String, // game file and ply the position comes from
+    pub score: i32,
+    pub gap: i32, // how much worse the second best move is
+}
+
+pub struct PuzzleOptions {
+    pub depth: u8,
+    pub min_score: i32, // the solution has to win at least this much
+    pub min_gap: i32, // and be the only move that does by this margin
+}
+
+// A position is a puzzle when the best move wins and the best alternative falls short of it
+// by the gap. Only-moves don't count.
+pub fn find_puzzle(searcher: &mut Searcher, board: &Board, options: &PuzzleOptions) -> Option<(Move, i32, i32)> {
+    let ranked = searcher.rank_moves(&mut board.clone_without_history(), options.depth);
+    // infinite slides come unranked, and an unranked runner-up leaves the gap unknown
+    let [(best, Some(score)), (_, Some(second)), ..] = ranked.as_slice() else { return None };
+    if *score < options.min_score {
+        return None;
+    }
+    let gap = score - second;
+    (gap >= options.min_gap).then_some((best.clone(), *score, gap))
+}
+
+// Replay a game from the start position and collect its puzzles
+pub fn extract_puzzles(moves: &[String], source: &str, options: &PuzzleOptions, search_options: &GlobalOptions) -> Result<Vec<Puzzle>, String> {
+    let mut searcher = Searcher::new();
+    searcher.print_info = false;
+    search_options.apply(&mut searcher);
+    let mut board = Board::new();
+    let mut puzzles = Vec::new();
+
+    for (ply, text) in moves.iter().enumerate() {
+        if let Some((solution, score, gap)) = find_puzzle(&mut searcher, &board, options) {
+            puzzles.push(Puzzle {
+                fen: format_fen(&board),
+                solution: format_move(&solution, None, false),
+                source: format!("{}:{}", source, ply + 1),
+                score,
+                gap,
+            });
+        }
+
+        let mv = parse_move(&board, text)?;
+        if !board.make(mv) {
+            return Err(format!("{}: move '{}' leaves the king in check", source, text));
+        }
+    }
+
+    Ok(puzzles)
+}
+
+// One puzzle per line, `<fen> | <solution> # <source> score <cp> gap <cp>`
+pub fn format_puzzles(puzzles: &[Puzzle]) -> String {
+    puzzles.iter()
+        .map(|puzzle| format!("{} | {} # {} score {} gap {}\n", puzzle.fen, puzzle.solution, puzzle.source, puzzle.score, puzzle.gap))
+        .collect()
+}
+
+#[derive(Args, Debug)]
+pub struct PuzzlesArgs {
+    #[arg(required = true, value_name = "GAME FILE")]
+    pub games: Vec<String>,
+    #[arg(long, default_value_t = 3)]
+    pub depth: u8,
+    #[arg(long, value_name = "CP", default_value_t = 200, allow_negative_numbers = true, help = "the solution has to win at least this much")]
+    pub min_score: i32,
+    #[arg(long, value_name = "CP", default_value_t = 200, allow_negative_numbers = true, help = "and be the only move that does by this margin")]
+    pub gap: i32,
+    #[arg(long, value_name = "FILE", help = "write the puzzles here instead of printing them")]
+    pub out: Option<String>,
+}
+
+pub fn run(args: &PuzzlesArgs, search_options: &GlobalOptions) -> Result<(), String> {
+    let options = PuzzleOptions { depth: args.depth, min_score: args.min_score, min_gap: args.gap };
+    let (paths, output_path) = (&args.games, &args.out);
+    let mut puzzles = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        puzzles.extend(extract_puzzles(&game_moves(&text), path, &options, search_options)?);
+    }
+    let formatted = format_puzzles(&puzzles);
+
+    match output_path {
+        Some(output_path) => fs::write(output_path, formatted).map_err(|err| format!("{}: {}", output_path, err)),
+        None => {
+            print!("{}", formatted);
+            Ok(())
+        }
+    }
+}
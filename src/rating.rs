@@ -0,0 +1,167 @@
+// src/rating.rs
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use clap::Args;
+use crate::game::{format_result, GameResult};
+
+// Player every rating is relative to when it took part, otherwise the average is 0
+pub const ANCHOR: &str = "random";
+const ITERATIONS: usize = 1000;
+const ELO_PER_UNIT: f64 = 400.0 / std::f64::consts::LN_10; // logistic strength unit to Elo
+
+// Points scored and games played by the first player against the second
+pub type PairResults = HashMap<(String, String), (f64, f64)>;
+
+// Results of every match played with --ratings, one game per line as `<white> <black> <result>`
+// separated by tabs, so names may contain spaces; files written before that are separated by
+// spaces. Players are named after their configuration, see arena::config_name, so results of
+// the same configuration add up across sessions.
+pub struct RatingFile {
+    pub path: String,
+}
+
+impl RatingFile {
+    pub fn record(&self, white: &str, black: &str, result: GameResult) -> Result<(), String> {
+        check_name(white)?;
+        check_name(black)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path).map_err(|err| format!("{}: {}", self.path, err))?;
+        writeln!(file, "{}\t{}\t{}", white, black, format_result(result)).map_err(|err| format!("{}: {}", self.path, err))
+    }
+
+    pub fn load(&self) -> Result<PairResults, String> {
+        let text = fs::read_to_string(&self.path).map_err(|err| format!("{}: {}", self.path, err))?;
+        let mut pairs = PairResults::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')) {
+            let fields: Vec<&str> = if line.contains('\t') { line.split('\t').collect() } else { line.split_whitespace().collect() };
+            let [white, black, result] = fields[..] else {
+                return Err(format!("{}:{}: expected '<white> <black> <result>'", self.path, number + 1));
+            };
+            let points = match result {
+                "1-0" => 1.0,
+                "0-1" => 0.0,
+                "1/2-1/2" => 0.5,
+                _ => return Err(format!("{}:{}: unknown result '{}'", self.path, number + 1, result)),
+            };
+            for (a, b, points) in [(white, black, points), (black, white, 1.0 - points)] {
+                let entry = pairs.entry((a.to_string(), b.to_string())).or_insert((0.0, 0.0));
+                entry.0 += points;
+                entry.1 += 1.0;
+            }
+        }
+        Ok(pairs)
+    }
+}
+
+// Names have to read back as they were written: no tabs or line breaks, and no space at either
+// end where it would be lost
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.trim() != name || name.chars().any(char::is_control) {
+        return Err(format!("'{}' can't be stored in a ratings file: it must not be empty, start or end with a space or contain tabs or line breaks", name.escape_default()));
+    }
+    Ok(())
+}
+
+// --label, checked up front instead of when the first result is recorded
+pub fn parse_label(text: &str) -> Result<String, String> {
+    check_name(text).map(|_| text.to_string())
+}
+
+pub struct Rating {
+    pub name: String,
+    pub elo: f64,
+    pub error: f64, // 95% confidence half-width
+    pub games: f64,
+    pub points: f64,
+}
+
+// Maximum likelihood Bradley-Terry strengths, with draws as half points. Every pair that met
+// gets one virtual draw, so a player who won every game still has a finite rating.
+pub fn ratings(pairs: &PairResults) -> Vec<Rating> {
+    let mut names: Vec<String> = pairs.keys().map(|(a, _)| a.clone()).collect();
+    names.sort();
+    names.dedup();
+    let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+    let count = names.len();
+
+    let mut points = vec![0.0; count];
+    let mut games = vec![vec![0.0; count]; count];
+    for ((a, b), (score, played)) in pairs {
+        let (i, j) = (index[a.as_str()], index[b.as_str()]);
+        points[i] += score + 0.5;
+        games[i][j] += played + 1.0;
+    }
+
+    // minorization-maximization on gamma = e^strength
+    let mut gamma = vec![1.0; count];
+    for _ in 0..ITERATIONS {
+        for i in 0..count {
+            let denominator: f64 = (0..count).filter(|j| games[i][*j] > 0.0).map(|j| games[i][j] / (gamma[i] + gamma[j])).sum();
+            if denominator > 0.0 {
+                gamma[i] = points[i] / denominator;
+            }
+        }
+        let mean = gamma.iter().map(|g: &f64| g.ln()).sum::<f64>() / count.max(1) as f64;
+        gamma.iter_mut().for_each(|g| *g = (g.ln() - mean).exp());
+    }
+
+    let offset = index.get(ANCHOR).map_or(0.0, |i| gamma[*i].ln());
+    let mut ratings: Vec<Rating> = (0..count).map(|i| {
+        // inverse of the Fisher information of this player's strength, the others held fixed
+        let information: f64 = (0..count).map(|j| {
+            let p = gamma[i] / (gamma[i] + gamma[j]);
+            games[i][j] * p * (1.0 - p)
+        }).sum();
+        let played: f64 = pairs.iter().filter(|((a, _), _)| *a == names[i]).map(|(_, (_, played))| played).sum();
+        let scored: f64 = pairs.iter().filter(|((a, _), _)| *a == names[i]).map(|(_, (score, _))| score).sum();
+        Rating {
+            name: names[i].clone(),
+            elo: (gamma[i].ln() - offset) * ELO_PER_UNIT,
+            error: if information > 0.0 { 1.96 * ELO_PER_UNIT / information.sqrt() } else { f64::INFINITY },
+            games: played,
+            points: scored,
+        }
+    }).collect();
+    ratings.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+    ratings
+}
+
+pub fn print_ratings(file: &RatingFile) -> Result<(), String> {
+    println!("{:<24} {:>7} {:>6} {:>7} {:>7}", "player", "elo", "+/-", "games", "score");
+    for rating in ratings(&file.load()?) {
+        println!("{:<24} {:>7.0} {:>6.0} {:>7} {:>6.1}%", rating.name, rating.elo, rating.error, rating.games, 100.0 * rating.points / rating.games.max(1.0));
+    }
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+pub struct RatingsArgs {
+    pub file: String,
+}
+
+pub fn run(args: &RatingsArgs) -> Result<(), String> {
+    print_ratings(&RatingFile { path: args.file.clone() })
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_with_spaces_round_trip() {
+        let file = RatingFile { path: std::env::temp_dir().join(format!("ratings-{}.txt", std::process::id())).to_string_lossy().to_string() };
+        fs::write(&file.path, "random engine-d2 0-1\n").unwrap();
+        file.record("engine-d3@My Engine", "random", GameResult::WhiteWins).unwrap();
+        file.record("random", "engine-d3@My Engine", GameResult::Draw).unwrap();
+        let pairs = file.load().unwrap();
+        fs::remove_file(&file.path).unwrap();
+        assert_eq!(pairs[&("engine-d3@My Engine".to_string(), "random".to_string())], (1.5, 2.0));
+        assert_eq!(pairs[&("engine-d2".to_string(), "random".to_string())], (1.0, 1.0));
+
+        for name in ["", " padded", "tab\there", "line\nbreak"] {
+            assert!(parse_label(name).is_err(), "{:?}", name);
+            assert!(file.record(name, "random", GameResult::Draw).is_err(), "{:?}", name);
+        }
+    }
+}
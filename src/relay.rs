@@ -0,0 +1,103 @@
+// src/relay.rs
+use num_bigint::BigInt;
+use crate::board::{Board, Coordinate, Piece};
+use crate::export::{format_eval, format_time};
+use crate::notation::{format_move, format_square};
+use crate::r#move::Move;
+
+// Pieces at most this many squares apart (in both directions) are summarised together
+const CLUSTER_GAP: i64 = 4;
+
+pub struct Cluster {
+    pub min: Coordinate,
+    pub max: Coordinate,
+    pub pieces: Vec<Piece>,
+}
+
+fn near(a: &Coordinate, b: &Coordinate) -> bool {
+    a.chebyshev(b) <= BigInt::from(CLUSTER_GAP)
+}
+
+// Groups of pieces chained together by CLUSTER_GAP, the biggest first
+pub fn clusters(board: &Board) -> Vec<Cluster> {
+    let squares: Vec<(&Coordinate, &Piece)> = board.state.iter().collect();
+    let mut group: Vec<usize> = (0..squares.len()).collect();
+    for i in 0..squares.len() {
+        for j in i + 1..squares.len() {
+            if near(squares[i].0, squares[j].0) {
+                let (from, to) = (group[j], group[i]);
+                group.iter_mut().filter(|id| **id == from).for_each(|id| *id = to);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut ids: Vec<usize> = Vec::new();
+    for (index, (coord, piece)) in squares.iter().enumerate() {
+        match ids.iter().position(|id| *id == group[index]) {
+            Some(found) => {
+                let cluster = &mut clusters[found];
+                cluster.min = Coordinate(cluster.min.0.clone().min(coord.0.clone()), cluster.min.1.clone().min(coord.1.clone()));
+                cluster.max = Coordinate(cluster.max.0.clone().max(coord.0.clone()), cluster.max.1.clone().max(coord.1.clone()));
+                cluster.pieces.push(**piece);
+            }
+            None => {
+                ids.push(group[index]);
+                clusters.push(Cluster { min: (*coord).clone(), max: (*coord).clone(), pieces: vec![**piece] });
+            }
+        }
+    }
+    for cluster in &mut clusters {
+        cluster.pieces.sort_by_key(|piece| (!piece.is_white(), piece.kind() != Piece::WhiteKing.kind(), std::cmp::Reverse(board.rules.pieces.def(*piece).value), piece.kind()));
+    }
+    clusters.sort_by(|a, b| b.pieces.len().cmp(&a.pieces.len()).then_with(|| (&a.min.0, &a.min.1).cmp(&(&b.min.0, &b.min.1))));
+    clusters
+}
+
+// `KR2 kp3` style: each letter once, followed by its count when more than one, white first
+fn format_pieces(pieces: &[Piece]) -> String {
+    let mut text = String::new();
+    let mut index = 0;
+    while index < pieces.len() {
+        if index > 0 && pieces[index].is_white() != pieces[index - 1].is_white() {
+            text.push(' ');
+        }
+        let count = pieces[index..].iter().take_while(|piece| **piece == pieces[index]).count();
+        text.push(pieces[index].letter());
+        if count > 1 {
+            text.push_str(&count.to_string());
+        }
+        index += count;
+    }
+    text
+}
+
+fn format_cluster(cluster: &Cluster, algebraic: bool) -> String {
+    let area = if cluster.min == cluster.max {
+        format_square(&cluster.min, algebraic)
+    } else {
+        format!("{}..{}", format_square(&cluster.min, algebraic), format_square(&cluster.max, algebraic))
+    };
+    format!("{} {}", area, format_pieces(&cluster.pieces))
+}
+
+// One chat-sized line: move number and last move (given with the plies played so far), eval,
+// clocks, side to move and the pieces by cluster, e.g. `12. 5,2>5,4 | +0.35 | 0:00:59.5 0:01:00.0 | black | 1,1..8,8 KQR2P8q...`
+pub fn summary(board: &Board, last: Option<(usize, &Move)>, eval: Option<i32>, clocks: Option<(i64, i64)>, algebraic: bool) -> String {
+    let mut parts = Vec::new();
+    if let Some((plies, mv)) = last {
+        let number = plies.div_ceil(2);
+        parts.push(format!("{}{} {}", number, if board.side_to_move { "..." } else { "." }, format_move(mv, None, algebraic)));
+    }
+    if let Some(eval) = eval {
+        let text = format_eval(eval);
+        parts.push(if eval > 0 && !text.starts_with('#') { format!("+{}", text) } else { text });
+    }
+    if let Some((white, black)) = clocks {
+        parts.push(format!("{} {}", format_time(white), format_time(black)));
+    }
+    parts.push(if board.side_to_move { "white".to_string() } else { "black".to_string() });
+    let clusters: Vec<String> = clusters(board).iter().map(|cluster| format_cluster(cluster, algebraic)).collect();
+    parts.push(if clusters.is_empty() { "empty".to_string() } else { clusters.join(" / ") });
+    parts.join(" | ")
+}
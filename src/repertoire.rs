@@ -0,0 +1,173 @@
+// src/repertoire.rs
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use clap::Args;
+use crate::board::Board;
+use crate::hash::position_key;
+use crate::notation::{format_fen, format_move, parse_move};
+use crate::openings::Rng;
+use crate::r#move::Move;
+use crate::search::Searcher;
+
+// Lines played through in a row without a position for the user before the quiz gives up
+pub const QUIZ_ATTEMPTS: u32 = 100;
+
+// An opening repertoire as a tree of lines from the start position, one line per row of
+// space separated moves, `#` starting a comment. Lines share their common prefixes, and
+// transpositions meet as the tree is keyed by position.
+pub struct Repertoire {
+    pub moves: HashMap<u64, Vec<Move>>, // repertoire moves by position hash
+}
+
+impl Repertoire {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut moves: HashMap<u64, Vec<Move>> = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let mut board = Board::new();
+            for token in line.split_whitespace() {
+                let mv = parse_move(&board, token).map_err(|err| format!("line {}: {}", number + 1, err))?;
+                let known = moves.entry(position_key(&board)).or_default();
+                if !known.contains(&mv) {
+                    known.push(mv.clone());
+                }
+                if !board.make(mv) {
+                    return Err(format!("line {}: move '{}' leaves the king in check", number + 1, token));
+                }
+            }
+        }
+        Ok(Repertoire { moves })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Repertoire::parse(&text)
+    }
+
+    pub fn moves_at(&self, board: &Board) -> &[Move] {
+        self.moves.get(&position_key(board)).map_or(&[], Vec::as_slice)
+    }
+}
+
+fn move_list(moves: &[Move]) -> String {
+    moves.iter().map(|mv| format_move(mv, None, false)).collect::<Vec<_>>().join(" ")
+}
+
+// Play through random repertoire lines: the trainer answers for the other side and the user
+// has to find a repertoire move for `white`'s side. Returns (correct, asked).
+pub fn quiz(repertoire: &Repertoire, white: bool, rng: &mut Rng, input: &mut impl BufRead) -> Result<(u32, u32), String> {
+    let (mut correct, mut asked) = (0, 0);
+    let mut board = Board::new();
+    // lines in a row that ended before asking anything, and the count asked when this one began
+    let (mut misses, mut asked_before) = (0, 0);
+
+    loop {
+        let options = repertoire.moves_at(&board);
+        if options.is_empty() {
+            if board.history.is_empty() {
+                return Err(format!("the repertoire has no moves for {}", if white { "white" } else { "black" }));
+            }
+            misses = if asked == asked_before { misses + 1 } else { 0 };
+            if misses >= QUIZ_ATTEMPTS {
+                return Err(format!("no quiz position found: {} lines in a row had no {} move to ask", misses, if white { "white" } else { "black" }));
+            }
+            asked_before = asked;
+            println!("End of line, starting over");
+            board = Board::new();
+            continue;
+        }
+
+        let mv = if board.side_to_move != white {
+            let mv = options[rng.below(options.len())].clone();
+            println!("Opponent plays {}", format_move(&mv, None, false));
+            mv
+        } else {
+            print!("Your move: ");
+            io::stdout().flush().ok();
+            let mut line = String::new();
+            if input.read_line(&mut line).map_err(|err| err.to_string())? == 0 || line.trim() == "quit" {
+                return Ok((correct, asked));
+            }
+            asked += 1;
+            match parse_move(&board, line.trim()) {
+                Ok(mv) if options.contains(&mv) => {
+                    correct += 1;
+                    println!("Correct");
+                    mv
+                }
+                _ => {
+                    println!("Expected {}", move_list(options));
+                    options[0].clone()
+                }
+            }
+        };
+        board.make(mv);
+    }
+}
+
+// Search every repertoire position with `white` to move and report where the engine would
+// leave the repertoire. Returns (agreeing, checked).
+pub fn check_engine(repertoire: &Repertoire, white: bool, depth: u8) -> (u32, u32) {
+    let mut searcher = Searcher::new();
+    searcher.print_info = false;
+    let mut seen = Vec::new();
+    let mut counts = (0, 0);
+    check_node(repertoire, white, depth, &mut searcher, &mut Board::new(), &mut seen, &mut counts);
+    counts
+}
+
+fn check_node(repertoire: &Repertoire, white: bool, depth: u8, searcher: &mut Searcher, board: &mut Board, seen: &mut Vec<u64>, counts: &mut (u32, u32)) {
+    let key = position_key(board);
+    let options = repertoire.moves_at(board).to_vec();
+    if options.is_empty() || seen.contains(&key) {
+        return;
+    }
+    seen.push(key);
+
+    if board.side_to_move == white {
+        let result = searcher.search_position(&mut board.clone_without_history(), depth);
+        counts.1 += 1;
+        if options.contains(&result.best_move) {
+            counts.0 += 1;
+        } else {
+            println!("{}: engine {}, repertoire {}", format_fen(board), format_move(&result.best_move, None, false), move_list(&options));
+        }
+    }
+
+    for mv in options {
+        board.make(mv.clone());
+        check_node(repertoire, white, depth, searcher, board, seen, counts);
+        board.unmake(mv);
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TrainArgs {
+    #[arg(value_name = "REPERTOIRE FILE")]
+    pub file: String,
+    #[arg(long, value_parser = ["white", "black"], help = "the side the repertoire is played for")]
+    pub side: String,
+    #[arg(long, help = "check where the engine leaves the repertoire instead of quizzing")]
+    pub engine: bool,
+    #[arg(long, default_value_t = 4)]
+    pub depth: u8,
+    #[arg(long, help = "the time by default")]
+    pub seed: Option<u64>,
+}
+
+pub fn run(args: &TrainArgs) -> Result<(), String> {
+    let (path, white, engine, depth) = (&args.file, args.side == "white", args.engine, args.depth);
+    let seed = args.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_nanos() as u64);
+    let repertoire = Repertoire::load(path)?;
+
+    if engine {
+        let (agreeing, checked) = check_engine(&repertoire, white, depth);
+        println!("Engine follows the repertoire in {} of {} positions", agreeing, checked);
+    } else {
+        let (correct, asked) = quiz(&repertoire, white, &mut Rng(seed), &mut io::stdin().lock())?;
+        println!("{} of {} correct", correct, asked);
+    }
+    Ok(())
+}
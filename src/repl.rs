@@ -0,0 +1,275 @@
+// src/repl.rs
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use crate::board::{format_attackers, Board};
+use crate::game::{format_result, Game, GameResult, HistoryPolicy, Observer};
+use crate::notation::{format_fen, format_move, format_square, parse_coordinate, parse_fen, parse_move};
+use crate::output;
+use crate::relay;
+use crate::rules::RuleSet;
+use crate::r#move::Move;
+use crate::search::{parse_heuristic, HeuristicStats, Searcher};
+use crate::threats::describe_threats;
+use crate::tools::{destinations, legal_moves};
+
+const HELP: &str = "\
+commands:
+  show                  print the board and what either side threatens
+  fen [<fen>]           print the position, or set up a new one
+  new                   back to the start position
+  moves [<square>]      legal moves with their static eval, best first, or where the piece on a square can go
+  attackers <x,y>       pieces attacking a square, and defending it when occupied
+  heuristics [...]      'on [<n>]' counts extensions and prunings, verifying every nth; 'off';
+                        'disable|enable <extension|futility|lmp|lmr>'; without arguments the counts
+  rank <depth>          legal moves scored by a search of each, best first
+  move <move>           play a move
+  undo                  take back the last move
+  search <ms>           search for a number of milliseconds
+  pv [<n>]              show the last principal variation, or play its first n moves
+  mark <name>           bookmark the position
+  goto <name>           return to a bookmark
+  marks                 list bookmarks
+  claim                 claim a draw by threefold repetition or the fifty-move rule
+  history <policy>      keep 'full' history, or boards for n plies then 'snapshots <n>' or 'hashes <n>'
+  repetition <kind>     'exact', or 'translated' to count shifted pawnless positions as repeats
+  algebraic on|off      print files 1 to 8 of moves as a to h, e2e4 is always accepted
+  quit";
+
+// A bookmarked or searched position together with the moves that led to it, so undo keeps
+// working after jumping there
+#[derive(Clone)]
+struct Line {
+    board: Board,
+    moves: Vec<Move>,
+}
+
+// Tells the user when a move ends the game
+struct Announcer;
+
+impl Observer for Announcer {
+    fn on_game_end(&mut self, result: GameResult, reason: &str) {
+        println!("game over {} ({})", format_result(result), reason);
+    }
+}
+
+// Interactive analysis on top of Board and Searcher: make and take back moves, search and
+// walk along the resulting line
+pub struct Repl {
+    game: Game,
+    moves: Vec<Move>,
+    pub searcher: Searcher,
+    pv: Option<(Line, Vec<Move>)>, // last PV and the position it starts from
+    marks: HashMap<String, Line>,
+    translation_repetition: bool, // carried into every position set up, see repetition_key
+    pub algebraic: bool, // file letters in the moves shown, see format_square
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let mut searcher = Searcher::new();
+        searcher.print_info = false;
+        let mut game = Game::new(Board::new());
+        game.observe(Box::new(Announcer));
+        Repl { game, moves: Vec::new(), searcher, pv: None, marks: HashMap::new(), translation_repetition: false, algebraic: false }
+    }
+
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("> ");
+            io::stdout().flush().ok();
+            let Some(Ok(line)) = lines.next() else { break };
+            match self.handle_command(line.trim()) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => println!("error: {}", err),
+            }
+        }
+    }
+
+    // Returns false once the REPL should exit
+    pub fn handle_command(&mut self, line: &str) -> Result<bool, String> {
+        let (command, rest) = line.split_once(' ').map_or((line, ""), |(command, rest)| (command, rest.trim()));
+        match command {
+            "" => {}
+            "help" => println!("{}", HELP),
+            "quit" | "exit" => return Ok(false),
+            "show" if output::compact() => println!("{}", self.summary()),
+            "show" => {
+                self.game.board.show(true);
+                for threat in describe_threats(&self.game.board) {
+                    println!("{}", threat);
+                }
+            }
+            "fen" if rest.is_empty() => println!("{}", format_fen(&self.game.board)),
+            "fen" => self.set_line(Line { board: parse_fen(rest)?, moves: Vec::new() }),
+            "new" => self.set_line(Line { board: Board::new(), moves: Vec::new() }),
+            "moves" if rest.is_empty() => self.list_moves(),
+            "moves" => self.destinations(rest)?,
+            "attackers" => self.attackers(rest)?,
+            "heuristics" => self.heuristics(rest)?,
+            "rank" => self.rank(rest.parse().map_err(|_| "expected 'rank <depth>'")?),
+            "move" => self.play(rest)?,
+            "undo" => {
+                self.game.undo()?;
+                self.moves.pop();
+            }
+            "claim" => self.game.claim_draw()?,
+            "history" => self.game.policy = HistoryPolicy::parse(rest)?,
+            "algebraic" => self.algebraic = match rest {
+                "on" => true,
+                "off" => false,
+                _ => return Err("expected 'algebraic on' or 'algebraic off'".to_string()),
+            },
+            "search" => self.search(rest.parse().map_err(|_| "expected 'search <ms>'")?),
+            "pv" => self.pv(rest)?,
+            "mark" if !rest.is_empty() => {
+                self.marks.insert(rest.to_string(), Line { board: self.game.board.clone(), moves: self.moves.clone() });
+            }
+            "goto" => {
+                let line = self.marks.get(rest).cloned().ok_or_else(|| format!("no bookmark '{}'", rest))?;
+                self.set_line(line);
+            }
+            "marks" => {
+                let mut names: Vec<&String> = self.marks.keys().collect();
+                names.sort();
+                for name in names {
+                    println!("{} {}", name, format_fen(&self.marks[name].board));
+                }
+            }
+            "repetition" => {
+                self.translation_repetition = RuleSet::parse_repetition(rest)?;
+                self.game.board.rules.translation_repetition = self.translation_repetition;
+            }
+            _ => return Err(format!("unknown command '{}', try 'help'", line)),
+        }
+        Ok(true)
+    }
+
+    fn set_line(&mut self, mut line: Line) {
+        line.board.rules.translation_repetition = self.translation_repetition;
+        self.game.set_board(line.board);
+        self.moves = line.moves;
+    }
+
+    fn play(&mut self, text: &str) -> Result<(), String> {
+        let mv = parse_move(&self.game.board, text)?;
+        self.game.play(mv.clone()).map_err(|err| format!("move '{}': {}", text, err))?;
+        self.moves.push(mv);
+        if output::compact() {
+            println!("{}", self.summary());
+        }
+        if self.game.can_claim_draw() {
+            println!("a draw can be claimed");
+        }
+        Ok(())
+    }
+
+    // The compact form of `show`, with the static eval
+    fn summary(&self) -> String {
+        let last = self.moves.last().map(|mv| (self.moves.len(), mv));
+        relay::summary(&self.game.board, last, Some(self.game.board.evaluate_white_relative()), None, self.algebraic)
+    }
+
+    fn list_moves(&mut self) {
+        let mut scored: Vec<(i32, Move)> = legal_moves(&mut self.game.board).into_iter()
+            .map(|mv| {
+                self.game.board.make(mv.clone());
+                let score = -self.game.board.evaluate();
+                self.game.board.unmake(mv.clone());
+                (score, mv)
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        for (score, mv) in scored {
+            println!("{:<24} {}", format_move(&mv, None, self.algebraic), score);
+        }
+    }
+
+    // One line per destination, `<square> <move>`, an infinite slide on the first square of its ray
+    fn destinations(&mut self, square: &str) -> Result<(), String> {
+        for destination in destinations(&mut self.game.board, &parse_coordinate(square)?)? {
+            let (square, mv) = (format_square(&destination.square, self.algebraic), format_move(&destination.mv, None, self.algebraic));
+            output::emit(&format!("{:<16} {}", square, mv), "destination", &[
+                ("square", output::json_string(&square)),
+                ("move", output::json_string(&mv)),
+                ("infinite", matches!(destination.mv, Move::InfiniteMove(..)).to_string()),
+            ]);
+        }
+        Ok(())
+    }
+
+    // `on [<verify every>]`, `off`, `disable <name>`, `enable <name>`, or the stats so far
+    fn heuristics(&mut self, args: &str) -> Result<(), String> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        match words[..] {
+            [] => match &self.searcher.heuristic_stats {
+                Some(stats) => println!("{}", stats.summary()),
+                None => println!("heuristic debugging is off, turn it on with 'heuristics on'"),
+            },
+            ["on"] | ["on", _] => {
+                self.searcher.verify_every = words.get(1).map_or(Ok(0), |every| every.parse().map_err(|_| "expected 'heuristics on [<verify every>]'"))?;
+                self.searcher.heuristic_stats = Some(HeuristicStats::default());
+            }
+            ["off"] => self.searcher.heuristic_stats = None,
+            ["disable", name] => self.searcher.disabled[parse_heuristic(name)?] = true,
+            ["enable", name] => self.searcher.disabled[parse_heuristic(name)?] = false,
+            _ => return Err("expected 'heuristics [on [<verify every>] | off | disable <name> | enable <name>]'".to_string()),
+        }
+        Ok(())
+    }
+
+    // Attackers and defenders of an occupied square, the attackers of either side otherwise
+    fn attackers(&self, square: &str) -> Result<(), String> {
+        let square = parse_coordinate(square)?;
+        let board = &self.game.board;
+        match board.get_piece(&square) {
+            Some(piece) => {
+                println!("{}{}", piece.letter(), square);
+                println!("attackers: {}", format_attackers(&board.attackers_of(&square, !piece.is_white())));
+                println!("defenders: {}", format_attackers(&board.attackers_of(&square, piece.is_white())));
+            }
+            None => board.show_attackers(&square),
+        }
+        Ok(())
+    }
+
+    fn rank(&mut self, depth: u8) {
+        for (mv, score) in self.searcher.rank_moves(&mut self.game.board.clone_without_history(), depth) {
+            println!("{:<24} {}", format_move(&mv, None, self.algebraic), score.map_or("unranked".to_string(), |score| score.to_string()));
+        }
+    }
+
+    fn search(&mut self, movetime: i32) {
+        self.searcher.movetime = movetime;
+        self.searcher.clear_heuristics(true);
+        let mut board = self.game.board.clone_without_history();
+        let result = self.searcher.search_position(&mut board, u8::MAX);
+        self.searcher.movetime = -1;
+
+        println!("best {} score {} depth {} nodes {}", format_move(&result.best_move, None, self.algebraic), result.score, result.depth, result.nodes);
+        self.pv = Some((Line { board: self.game.board.clone(), moves: self.moves.clone() }, result.pv));
+        self.pv("").ok();
+    }
+
+    fn pv(&mut self, count: &str) -> Result<(), String> {
+        let (start, pv) = self.pv.clone().ok_or("no search yet")?;
+        if count.is_empty() {
+            let line: Vec<String> = pv.iter().map(|mv| format_move(mv, None, self.algebraic)).collect();
+            println!("pv {}", line.join(" "));
+            return Ok(());
+        }
+
+        let count: usize = count.parse().map_err(|_| "expected 'pv [<n>]'")?;
+        if count > pv.len() {
+            return Err(format!("the pv is only {} moves long", pv.len()));
+        }
+        self.set_line(start);
+        for mv in &pv[..count] {
+            self.game.play(mv.clone())?;
+            self.moves.push(mv.clone());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+// src/rules.rs
+use std::sync::Arc;
+use num_bigint::BigInt;
+use num_traits::{One, Signed};
+use crate::board::{Coordinate, Piece};
+use crate::evaluation::{EvalParams, DEFAULT_EVAL};
+use crate::geometry::Direction;
+use crate::notation::parse_integer;
+use crate::pieces::PieceSet;
+
+// How a game is won. Under royal capture a king may be left attacked and the game ends when
+// it is actually taken, so there is no checkmate and no legality filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinCondition {
+    Checkmate,
+    RoyalCapture,
+}
+
+impl WinCondition {
+    // Names as infinitechess.org writes them, several may be joined with `|` and put in
+    // parentheses. Conditions the engine does not know are ignored.
+    pub fn parse(text: &str) -> Option<Self> {
+        let conditions: Vec<&str> = text.trim_matches(|c| c == '(' || c == ')').split('|').collect();
+        if conditions.contains(&"royalcapture") {
+            Some(WinCondition::RoyalCapture)
+        } else if conditions.contains(&"checkmate") {
+            Some(WinCondition::Checkmate)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WinCondition::Checkmate => "checkmate",
+            WinCondition::RoyalCapture => "royalcapture",
+        }
+    }
+}
+
+// Variant rules that differ between setups and travel with the board
+// Longest first pawn push a rule set may ask for. Pawn generation walks every square of it, and
+// no variant needs anything near this.
+pub const MAX_PAWN_PUSH: u32 = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    pub white_pawn_ranks: Vec<i64>, // ranks a white pawn may push further than one square from
+    pub black_pawn_ranks: Vec<i64>,
+    pub pawn_push: u32, // how many squares a pawn may advance from a start rank, 2 classically
+    pub win_condition: WinCondition,
+    pub coordinate_limit: Option<BigInt>, // largest |x| and |y| a piece may move to, None for no limit
+    pub material_baseline: i32, // white's material lead in the setup, which the evaluation counts as level
+    pub translation_repetition: bool, // positions equal up to a shift of the whole board repeat, see repetition_key
+    pub eval: Arc<EvalParams>, // evaluation profile of the variant, see Variant::eval
+    pub pieces: Arc<PieceSet>, // values and capture order of the pieces, see --pieces
+    pub verify_movegen: bool, // compare every generated move list with the reference generator, see --verify-movegen
+}
+
+impl RuleSet {
+    pub fn classic() -> Self {
+        RuleSet {
+            white_pawn_ranks: vec![2],
+            black_pawn_ranks: vec![7],
+            pawn_push: 2,
+            win_condition: WinCondition::Checkmate,
+            coordinate_limit: None,
+            material_baseline: 0,
+            translation_repetition: false,
+            eval: Arc::new(DEFAULT_EVAL),
+            pieces: Arc::new(PieceSet::classic()),
+            verify_movegen: false,
+        }
+    }
+
+    // Worth of a piece in the evaluation profile, the piece set's value unless the profile has its own
+    pub fn value(&self, piece: Piece) -> i32 {
+        self.eval.value(piece, &self.pieces)
+    }
+
+    pub fn capture_order(&self, victim: Piece, attacker: Piece) -> u32 {
+        self.eval.capture_order(victim, attacker, &self.pieces)
+    }
+
+    pub fn royal_capture(&self) -> bool {
+        self.win_condition == WinCondition::RoyalCapture
+    }
+
+    pub fn has_classic_pawn_rules(&self) -> bool {
+        let classic = RuleSet::classic();
+        self.white_pawn_ranks == classic.white_pawn_ranks && self.black_pawn_ranks == classic.black_pawn_ranks && self.pawn_push == classic.pawn_push
+    }
+
+    pub fn within_limit(&self, coord: &Coordinate) -> bool {
+        self.coordinate_limit.as_ref().is_none_or(|limit| coord.0.abs() <= *limit && coord.1.abs() <= *limit)
+    }
+
+    // How far a slider may run along an empty ray without crossing the limit, None when there
+    // is no limit and the ray really is endless
+    pub fn steps_within_limit(&self, from: &Coordinate, direction: Direction) -> Option<BigInt> {
+        let limit = self.coordinate_limit.as_ref()?;
+        let offset = direction.offset();
+        let room = |position: &BigInt, step: i64| match step {
+            1 => Some(limit - position),
+            -1 => Some(limit + position),
+            _ => None,
+        };
+        [room(&from.0, offset.dx), room(&from.1, offset.dy)].into_iter().flatten().min()
+    }
+
+    // `none` for no limit, otherwise a magnitude such as `1000000000` or `1e9`
+    pub fn parse_coordinate_limit(text: &str) -> Result<Option<BigInt>, String> {
+        if text == "none" {
+            return Ok(None);
+        }
+        let limit = parse_integer(text)?;
+        if limit < BigInt::one() {
+            return Err(format!("the coordinate limit must be at least 1, found '{}'", text));
+        }
+        Ok(Some(limit))
+    }
+
+    // `exact`, or `translated` for translation_repetition
+    pub fn parse_repetition(text: &str) -> Result<bool, String> {
+        match text {
+            "exact" => Ok(false),
+            "translated" => Ok(true),
+            _ => Err(format!("invalid repetition '{}', expected 'exact' or 'translated'", text)),
+        }
+    }
+
+    pub fn check_pawn_push(&self) -> Result<(), String> {
+        if !(1..=MAX_PAWN_PUSH).contains(&self.pawn_push) {
+            return Err(format!("pawn push distance {} is outside 1 to {}", self.pawn_push, MAX_PAWN_PUSH));
+        }
+        Ok(())
+    }
+
+    pub fn is_pawn_start(&self, white: bool, rank: &BigInt) -> bool {
+        let ranks = if white { &self.white_pawn_ranks } else { &self.black_pawn_ranks };
+        ranks.iter().any(|start| *rank == BigInt::from(*start))
+    }
+
+    // Start ranks written as `<white>/<black>`, each a comma separated list, e.g. `2,3/7,6`,
+    // optionally followed by the first push distance when it is not 2, e.g. `2/7:3`
+    pub fn parse_pawn_ranks(text: &str) -> Result<Self, String> {
+        let (text, pawn_push) = match text.split_once(':') {
+            Some((ranks, push)) => (ranks, push.parse().ok().filter(|push| (1..=MAX_PAWN_PUSH).contains(push))
+                .ok_or_else(|| format!("invalid pawn push '{}', expected 1 to {}", push, MAX_PAWN_PUSH))?),
+            None => (text, 2),
+        };
+        let (white, black) = text.split_once('/')
+            .ok_or_else(|| format!("expected '<white ranks>/<black ranks>', found '{}'", text))?;
+        let ranks = |list: &str| -> Result<Vec<i64>, String> {
+            list.split(',').filter(|rank| !rank.is_empty())
+                .map(|rank| rank.trim().parse().map_err(|_| format!("invalid pawn rank '{}'", rank)))
+                .collect()
+        };
+        Ok(RuleSet {
+            white_pawn_ranks: ranks(white)?,
+            black_pawn_ranks: ranks(black)?,
+            pawn_push,
+            win_condition: WinCondition::Checkmate,
+            coordinate_limit: None,
+            material_baseline: 0,
+            translation_repetition: false,
+            eval: Arc::new(DEFAULT_EVAL),
+            pieces: Arc::new(PieceSet::classic()),
+            verify_movegen: false,
+        })
+    }
+
+    pub fn format_pawn_ranks(&self) -> String {
+        let list = |ranks: &[i64]| ranks.iter().map(i64::to_string).collect::<Vec<_>>().join(",");
+        let mut text = format!("{}/{}", list(&self.white_pawn_ranks), list(&self.black_pawn_ranks));
+        if self.pawn_push != 2 {
+            text.push_str(&format!(":{}", self.pawn_push));
+        }
+        text
+    }
+
+    // Rules of the colour-flipped position, see Board::flipped. None when a pawn start rank's
+    // mirror doesn't fit an i64, as for the other transforms.
+    pub fn flipped(&self) -> Option<Self> {
+        Some(RuleSet {
+            white_pawn_ranks: mirror_ranks(&self.black_pawn_ranks)?,
+            black_pawn_ranks: mirror_ranks(&self.white_pawn_ranks)?,
+            pawn_push: self.pawn_push,
+            win_condition: self.win_condition,
+            coordinate_limit: self.coordinate_limit.clone(),
+            material_baseline: -self.material_baseline,
+            translation_repetition: self.translation_repetition,
+            eval: self.eval.clone(),
+            pieces: self.pieces.clone(),
+            verify_movegen: self.verify_movegen,
+        })
+    }
+
+    // Start ranks of pawns that were turned around the board centre, colours unchanged
+    pub fn rotated(&self) -> Option<Self> {
+        Some(RuleSet {
+            white_pawn_ranks: mirror_ranks(&self.white_pawn_ranks)?,
+            black_pawn_ranks: mirror_ranks(&self.black_pawn_ranks)?,
+            pawn_push: self.pawn_push,
+            win_condition: self.win_condition,
+            coordinate_limit: self.coordinate_limit.clone(),
+            material_baseline: self.material_baseline,
+            translation_repetition: self.translation_repetition,
+            eval: self.eval.clone(),
+            pieces: self.pieces.clone(),
+            verify_movegen: self.verify_movegen,
+        })
+    }
+
+    // None when a pawn start rank would leave the i64 range, rather than wrapping around
+    pub fn translated(&self, dy: i64) -> Option<Self> {
+        let shift = |ranks: &[i64]| ranks.iter().map(|rank| rank.checked_add(dy)).collect::<Option<Vec<i64>>>();
+        Some(RuleSet {
+            white_pawn_ranks: shift(&self.white_pawn_ranks)?,
+            black_pawn_ranks: shift(&self.black_pawn_ranks)?,
+            pawn_push: self.pawn_push,
+            win_condition: self.win_condition,
+            coordinate_limit: self.coordinate_limit.clone(),
+            material_baseline: self.material_baseline,
+            translation_repetition: self.translation_repetition,
+            eval: self.eval.clone(),
+            pieces: self.pieces.clone(),
+            verify_movegen: self.verify_movegen,
+        })
+    }
+}
+
+// Ranks turned around the classical centre line, y -> 9 - y
+fn mirror_ranks(ranks: &[i64]) -> Option<Vec<i64>> {
+    ranks.iter().map(|rank| 9i64.checked_sub(*rank)).collect()
+}
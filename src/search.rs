@@ -1,28 +1,203 @@
-use crate::board::{Board, Coordinate, Piece, PIECE_VALUES};
+use crate::board::{Anchor, Board, Phase, Piece};
+use crate::notation::format_move;
+use crate::output::{self, Score};
+use crate::hash::{position_key, quiescence_key};
+use crate::experience::Experience;
+use crate::evaluation::{EvalParams, PawnHash};
+use crate::pieces::PieceSet;
+use crate::tt::{TranspositionTable, DEFAULT_TT_ENTRIES, HASH_ALPHA, HASH_BETA, HASH_EXACT, QUIESCENCE_DEPTH};
 use crate::r#move::movegen::{Move, MoveGen};
-use std::mem::MaybeUninit;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use num_bigint::BigInt;
 use num_traits::Signed;
 use crate::r#move::MoveList;
-use array_init::array_init;
+use crate::r#move::encode::{encode_move, encode_square, EncodedMove, NO_MOVE};
 
-pub const MAX_PLY: usize = 127;
+pub const MAX_PLY: usize = 127; // default ply limit, see Searcher::set_max_ply
+pub const MAX_PLY_LIMIT: usize = 250; // ply counters are u8, keep some headroom
 pub const INFINITY: i32 = 1000000;
 pub const MATE_VALUE: i32 = INFINITY - 150;
-pub const MATE_SCORE: i32 = INFINITY - 300;
+// every mate within the ply limit scores above this
+pub const MATE_SCORE: i32 = MATE_VALUE - MAX_PLY_LIMIT as i32 - 50;
 pub const TIME_UP: i32 = INFINITY + 500;
+pub const SANITY_DEPTH: u8 = 2; // enough to see a hung piece or a mate in one against the move
+pub const SANITY_MARGIN: i32 = 300; // verification loss that counts as a refutation
+pub const SANITY_CANDIDATES: usize = 3; // alternatives verified when the best move fails
+pub const CURRMOVE_DELAY: u128 = 1000; // ms into a search before root moves are reported
+pub const INFO_INTERVAL: u128 = 1000; // ms between node count updates while an iteration runs
+pub const MAX_HISTORY: i32 = 2000; // history scores stay within ±this, below the killers in move ordering
+pub const MOVE_KINDS: [&str; 5] = ["quiet", "capture", "promotion", "castling", "infinite"];
+
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32, // from the side to move's point of view
+    pub depth: u8, // last fully completed iteration
+    pub seldepth: u8, // deepest ply reached in that iteration, quiescence included
+    pub stability: u8, // consecutive iterations that ended with the same best move
+    pub nodes: u64,
+    pub pv: Vec<Move>, // principal variation of that iteration
+}
+
+// Deepest completed iteration posted by the helper threads of a search
+pub type HelperReport = Arc<Mutex<Option<SearchResult>>>;
+
+pub fn nodes_per_second(nodes: u64, time: u128) -> u64 {
+    (nodes as u128 * 1000 / time.max(1)) as u64
+}
+
+// Where the search effort goes, split by the kind of move (see MOVE_KINDS) that led to a node
+#[derive(Debug, Clone, Default)]
+pub struct MoveStats {
+    pub searched: [u64; MOVE_KINDS.len()], // moves of the kind searched
+    pub nodes: [u64; MOVE_KINDS.len()], // nodes entered through a move of the kind
+    pub cutoffs: [u64; MOVE_KINDS.len()], // of the searched moves, those that failed high
+}
+
+impl MoveStats {
+    pub fn kind(board: &Board, mv: &Move) -> usize {
+        match mv {
+            Move::Normal(_, to) if board.get_piece(to).is_some() => 1,
+            Move::EnPassant(..) => 1,
+            Move::Promotion(..) => 2,
+            Move::Castling(..) => 3,
+            Move::InfiniteMove(..) => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn add(&mut self, other: &MoveStats) {
+        for kind in 0..MOVE_KINDS.len() {
+            self.searched[kind] += other.searched[kind];
+            self.nodes[kind] += other.nodes[kind];
+            self.cutoffs[kind] += other.cutoffs[kind];
+        }
+    }
+
+    // `quiet 1200 moves 54% nodes 8% cutoffs, capture ...`, kinds that never came up left out
+    pub fn summary(&self) -> String {
+        let total_nodes = self.nodes.iter().sum::<u64>().max(1);
+        let parts: Vec<String> = (0..MOVE_KINDS.len())
+            .filter(|kind| self.searched[*kind] > 0)
+            .map(|kind| format!("{} {} moves {}% nodes {}% cutoffs", MOVE_KINDS[kind], self.searched[kind],
+                self.nodes[kind] * 100 / total_nodes, self.cutoffs[kind] * 100 / self.searched[kind]))
+            .collect();
+        parts.join(", ")
+    }
+}
+
+// Root moves whose static score is this close to the best count as candidates, see Complexity
+pub const CANDIDATE_MARGIN: i32 = 50;
+// A search on the game clock may take up to this many times its planned time
+pub const MAX_STRETCH: i32 = 2;
+// Share of the planned time an opening position gets, the rest is saved for later phases
+pub const OPENING_TIME_PERCENT: i32 = 75;
+// Share of the time spent pondering that a ponderhit takes off the budget, at most half of it
+pub const PONDER_CREDIT_PERCENT: u128 = 50;
+// Longest single sleep of the NPS throttle, so a stop is still noticed promptly
+pub const THROTTLE_SLEEP: u128 = 10;
+// How difficult the root position is: a forced recapture has one playable move and a steady
+// score, a critical position several close candidates and a score that keeps moving
+#[derive(Debug, Clone, Default)]
+pub struct Complexity {
+    pub candidates: usize, // root moves within CANDIDATE_MARGIN of the best static score
+    pub swing: i64, // summed score change between consecutive iterations, centipawns
+    pub iterations: u32,
+    pub last_score: Option<i32>,
+    pub phase: Phase,
+}
+
+impl Complexity {
+    pub fn record(&mut self, score: i32) {
+        // mate scores would swamp the average
+        let score = score.clamp(-MATE_SCORE, MATE_SCORE);
+        if let Some(last) = self.last_score {
+            self.swing += (score - last).abs() as i64;
+            self.iterations += 1;
+        }
+        self.last_score = Some(score);
+    }
+
+    pub fn volatility(&self) -> i64 {
+        self.swing / self.iterations.max(1) as i64
+    }
+
+    // Scale of the planned time, from half of it for a forced move to twice in wild positions
+    pub fn factor(&self) -> f64 {
+        let candidates = 0.6 + 0.15 * self.candidates.min(6) as f64;
+        let volatility = 1.0 + self.volatility().min(100) as f64 / 100.0;
+        let phase = if self.phase == Phase::Opening { OPENING_TIME_PERCENT as f64 / 100.0 } else { 1.0 };
+        (candidates * volatility * phase).clamp(0.5, MAX_STRETCH as f64)
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} candidates {} volatility {} factor {:.2}", self.phase.name(), self.candidates, self.volatility(), self.factor())
+    }
+}
+
+// Search rules that extend, prune or reduce, which the debug mode tracks and can switch off
+pub const HEURISTICS: [&str; 4] = ["extension", "futility", "lmp", "lmr"];
+pub const CHECK_EXTENSION: usize = 0;
+pub const FUTILITY: usize = 1; // static eval far enough above beta at low depth
+pub const LATE_MOVE_PRUNING: usize = 2; // quiet moves skipped once the static eval can't reach alpha
+pub const REDUCTION: usize = 3; // late moves searched shallower first
+// Futility and late move pruning margins were set for about this many moves a node, as in
+// classical chess. Open infinite boards have far more, every quiet move a little less likely to
+// be the one that matters, so the margins grow with the search's average mobility, up to
+// MAX_MOBILITY_SCALE times.
+pub const BASE_MOBILITY: i32 = 30;
+pub const MAX_MOBILITY_SCALE: i32 = 4;
+const LMP_MARGIN: i32 = 25; // per ply of depth and per base mobility above the base
+// Verification searches get a small table of their own, so they never see pruned results
+const VERIFY_TT_ENTRIES: usize = 1 << 12;
+
+// How often each heuristic fired and, for the sampled firings re-searched with the heuristic
+// switched off, whether the result held and how many nodes it spared
+#[derive(Debug, Clone, Default)]
+pub struct HeuristicStats {
+    pub fired: [u64; HEURISTICS.len()],
+    pub verified: [u64; HEURISTICS.len()],
+    pub errors: [u64; HEURISTICS.len()], // verifications that crossed the bound the heuristic relied on
+    pub saved: [i64; HEURISTICS.len()], // nodes of the verification searches minus those of the pruned ones
+}
+
+impl HeuristicStats {
+    // `futility 310 fired 31 verified 2 errors 5400 nodes saved, ...`, heuristics that never fired left out
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = (0..HEURISTICS.len())
+            .filter(|heuristic| self.fired[*heuristic] > 0)
+            .map(|heuristic| match self.verified[heuristic] {
+                0 => format!("{} {} fired", HEURISTICS[heuristic], self.fired[heuristic]),
+                verified => format!("{} {} fired {} verified {} errors {} nodes saved", HEURISTICS[heuristic], self.fired[heuristic],
+                    verified, self.errors[heuristic], self.saved[heuristic]),
+            })
+            .collect();
+        if parts.is_empty() { "none fired".to_string() } else { parts.join(", ") }
+    }
+}
 
-pub static mut STOP: bool = false;
+pub fn parse_heuristic(name: &str) -> Result<usize, String> {
+    HEURISTICS.iter().position(|known| *known == name).ok_or_else(|| format!("unknown heuristic '{}', expected one of {}", name, HEURISTICS.join(", ")))
+}
 
 #[derive(Clone)]
 pub struct Searcher {
     pub ply: u8,
+    pub seldepth: u8,
     pub nodes: u64,
     pub time: u128,
-    pub killers: Vec<Vec<Move>>,
-    pub pv_table: Vec<Vec<Move>>,
-    pub pv_length: [u8; MAX_PLY],
+    pub killers: Vec<Vec<EncodedMove>>,
+    pub history: HashMap<(Piece, u64), i32>, // quiet move cutoff scores by moving piece and encoded target square, see update_history
+    pub tt: TranspositionTable,
+    pub hash_move: Option<Move>, // best move stored for the node whose moves are being scored
+    pub root_nodes: HashMap<Move, u64>, // subtree size of each root move in the last iteration
+    pub pv_table: Vec<Vec<Move>>, // triangular: the line at ply p only holds moves from p on
+    pub max_ply: usize, // plies searched before falling back to the static eval
     pub follow_pv: bool,
     pub score_pv: bool,
     pub full_depth_moves: u8,
@@ -33,23 +208,51 @@ pub struct Searcher {
     pub playtime: i32,
     pub timeset: bool,
     pub stoptime: u128,
+    pub stop: Arc<AtomicBool>, // raised to abort the search, cleared by whoever starts one
+    pub anchor: Anchor,
+    pub print_info: bool,
+    pub infinite: bool, // search until stopped, ignoring the depth limit
+    pub pondering: bool, // searching on the opponent's time, untimed until ponderhit
+    pub ponder_time: u128, // ms spent pondering before the ponderhit, 0 for a normal search
+    pub ponder_nodes: u64, // nodes searched by then
+    pub max_nodes: Option<u64>, // `go nodes`, counted over all threads and through a ponderhit
+    pub max_nps: Option<u64>, // nodes a second all threads together may search, for weaker and fairer play
+    pub ponder: Arc<AtomicBool>, // raised by whoever starts a ponder search, cleared on ponderhit
+    pub root_depth: u8, // depth of the current iteration at the root, check extension included
+    pub experience: Option<Experience>, // root results from earlier searches, used like a book
+    pub params: Option<Arc<EvalParams>>, // evaluation weights for every position searched, the board's own profile when None
+    pub pieces: Option<Arc<PieceSet>>, // piece values for every position searched, the board's own set when None
+    pub verify_movegen: bool, // check every move list of the search against the reference generator, with --features verify
+    pub pawn_hash: PawnHash, // pawn structure scores of this searcher's positions
+    pub threads: usize, // search threads, all but one are helpers sharing the TT
+    pub report: Option<HelperReport>, // where a helper posts its deepest result
+    pub move_stats: MoveStats, // effort by move kind in the current search
+    pub move_kinds: Vec<usize>, // kind of the move that led to each ply
+    pub total_nodes: Arc<AtomicU64>, // nodes of all threads in the current search, helpers share it
+    pub last_info: u128, // elapsed ms at the last node count update
+    pub complexity: Complexity, // of the root position, for the time manager
+    pub mobility: i32, // running average of the moves generated a node, in 1/16ths
+    pub soft_deadline: Option<(u128, u128)>, // start and planned ms of a search on the game clock
+    pub disabled: [bool; HEURISTICS.len()], // heuristics switched off, see HEURISTICS
+    pub heuristic_stats: Option<HeuristicStats>, // debug mode, None unless switched on
+    pub verify_every: u64, // in debug mode, re-search one firing in this many per heuristic, 0 for none
+    pub verifier: Option<Box<Searcher>>, // searcher of the verification searches, made on first use
 }
 
 impl Searcher {
     pub fn new() -> Searcher {
-        let default_move = Move::Normal(Coordinate::new(0, 0), Coordinate::new(0, 0));
-
-        // Initialize the arrays using Vec
-        let killers = vec![vec![default_move.clone(); MAX_PLY]; 2];
-        let pv_table = vec![vec![default_move.clone(); MAX_PLY]; MAX_PLY];
-
-        Searcher {
+        let mut searcher = Searcher {
             ply: 0,
+            seldepth: 0,
             nodes: 0,
             time: 0,
-            killers,
-            pv_table,
-            pv_length: [0; MAX_PLY],
+            killers: Vec::new(),
+            history: HashMap::new(),
+            tt: TranspositionTable::new(DEFAULT_TT_ENTRIES),
+            hash_move: None,
+            root_nodes: HashMap::new(),
+            pv_table: Vec::new(),
+            max_ply: MAX_PLY,
             follow_pv: false,
             score_pv: false,
             full_depth_moves: 3,
@@ -60,65 +263,568 @@ impl Searcher {
             playtime: -1,
             timeset: false,
             stoptime: 0,
+            stop: Arc::new(AtomicBool::new(false)),
+            anchor: Anchor::Origin,
+            print_info: true,
+            infinite: false,
+            pondering: false,
+            ponder_time: 0,
+            ponder_nodes: 0,
+            max_nodes: None,
+            max_nps: None,
+            ponder: Arc::new(AtomicBool::new(false)),
+            root_depth: 0,
+            experience: None,
+            params: None,
+            pieces: None,
+            verify_movegen: false,
+            pawn_hash: PawnHash::default(),
+            threads: 1,
+            report: None,
+            move_stats: MoveStats::default(),
+            move_kinds: Vec::new(),
+            total_nodes: Arc::new(AtomicU64::new(0)),
+            last_info: 0,
+            complexity: Complexity::default(),
+            mobility: BASE_MOBILITY * 16,
+            soft_deadline: None,
+            disabled: [false; HEURISTICS.len()],
+            heuristic_stats: None,
+            verify_every: 0,
+            verifier: None,
+        };
+        searcher.set_max_ply(MAX_PLY);
+        searcher
+    }
+
+    // Long forced mating lines on an infinite board can run past the default limit. The
+    // tables get one spare slot, as a node at the last ply still reads its child's PV length.
+    pub fn set_max_ply(&mut self, max_ply: usize) {
+        self.max_ply = max_ply.clamp(2, MAX_PLY_LIMIT);
+        self.killers = vec![vec![NO_MOVE; self.max_ply + 1]; 2];
+        self.pv_table = vec![Vec::new(); self.max_ply + 1];
+        self.move_kinds = vec![0; self.max_ply + 1];
+    }
+
+    // Reset everything learned about the previous game, keeping the allocated tables
+    pub fn new_game(&mut self) {
+        self.clear_heuristics(false);
+        self.tt.clear();
+        for line in self.pv_table.iter_mut() {
+            line.clear();
+        }
+        self.nodes = 0;
+    }
+
+    // Killers always go, as they are tied to plies of the previous search tree. With
+    // age_only the history table is halved instead of cleared, so ordering knowledge
+    // carries over between moves of the same game.
+    pub fn clear_heuristics(&mut self, age_only: bool) {
+        for killers in self.killers.iter_mut() {
+            killers.fill(NO_MOVE);
+        }
+
+        if age_only {
+            self.history.retain(|_, score| {
+                *score /= 2;
+                *score != 0
+            });
+        } else {
+            self.history.clear();
+        }
+    }
+
+    // Derive the search deadline from the time control, as set by the game clock or UCI `go`
+    pub fn start_timer(&mut self) {
+        self.time = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis();
+        self.timeset = false;
+        self.soft_deadline = None;
+        self.ponder_time = 0;
+        self.ponder_nodes = 0;
+        if !self.pondering {
+            self.set_deadline(self.time);
+        }
+    }
+
+    // The time budget counts from `start`, the search start or the ponderhit
+    fn set_deadline(&mut self, start: u128) {
+        let (playtime, movestogo) = if self.movetime != -1 {
+            (self.movetime, 1)
+        } else {
+            (self.playtime, self.movestogo.max(1))
+        };
+
+        if playtime != -1 {
+            self.timeset = true;
+            // keep a safety margin for move overhead, never plan past the remaining time
+            let mut budget = (playtime / movestogo + self.inc).min(playtime - 50).max(0);
+            if self.movetime == -1 {
+                // the iterations finished while pondering were searched on the right position,
+                // so part of that time is taken off the budget instead of being spent again
+                let credit = (self.ponder_time * PONDER_CREDIT_PERCENT / 100).min(budget as u128 / 2);
+                budget -= credit as i32;
+            }
+            if self.movetime != -1 {
+                self.stoptime = start + budget as u128;
+            } else {
+                // the planned time is a target between iterations, the hard stop leaves room to
+                // spend more of it on difficult positions
+                self.soft_deadline = Some((start, budget as u128));
+                self.stoptime = start + (budget * MAX_STRETCH).min(playtime / 2).max(budget).min(playtime - 50).max(0) as u128;
+            }
         }
     }
 
     pub fn stop_search(&mut self) -> bool {
-        if unsafe { STOP } || (self.timeset && SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() > self.stoptime) {
+        // on ponderhit the running search turns into a timed one, keeping its tables and iteration
+        if self.pondering && !self.ponder.load(Ordering::Relaxed) {
+            self.pondering = false;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+            self.ponder_time = now - self.time;
+            self.ponder_nodes = self.total_nodes.load(Ordering::Relaxed);
+            self.set_deadline(now);
+            if self.print_info {
+                output::info_string(&format!("ponderhit after {} ms and {} nodes", self.ponder_time, self.ponder_nodes));
+            }
+        }
+        if self.max_nodes.is_some_and(|max| self.total_nodes.load(Ordering::Relaxed) >= max) {
+            return true;
+        }
+        if self.stop.load(Ordering::Relaxed) || (self.timeset && SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() > self.stoptime) {
             return true;
         }
         false
     }
 
-    pub fn search_position(&mut self, board: &mut Board, depth: u8) -> Move {
-        unsafe { STOP = false; }
+    // Pruning margins in percent of their classical size, from the average mobility so far
+    pub fn mobility_scale(&self) -> i32 {
+        (self.mobility * 100 / (BASE_MOBILITY * 16)).clamp(100, MAX_MOBILITY_SCALE * 100)
+    }
 
-        let mut best_move = Move::Normal(Coordinate::new(0, 0), Coordinate::new(0, 0));
+    // With params, pieces or movegen verification of its own, the searcher plays the position by
+    // them for the search and gives the board its rules back afterwards
+    pub fn search_position(&mut self, board: &mut Board, depth: u8) -> SearchResult {
+        self.with_rules(board, |searcher, board| searcher.search_board(board, depth))
+    }
+
+    fn with_rules<T>(&mut self, board: &mut Board, run: impl FnOnce(&mut Self, &mut Board) -> T) -> T {
+        let rules = board.rules.clone();
+        if let Some(params) = &self.params {
+            board.rules.eval = params.clone();
+        }
+        if let Some(pieces) = &self.pieces {
+            board.rules.pieces = pieces.clone();
+        }
+        board.rules.verify_movegen |= self.verify_movegen;
+        let result = run(self, board);
+        board.rules = rules;
+        result
+    }
+
+    fn search_board(&mut self, board: &mut Board, depth: u8) -> SearchResult {
+        self.start_timer();
+        self.nodes = 0;
+        self.root_nodes.clear();
+        self.move_stats = MoveStats::default();
+        self.mobility = BASE_MOBILITY * 16;
+        self.last_info = 0;
+        // helpers add to the count of the search that started them
+        if self.report.is_none() {
+            self.total_nodes.store(0, Ordering::Relaxed);
+        }
 
-        for current_depth in 1..=depth {
+        // there is always a legal move to report, even if stopped before depth 1 completes
+        let (fallback, fallback_score) = self.fallback_move(board);
+        let mut result = SearchResult {
+            best_move: fallback,
+            score: fallback_score,
+            depth: 0,
+            seldepth: 0,
+            stability: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        let anchor = self.anchor.resolve(board);
+
+        // a position searched at least this deep before is answered from experience,
+        // analysis (`go infinite`) always searches afresh
+        if let Some((best_move, entry)) = self.experience.as_ref().filter(|_| !self.infinite).and_then(|experience| experience.probe(board, depth)) {
+            if self.print_info {
+                output::info_string(&format!("experience move {} depth {} score {}", format_move(&best_move, anchor.as_ref(), false), entry.depth, entry.score));
+            }
+            result.pv = vec![best_move.clone()];
+            result.best_move = best_move;
+            result.score = entry.score;
+            result.depth = entry.depth;
+            return result;
+        }
+
+        // an infinite search only ends on `stop`, deepening as far as the ply limit allows
+        let max_depth = if self.infinite { self.max_ply as u8 - 1 } else { depth.min(self.max_ply as u8 - 1) };
+        let (helpers, helper_stop, report) = self.start_helpers(board, max_depth);
+
+        for current_depth in 1..=max_depth {
             if self.stop_search() {
                 break;
             }
             self.follow_pv = true;
+            self.seldepth = 0;
 
-            let mut score = -INFINITY;
-
-            score = self.negamax(board, -INFINITY, INFINITY, current_depth);
+            let score = self.negamax(board, -INFINITY, INFINITY, current_depth);
 
             if self.stop_search() {
                 break;
             }
 
-            if score > -MATE_VALUE && score < -MATE_SCORE {
-                print!("info score mate {} depth {} nodes {} time {} pv ", -(self.pv_length[0] as i16) / 2 - 1, current_depth, self.nodes, SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() - self.time);
-            } else if score > MATE_SCORE && score < MATE_VALUE {
-                print!("info score mate {} depth {} nodes {} time {} pv ", self.pv_length[0] / 2 + 1, current_depth, self.nodes, SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() - self.time);
+            if let Some(best_move) = self.pv_table[0].first() {
+                if result.depth > 0 && result.best_move == *best_move {
+                    result.stability += 1;
+                } else {
+                    result.stability = 1;
+                }
+                result.best_move = best_move.clone();
+            }
+            result.score = score;
+            result.depth = current_depth;
+            result.seldepth = self.seldepth;
+            result.nodes = self.nodes;
+            result.pv = self.pv_table[0].clone();
+            self.post_report(&result);
+            self.adopt_helper_move(board, &report, &mut result);
+
+            self.complexity.record(result.score);
+
+            if self.print_info {
+                let score = result.score;
+                let reported = if score > -MATE_VALUE && score < -MATE_SCORE {
+                    Score::Mate(-(result.pv.len() as i32) / 2 - 1)
+                } else if score > MATE_SCORE && score < MATE_VALUE {
+                    Score::Mate(result.pv.len() as i32 / 2 + 1)
+                } else {
+                    Score::Centipawns(score)
+                };
+                let pv: Vec<String> = result.pv.iter().map(|mv| format_move(mv, anchor.as_ref(), false)).collect();
+                let (nodes, time) = (self.total_nodes.load(Ordering::Relaxed), self.elapsed());
+                output::search_info(&reported, self.root_depth, self.seldepth.max(self.root_depth), nodes, nodes_per_second(nodes, time), time, &pv);
+                output::info_string(&format!("best move stable for {} iterations", result.stability));
+            }
+
+            // a game clock search plans its time and stretches or shrinks it with the position's complexity
+            if let Some((start, planned)) = self.soft_deadline {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+                if !self.infinite && (now - start) as f64 > planned as f64 * self.complexity.factor() {
+                    break;
+                }
+            }
+        }
+
+        helper_stop.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            helper.join().ok();
+        }
+        if self.print_info && self.move_stats.searched.iter().any(|count| *count > 0) {
+            output::info_string(&format!("moves {}", self.move_stats.summary()));
+        }
+        if let (true, Some(_)) = (self.print_info, self.soft_deadline) {
+            output::info_string(&format!("complexity {} mobility {:.1} margins {}%", self.complexity.summary(), self.mobility as f64 / 16.0, self.mobility_scale()));
+        }
+        if let (true, Some(stats)) = (self.print_info, &self.heuristic_stats) {
+            output::info_string(&format!("heuristics {}", stats.summary()));
+        }
+
+        // timed play can cut an iteration short right after a pruned refutation was missed
+        if self.timeset && !self.infinite && result.stability > 0 && !self.stop.load(Ordering::Relaxed) {
+            self.sanity_check(board, &mut result);
+        }
+
+        // stability is only set once an iteration produced a move
+        if let Some(experience) = self.experience.as_mut().filter(|_| result.stability > 0) {
+            experience.record(board, &result.best_move, result.score, result.depth);
+        }
+
+        // `go infinite` must not report a best move before `stop` arrives
+        if self.infinite {
+            while !self.stop_search() {
+                thread::sleep(Duration::from_millis(10));
+                if self.print_info {
+                    self.report_progress();
+                }
+            }
+        }
+
+        result
+    }
+
+    // Lazy SMP: helpers run the same iterative deepening on clones of this searcher, which share
+    // the TT, and post their deepest completed iteration to a common slot. They stop when this
+    // search ends.
+    fn start_helpers(&self, board: &Board, depth: u8) -> (Vec<JoinHandle<()>>, Arc<AtomicBool>, HelperReport) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let report = Arc::new(Mutex::new(None));
+        let helpers = (1..self.threads)
+            .map(|_| {
+                let mut helper = self.clone();
+                helper.threads = 1;
+                helper.print_info = false;
+                helper.infinite = false;
+                helper.pondering = false;
+                helper.movetime = -1;
+                helper.playtime = -1;
+                helper.experience = None;
+                helper.heuristic_stats = None;
+                helper.verifier = None;
+                helper.stop = stop.clone();
+                helper.report = Some(report.clone());
+                let mut board = board.clone_without_history();
+                thread::spawn(move || {
+                    helper.search_position(&mut board, depth);
+                })
+            })
+            .collect();
+        (helpers, stop, report)
+    }
+
+    // A helper that got at least as deep and prefers another move only changes the root choice
+    // after its move fails high on a null window around our score at our depth. Helpers search
+    // with whatever the shared TT held when they got there, so an unverified switch would make
+    // the best move flicker between threads.
+    fn adopt_helper_move(&mut self, board: &mut Board, report: &Mutex<Option<SearchResult>>, result: &mut SearchResult) {
+        let Some(helper) = report.lock().unwrap().clone() else { return };
+        if helper.depth < result.depth || helper.best_move == result.best_move || helper.stability == 0 {
+            return;
+        }
+        if !board.make(helper.best_move.clone()) {
+            board.unmake(helper.best_move);
+            return;
+        }
+        // the null window only proves the move is better, its score comes from an open re-search
+        self.ply = 1;
+        let mut score = -self.negamax(board, -result.score - 1, -result.score, result.depth - 1);
+        if score > result.score && !self.stop_search() {
+            score = -self.negamax(board, -INFINITY, -result.score, result.depth - 1);
+        }
+        self.ply = 0;
+        board.unmake(helper.best_move.clone());
+
+        if score > result.score && !self.stop_search() {
+            if self.print_info {
+                output::info_string(&format!("helper move {} verified ({} > {})", helper.best_move, score, result.score));
+            }
+            result.stability = 1;
+            result.score = score;
+            result.pv = if helper.pv.first() == Some(&helper.best_move) { helper.pv } else { vec![helper.best_move.clone()] };
+            result.best_move = helper.best_move;
+        }
+    }
+
+    // Post a completed iteration of a helper, keeping only the deepest
+    fn post_report(&self, result: &SearchResult) {
+        if let Some(report) = &self.report {
+            let mut slot = report.lock().unwrap();
+            if slot.as_ref().is_none_or(|posted| posted.depth < result.depth) {
+                *slot = Some(result.clone());
+            }
+        }
+    }
+
+    // Verify the chosen move with a quick search of the replies. If it loses clearly more than
+    // the search thought, look at the most searched alternatives at the same shallow depth and
+    // switch only to one that holds up clearly better there. Mates were proven by the deep search
+    // and are kept, and a check cut short by stop or the node limit changes nothing.
+    pub fn sanity_check(&mut self, board: &mut Board, result: &mut SearchResult) {
+        if result.score.abs() > MATE_SCORE {
+            return;
+        }
+        let timeset = self.timeset;
+        self.timeset = false;
+        let switch = self.sanity_alternative(board, result);
+        self.timeset = timeset;
+
+        if let Some((mv, score, checked)) = switch {
+            if self.print_info {
+                output::info_string(&format!("sanity check: {} fails ({}), playing {} ({})", result.best_move, checked, mv, score));
+            }
+            result.best_move = mv.clone();
+            result.score = score;
+            result.pv = vec![mv];
+        }
+    }
+
+    // The alternative to play with its score and the chosen move's, None to keep the choice
+    fn sanity_alternative(&mut self, board: &mut Board, result: &SearchResult) -> Option<(Move, i32, i32)> {
+        let checked = self.verify_move(board, &result.best_move)?;
+        if checked >= result.score - SANITY_MARGIN {
+            return None;
+        }
+        let mut candidates: Vec<(&Move, &u64)> = self.root_nodes.iter().filter(|(mv, _)| **mv != result.best_move).collect();
+        candidates.sort_by_key(|(_, nodes)| std::cmp::Reverse(**nodes));
+        let candidates: Vec<Move> = candidates.into_iter().take(SANITY_CANDIDATES).map(|(mv, _)| mv.clone()).collect();
+
+        let mut best: Option<(Move, i32)> = None;
+        for mv in candidates {
+            let score = self.verify_move(board, &mv)?;
+            if score > checked + SANITY_MARGIN && best.as_ref().is_none_or(|(_, best)| score > *best) {
+                best = Some((mv, score));
+            }
+        }
+        best.map(|(mv, score)| (mv, score, checked))
+    }
+
+    // Score of a root move by a shallow search of the position after it, None when the search
+    // was stopped before it could finish
+    fn verify_move(&mut self, board: &mut Board, mv: &Move) -> Option<i32> {
+        if self.stop_search() {
+            return None;
+        }
+        if !board.make(mv.clone()) {
+            board.unmake(mv.clone());
+            return Some(-INFINITY);
+        }
+        self.ply = 1;
+        let score = -self.negamax(board, -INFINITY, INFINITY, SANITY_DEPTH);
+        self.ply = 0;
+        board.unmake(mv.clone());
+        (!self.stop_search()).then_some(score)
+    }
+
+    // Every legal root move with the score of a depth - 1 search after it, best first. Each move
+    // gets an exact score of its own, which MultiPV would need as many iterations for.
+    pub fn rank_moves(&mut self, board: &mut Board, depth: u8) -> Vec<(Move, Option<i32>)> {
+        self.with_rules(board, |searcher, board| searcher.rank_board(board, depth))
+    }
+
+    fn rank_board(&mut self, board: &mut Board, depth: u8) -> Vec<(Move, Option<i32>)> {
+        self.start_timer();
+        self.nodes = 0;
+        let mut move_list = MoveList::new();
+        MoveGen::generate_moves(board, &mut move_list);
+
+        let mut ranked = Vec::new();
+        for mv in &move_list.moves[..move_list.count as usize] {
+            if !board.make(mv.clone()) {
+                board.unmake(mv.clone());
+                continue;
+            }
+            // make leaves the board as it was for an infinite slide, so searching it would score
+            // a pass; those moves are listed unranked after the rest
+            let score = if matches!(mv, Move::InfiniteMove(..)) {
+                None
             } else {
-                print!("info score cp {} depth {} nodes {} time {} pv ", score, current_depth, self.nodes, SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() - self.time);
+                self.ply = 1;
+                let score = -self.negamax(board, -INFINITY, INFINITY, depth.max(1) - 1);
+                self.ply = 0;
+                Some(score)
+            };
+            board.unmake(mv.clone());
+            ranked.push((mv.clone(), score));
+        }
+        ranked.sort_by_key(|(_, score)| (score.is_none(), score.map(std::cmp::Reverse)));
+        ranked
+    }
+
+    // Greedy one-ply choice by static eval, cheap enough to have ready the moment a search
+    // starts. Move::None only when there is no legal move at all. Evaluating every reply is
+    // not free on slow builds, so once there is a move the deadline cuts the scan short.
+    pub fn fallback_move(&mut self, board: &mut Board) -> (Move, i32) {
+        let mut move_list = MoveList::new();
+        MoveGen::generate_moves(board, &mut move_list);
+
+        let mut best = (Move::None, 0);
+        let mut scores = Vec::new();
+        for mv in &move_list.moves[..move_list.count as usize] {
+            if best.0 != Move::None && self.stop_search() {
+                break;
             }
-            for count in 0..self.pv_length[0] {
-                println!("{:?}", self.pv_table[0][count as usize]);
+            if board.make(mv.clone()) {
+                let score = if board.royal_captured() { MATE_VALUE } else { -board.evaluate_cached(&mut self.pawn_hash) };
+                scores.push(score);
+                if best.0 == Move::None || score > best.1 {
+                    best = (mv.clone(), score);
+                }
             }
-            println!();
+            board.unmake(mv.clone());
+        }
+        self.complexity = Complexity {
+            candidates: scores.iter().filter(|score| **score >= best.1 - CANDIDATE_MARGIN).count(),
+            phase: board.phase(),
+            ..Complexity::default()
+        };
+        best
+    }
+
+    // Every 64 nodes of the main search and quiescence alike, so the node count updates and the
+    // NPS cap keep going however long an iteration or a capture sequence takes
+    fn poll(&mut self) {
+        if !self.nodes.is_multiple_of(64) {
+            return;
+        }
+        if self.print_info {
+            self.report_progress();
+        }
+        self.throttle();
+    }
+
+    fn report_progress(&mut self) {
+        let time = self.elapsed();
+        if time - self.last_info < INFO_INTERVAL {
+            return;
+        }
+        self.last_info = time;
+        let nodes = self.total_nodes.load(Ordering::Relaxed);
+        output::progress(nodes, nodes_per_second(nodes, time), time);
+    }
 
-            best_move = self.pv_table[0][0].clone();
+    // Under an NPS cap, sleep until the nodes of all threads are no more than the cap allows for
+    // the time since the search started
+    fn throttle(&mut self) {
+        let Some(max_nps) = self.max_nps.filter(|nps| *nps > 0) else { return };
+        let due = self.total_nodes.load(Ordering::Relaxed) as u128 * 1000 / max_nps as u128;
+        loop {
+            let elapsed = self.elapsed();
+            if due <= elapsed || self.stop_search() {
+                break;
+            }
+            thread::sleep(Duration::from_millis((due - elapsed).min(THROTTLE_SLEEP) as u64));
         }
+    }
 
-        best_move
+    // Milliseconds since the current search started
+    pub fn elapsed(&self) -> u128 {
+        SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() - self.time
     }
 
     pub fn quiescence(&mut self, board: &mut Board, mut alpha: i32, beta: i32) -> i32 {
         self.nodes += 1;
+        self.total_nodes.fetch_add(1, Ordering::Relaxed);
+        self.poll();
+        self.seldepth = self.seldepth.max(self.ply);
+        self.move_stats.nodes[self.move_kinds[self.ply as usize]] += 1;
 
-        let eval = board.evaluate();
+        if board.royal_captured() {
+            return -MATE_VALUE + self.ply as i32;
+        }
+        // material only goes with a capture, so that is when mating chances can run out
+        if board.captured.is_some() && board.is_dead_draw() {
+            return 0;
+        }
+
+        let hash_key = quiescence_key(board);
+        if let Some(score) = self.tt.probe_score(hash_key, QUIESCENCE_DEPTH, alpha, beta, self.ply) {
+            return score;
+        }
+        let original_alpha = alpha;
+        let mut best_move = Move::None;
+
+        let eval = board.evaluate_cached(&mut self.pawn_hash);
 
         if eval >= beta {
+            self.tt.store(hash_key, QUIESCENCE_DEPTH, HASH_BETA, beta, Move::None, self.ply);
             return beta;
         } else if eval > alpha {
             alpha = eval;
         }
 
-        if self.ply >= MAX_PLY as u8 {
+        if self.ply as usize >= self.max_ply {
             return eval;
         }
 
@@ -127,8 +833,9 @@ impl Searcher {
         }
 
         let mut move_list = MoveList::new();
-        MoveGen::generate_moves(&board, &mut move_list);
-        let mut move_scores: [u32; 256] = unsafe { MaybeUninit::uninit().assume_init() };
+        MoveGen::generate_moves(board, &mut move_list);
+        let mut move_scores = vec![0; move_list.moves.len()];
+        self.hash_move = self.tt.best_move(hash_key);
 
         let counted = move_list.count;
         self.assign_move_scores(board, &move_list.moves, &mut move_scores, counted as usize);
@@ -136,18 +843,29 @@ impl Searcher {
         for count in 0..counted {
             let mv = self.sort_next_move(&mut move_list.moves, &mut move_scores, count as usize, counted as usize);
 
+            // only captures, but recaptures on the square just captured on are always tried
+            // so the search sees the material coming back
             if move_scores[count as usize] as i32 - 8000 < 0 {
-                break;
+                let is_recapture = match (&mv, &board.last_capture) {
+                    (Move::Normal(_, to), Some(square)) => to == square && board.get_piece(to).is_some(),
+                    _ => false,
+                };
+                if !is_recapture {
+                    continue;
+                }
             }
 
+            let kind = MoveStats::kind(board, &mv);
             if !board.make(mv.clone()) {
                 board.unmake(mv);
                 continue;
             }
 
             self.ply += 1;
+            self.move_stats.searched[kind] += 1;
+            self.move_kinds[self.ply as usize] = kind;
             let score = -self.quiescence(board, -beta, -alpha);
-            board.unmake(mv);
+            board.unmake(mv.clone());
             self.ply -= 1;
 
             if self.stop_search() {
@@ -156,33 +874,55 @@ impl Searcher {
 
             if score > alpha {
                 alpha = score;
+                best_move = mv.clone();
                 if score >= beta {
+                    self.move_stats.cutoffs[kind] += 1;
+                    self.tt.store(hash_key, QUIESCENCE_DEPTH, HASH_BETA, beta, mv, self.ply);
                     return beta;
                 }
             }
         }
-        return alpha;
+
+        let hash_flag = if alpha > original_alpha { HASH_EXACT } else { HASH_ALPHA };
+        self.tt.store(hash_key, QUIESCENCE_DEPTH, hash_flag, alpha, best_move, self.ply);
+        alpha
     }
 
     pub fn negamax(&mut self, board: &mut Board, mut alpha: i32, mut beta: i32, mut depth: u8) -> i32 {
         let pv_node = beta.wrapping_sub(alpha) > 1;
-        let mut best_move = Move::Normal(Coordinate::new(0, 0), Coordinate::new(0, 0));
 
         let mut score;
         let is_root = self.ply == 0;
 
         self.nodes += 1;
+        self.total_nodes.fetch_add(1, Ordering::Relaxed);
+        self.poll();
+        self.seldepth = self.seldepth.max(self.ply);
 
-        if self.ply >= MAX_PLY as u8 {
-            return board.evaluate();
+        self.pv_table[self.ply as usize].clear();
+        if !is_root {
+            self.move_stats.nodes[self.move_kinds[self.ply as usize]] += 1;
+        }
+
+        // the king was taken on the previous move, scored like being mated
+        if board.royal_captured() {
+            return -MATE_VALUE + self.ply as i32;
+        }
+        // a capture that leaves neither side able to mate ends the line, however long the
+        // chase after it could go on
+        if !is_root && board.captured.is_some() && board.is_dead_draw() {
+            return 0;
+        }
+
+        // too deep for the PV and killer tables, settle for the static eval
+        if self.ply as usize >= self.max_ply {
+            return board.evaluate_cached(&mut self.pawn_hash);
         }
         //
         // if board.is_fifty() {
         //     return 0;
         // }
 
-        self.pv_length[self.ply as usize] = self.ply;
-
         if !is_root {
             // if board.is_threefold() {
             //     return 0;
@@ -203,23 +943,39 @@ impl Searcher {
             return self.quiescence(board, alpha, beta);
         }
 
-        let in_check = board.is_attacked(board.king_position(board.side_to_move), !board.side_to_move);
+        let hash_key = position_key(board);
+        if !is_root && !pv_node {
+            if let Some(score) = self.tt.probe_score(hash_key, depth as i8, alpha, beta, self.ply) {
+                return score;
+            }
+        }
+        let mut hash_flag = HASH_ALPHA;
+        let mut best_move = Move::None;
+
+        let in_check = board.in_check(board.side_to_move);
 
-        if in_check {
+        if in_check && !self.disabled[CHECK_EXTENSION] {
             depth += 1;
+            self.fired(CHECK_EXTENSION);
+        }
+        if is_root {
+            self.root_depth = depth;
         }
 
         if self.stop_search() {
             return 0;
         }
 
-        let eval = board.evaluate();
-        if !in_check && !pv_node {
-            if depth < 3 && (beta - 1).abs() > -49000 + 100 {
-                let eval_margin = 100 * depth as i32;
-                if eval - eval_margin >= beta.into() {
-                    return eval - eval_margin;
+        let eval = board.evaluate_cached(&mut self.pawn_hash);
+        let scale = self.mobility_scale();
+        if !in_check && !pv_node && depth < 3 && (beta - 1).abs() > -49000 + 100 && !self.disabled[FUTILITY] {
+            let eval_margin = 100 * depth as i32 * scale / 100;
+            if eval - eval_margin >= beta {
+                if self.fired(FUTILITY) {
+                    let (score, nodes) = self.verify(FUTILITY, board, beta - 1, beta, depth, self.ply);
+                    self.record_verification(FUTILITY, score < beta, nodes as i64);
                 }
+                return eval - eval_margin;
             }
         }
 
@@ -229,38 +985,50 @@ impl Searcher {
 
         let mut legal_moves = 0;
         let mut move_list = MoveList::new();
-        MoveGen::generate_moves(&board, &mut move_list);
-        let mut move_scores: [u32; 256] = unsafe { MaybeUninit::uninit().assume_init() };
+        MoveGen::generate_moves(board, &mut move_list);
+        let mut move_scores = vec![0; move_list.moves.len()];
+        self.hash_move = self.tt.best_move(hash_key);
 
         let counted = move_list.count;
+        self.mobility += counted - self.mobility / 16;
         self.assign_move_scores(board, &move_list.moves, &mut move_scores, counted as usize);
+        if is_root {
+            self.order_root_moves(&move_list.moves, &mut move_scores, counted as usize);
+        }
 
         let mut moves_searched = 0;
 
         let mut best_score = -INFINITY;
         let mut skip_quiet = false;
+        let mut quiets_searched = Vec::new();
+        // quiet moves are skipped once the static eval is this far below alpha, 0 at base mobility
+        let lmp_margin = LMP_MARGIN * depth as i32 * (scale - 100) / 100;
 
         for count in 0..counted {
             let mv = self.sort_next_move(&mut move_list.moves, &mut move_scores, count as usize, counted as usize);
 
             let is_quiet = match mv {
-                Move::Normal(_, ref to) | Move::Promotion(_, ref to, _) => board.get_piece(&to).is_none(),
+                Move::Normal(_, ref to) | Move::Promotion(_, ref to, _) => board.get_piece(to).is_none(),
                 _ => false,
             };
 
-            if is_quiet && skip_quiet {
-                continue;
-            }
+            let encoded = encode_move(&mv);
+            let is_killer = self.killers[0][self.ply as usize] == encoded || self.killers[1][self.ply as usize] == encoded;
 
-            let is_killer = self.killers[0][self.ply as usize] == mv.clone() || self.killers[1][self.ply as usize] == mv.clone();
-
-            if !is_root && best_score > -INFINITY {
-                if depth < 8 && is_quiet && !is_killer && eval <= alpha && alpha.abs() < INFINITY - 100 {
-                    skip_quiet = true;
-                    continue;
+            // once one quiet move is skipped, the rest of them are too
+            if is_quiet && (skip_quiet || (!is_root && best_score > -INFINITY && depth < 8 && !is_killer && eval + lmp_margin <= alpha && alpha.abs() < INFINITY - 100 && !self.disabled[LATE_MOVE_PRUNING])) {
+                skip_quiet = true;
+                if self.fired(LATE_MOVE_PRUNING) {
+                    if board.make(mv.clone()) {
+                        let (score, nodes) = self.verify(LATE_MOVE_PRUNING, board, -alpha - 1, -alpha, depth - 1, self.ply + 1);
+                        self.record_verification(LATE_MOVE_PRUNING, -score > alpha, nodes as i64);
+                    }
+                    board.unmake(mv);
                 }
+                continue;
             }
 
+            let kind = MoveStats::kind(board, &mv);
             if !board.make(mv.clone()) {
                 board.unmake(mv);
                 continue;
@@ -268,12 +1036,21 @@ impl Searcher {
 
             self.ply += 1;
             legal_moves += 1;
+            let nodes_before = self.nodes;
+            self.move_stats.searched[kind] += 1;
+            self.move_kinds[self.ply as usize] = kind;
 
             if moves_searched == 0 {
                 score = -self.negamax(board, -beta, -alpha, depth - 1);
             } else {
-                if moves_searched >= self.full_depth_moves && depth >= self.reduction_limit && !in_check {
+                if moves_searched >= self.full_depth_moves && depth >= self.reduction_limit && !in_check && !self.disabled[REDUCTION] {
+                    let reduced_nodes = self.nodes;
                     score = -self.negamax(board, -alpha - 1, -alpha, depth - 2);
+                    // only a reduced search that fails low saves anything, the others are searched again
+                    if self.fired(REDUCTION) && score <= alpha {
+                        let (verified, nodes) = self.verify(REDUCTION, board, -alpha - 1, -alpha, depth - 1, self.ply);
+                        self.record_verification(REDUCTION, -verified > alpha, nodes as i64 - (self.nodes - reduced_nodes) as i64);
+                    }
                 } else {
                     score = alpha + 1;
                 }
@@ -288,6 +1065,15 @@ impl Searcher {
             board.unmake(mv.clone());
             self.ply -= 1;
 
+            if is_root {
+                self.root_nodes.insert(mv.clone(), self.nodes - nodes_before);
+                // only worth reporting once iterations get slow enough to watch
+                if self.print_info && self.elapsed() >= CURRMOVE_DELAY && !self.stop_search() {
+                    let anchor = self.anchor.resolve(board);
+                    output::current_move(depth, &format_move(&mv, anchor.as_ref(), false), legal_moves);
+                }
+            }
+
             if self.stop_search() {
                 return TIME_UP;
             }
@@ -299,24 +1085,35 @@ impl Searcher {
             }
 
             if score > alpha {
-                best_move = mv.clone();
                 best_score = score;
                 alpha = score;
+                hash_flag = HASH_EXACT;
+                best_move = mv.clone();
 
-                self.pv_table[self.ply as usize][self.ply as usize] = mv.clone();
-                for next_ply in self.ply + 1..self.pv_length[self.ply as usize + 1] {
-                    self.pv_table[self.ply as usize][next_ply as usize] = self.pv_table[self.ply as usize + 1][next_ply as usize].clone();
-                }
-                self.pv_length[self.ply as usize] = self.pv_length[self.ply as usize + 1];
+                self.update_pv(mv.clone());
 
                 if score >= beta {
+                    self.move_stats.cutoffs[kind] += 1;
                     if is_quiet {
-                        self.killers[1][self.ply as usize] = self.killers[0][self.ply as usize].clone();
-                        self.killers[0][self.ply as usize] = mv;
+                        // the quiet moves tried before this one failed to cut, they lose what it gains
+                        let bonus = (depth as i32 * depth as i32).min(MAX_HISTORY);
+                        for key in quiets_searched.drain(..) {
+                            self.update_history(key, -bonus);
+                        }
+                        if let Move::Normal(ref from, ref to) = mv {
+                            self.update_history((*board.get_piece(from).unwrap(), encode_square(to)), bonus);
+                        }
+                        self.killers[1][self.ply as usize] = self.killers[0][self.ply as usize];
+                        self.killers[0][self.ply as usize] = encoded;
                     }
+                    self.tt.store(hash_key, depth as i8, HASH_BETA, beta, mv, self.ply);
                     return beta;
                 }
             }
+
+            if let (true, Move::Normal(from, to)) = (is_quiet, &mv) {
+                quiets_searched.push((*board.get_piece(from).unwrap(), encode_square(to)));
+            }
         }
 
         if legal_moves == 0 {
@@ -327,20 +1124,97 @@ impl Searcher {
             }
         }
 
+        self.tt.store(hash_key, depth as i8, hash_flag, alpha, best_move, self.ply);
         alpha
     }
 
-    fn assign_move_scores(&mut self, board: &Board, moves: &[Move; 256], move_scores: &mut [u32; 256], moves_count: usize) {
+    // Count a firing in debug mode, true when this one is due for verification
+    fn fired(&mut self, heuristic: usize) -> bool {
+        let Some(stats) = &mut self.heuristic_stats else { return false };
+        stats.fired[heuristic] += 1;
+        self.verify_every > 0 && stats.fired[heuristic].is_multiple_of(self.verify_every)
+    }
+
+    // Search the node again from scratch with the heuristic switched off everywhere below it,
+    // on a separate searcher so nothing of this search's tables or counters is disturbed. The
+    // node keeps its ply and the search its settings, so the two searches differ only in the
+    // heuristic. Returns the score and the nodes it took.
+    fn verify(&mut self, heuristic: usize, board: &mut Board, alpha: i32, beta: i32, depth: u8, ply: u8) -> (i32, u64) {
+        let verifier = self.verifier.get_or_insert_with(|| {
+            let mut verifier = Searcher::new();
+            verifier.print_info = false;
+            verifier.tt = TranspositionTable::new(VERIFY_TT_ENTRIES);
+            Box::new(verifier)
+        });
+        verifier.disabled = self.disabled;
+        verifier.disabled[heuristic] = true;
+        verifier.stop = self.stop.clone();
+        verifier.timeset = self.timeset;
+        verifier.stoptime = self.stoptime;
+        if verifier.max_ply != self.max_ply {
+            verifier.set_max_ply(self.max_ply);
+        }
+        verifier.full_depth_moves = self.full_depth_moves;
+        verifier.reduction_limit = self.reduction_limit;
+        verifier.mobility = self.mobility;
+        verifier.anchor = self.anchor.clone();
+        verifier.root_depth = self.root_depth;
+        verifier.tt.clear();
+        verifier.clear_heuristics(false);
+        verifier.root_nodes.clear();
+        verifier.ply = ply;
+        verifier.nodes = 0;
+        let score = verifier.negamax(board, alpha, beta, depth);
+        (score, verifier.nodes)
+    }
+
+    fn record_verification(&mut self, heuristic: usize, error: bool, saved: i64) {
+        // a verification cut short by the clock proves nothing
+        if self.stop_search() {
+            return;
+        }
+        if let Some(stats) = &mut self.heuristic_stats {
+            stats.verified[heuristic] += 1;
+            stats.errors[heuristic] += error as u64;
+            stats.saved[heuristic] += saved;
+        }
+    }
+
+    // The PV of this node becomes the move followed by the PV of the child it leads to
+    fn update_pv(&mut self, mv: Move) {
+        let (lines, child_lines) = self.pv_table.split_at_mut(self.ply as usize + 1);
+        let line = &mut lines[self.ply as usize];
+        line.clear();
+        line.push(mv);
+        line.extend(child_lines[0].iter().cloned());
+    }
+
+    fn assign_move_scores(&mut self, board: &Board, moves: &[Move], move_scores: &mut [u32], moves_count: usize) {
         for move_index in 0..moves_count {
             move_scores[move_index] = self.score_move(board, &moves[move_index]);
         }
     }
 
+    // After the first iteration, root moves other than the PV/hash move are ordered by the
+    // size of their subtrees in the previous iteration: moves that needed a lot of effort to
+    // refute are the likeliest to become the new best move
+    fn order_root_moves(&self, moves: &[Move], move_scores: &mut [u32], moves_count: usize) {
+        if self.root_nodes.is_empty() {
+            return;
+        }
+
+        let mut by_nodes: Vec<usize> = (0..moves_count).filter(|&index| move_scores[index] < 15000).collect();
+        by_nodes.sort_by_key(|&index| std::cmp::Reverse(self.root_nodes.get(&moves[index]).copied().unwrap_or(0)));
+        for (rank, index) in by_nodes.into_iter().enumerate() {
+            move_scores[index] = 14000 - rank.min(13000) as u32;
+        }
+    }
+
     fn score_move(&mut self, board: &Board, mv: &Move) -> u32 {
         // if move scoring is allowed
         if self.score_pv {
             // make sure we are dealing with PV move
-            if self.pv_table[0][self.ply as usize] == *mv {
+            if self.pv_table[0].get(self.ply as usize) == Some(mv) {
                 // disable score PV flag
                 self.score_pv = false;
                 // give PV move the highest score, so we search it first
@@ -348,28 +1222,30 @@ impl Searcher {
             }
         }
 
+        // then the best move remembered in the transposition table
+        if self.hash_move.as_ref() == Some(mv) {
+            return 15000;
+        }
+
         let mut score: u32 = 0;
 
         match mv {
             Move::Normal(from, to) => {
                 if let Some(captured) = board.get_piece(to) {
-                    // prioritize captures
-                    score += 8000;
-                    // score move by piece value
-                    let piece_value = PIECE_VALUES[*captured as usize] - PIECE_VALUES[*board.get_piece(from).unwrap() as usize];
-                    if piece_value > 0 {
-                        score += piece_value as u32;
-                    } else {
-                        score -= piece_value.abs() as u32;
-                    }
+                    // prioritize captures, ordered by MVV-LVA
+                    score += 8000 + board.rules.capture_order(*captured, *board.get_piece(from).unwrap()) * 10;
                 } else {
                     // score quiet move
-                    if self.killers[0][self.ply as usize] == mv.clone() {
+                    let encoded = encode_move(mv);
+                    if self.killers[0][self.ply as usize] == encoded {
                         // score 1st killer move
                         score += 4000;
-                    } else if self.killers[1][self.ply as usize] == mv.clone() {
+                    } else if self.killers[1][self.ply as usize] == encoded {
                         // score 2nd killer move
                         score += 2500;
+                    } else if let Some(history) = self.history.get(&(*board.get_piece(from).unwrap(), encode_square(to))) {
+                        // score by history, staying below the killers
+                        score += (*history).max(0) as u32;
                     }
 
                     // reward for castling
@@ -380,9 +1256,15 @@ impl Searcher {
                     }
                 }
             }
-            Move::Promotion(_, _, promoted) => {
-                // promotions always first
-                score += 9500 + PIECE_VALUES[*promoted as usize] as u32;
+            Move::Promotion(from, to, promoted) => {
+                // promotions always first, capturing ones by what they take
+                score += 9500 + board.rules.value(*promoted).max(0) as u32;
+                if let Some(captured) = board.get_piece(to) {
+                    score += board.rules.capture_order(*captured, *board.get_piece(from).unwrap()) * 10;
+                }
+            }
+            Move::EnPassant(from, _, pawn) => {
+                score += 8000 + board.rules.capture_order(*board.get_piece(pawn).unwrap(), *board.get_piece(from).unwrap()) * 10;
             }
             _ => {}
         }
@@ -390,13 +1272,21 @@ impl Searcher {
         score
     }
 
-    fn sort_next_move(&self, moves: &mut [Move; 256], move_scores: &mut [u32; 256], start_index: usize, moves_count: usize) -> Move {
+    // History gravity: each update moves the score toward ±MAX_HISTORY by a share of the
+    // remaining distance, so frequent cutoffs saturate smoothly instead of overflowing and
+    // a move that stops cutting loses its place again
+    fn update_history(&mut self, key: (Piece, u64), bonus: i32) {
+        let score = self.history.entry(key).or_insert(0);
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+
+    fn sort_next_move(&self, moves: &mut [Move], move_scores: &mut [u32], start_index: usize, moves_count: usize) -> Move {
         let mut best_score = move_scores[start_index];
         let mut best_index = start_index;
 
-        for index in (start_index + 1)..moves_count {
-            if move_scores[index] > best_score {
-                best_score = move_scores[index];
+        for (index, &score) in move_scores.iter().enumerate().take(moves_count).skip(start_index + 1) {
+            if score > best_score {
+                best_score = score;
                 best_index = index;
             }
         }
@@ -408,4 +1298,87 @@ impl Searcher {
 
         moves[start_index].clone()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_fen;
+
+    // The draw pull is part of the evaluation, so every node past the no-progress mark sees it
+    #[test]
+    fn fortress_pull_is_in_the_evaluation() {
+        let mut board = parse_fen("K1,1;R3,3;n6,6;k8,8 w - -").unwrap();
+        let fresh = board.evaluate();
+        board.halfmove_clock = crate::evaluation::FORTRESS_PLIES + 10;
+        let stale = board.evaluate();
+        assert!(fresh > 0 && stale > 0 && stale < fresh, "{} -> {}", fresh, stale);
+        board.halfmove_clock = 10 * crate::evaluation::FORTRESS_PLIES;
+        assert!(board.evaluate() >= fresh * (100 - crate::evaluation::MAX_FORTRESS_NUDGE) / 100 - 1);
+    }
+
+    // An open rook has infinite slides, which come last and without a score instead of being
+    // searched as a pass
+    #[test]
+    fn infinite_slides_are_left_unranked() {
+        let mut board = parse_fen("K0,0;R5,5;k20,21 w - -").unwrap();
+        let mut searcher = Searcher::new();
+        searcher.print_info = false;
+        let ranked = searcher.rank_moves(&mut board, 2);
+        let slides = ranked.iter().filter(|(mv, _)| matches!(mv, Move::InfiniteMove(..))).count();
+        assert!(slides > 0);
+        for (index, (mv, score)) in ranked.iter().enumerate() {
+            assert_eq!(score.is_none(), matches!(mv, Move::InfiniteMove(..)), "{:?}", mv);
+            assert_eq!(score.is_none(), index >= ranked.len() - slides, "{:?}", mv);
+        }
+    }
+
+    // Searchers on threads of their own at once keep everything to themselves: tables,
+    // heuristics, node counters, stop flags, pawn caches and the piece values and movegen
+    // checks they play by. The rook position is searched both with classic values and with a
+    // rook worth 1500.
+    #[test]
+    fn concurrent_searches_are_isolated() {
+        let positions = [Board::new(), parse_fen("K1,1;R3,3;k8,8 w - -").unwrap()];
+        let heavy_rook = Arc::new(PieceSet::parse("rook 1500 ♖ ♜ r").unwrap());
+        let configs = [(&positions[0], None, false), (&positions[1], Some(heavy_rook), true), (&positions[1], None, false)];
+        let results: Vec<(Searcher, SearchResult, Board)> = thread::scope(|scope| {
+            let handles: Vec<_> = configs.iter().map(|(position, pieces, verify)| scope.spawn(move || {
+                let mut searcher = Searcher::new();
+                searcher.print_info = false;
+                searcher.pieces = pieces.clone();
+                searcher.verify_movegen = *verify;
+                let mut board = position.clone_without_history();
+                let result = searcher.search_position(&mut board, 3);
+                (searcher, result, board)
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        let heavy = &results[1];
+        let classic = &results[2];
+        assert!(heavy.1.score > 1400, "{}", heavy.1.score);
+        assert!(classic.1.score < 1000, "{}", classic.1.score);
+        for (_, _, board) in &results {
+            assert_eq!(*board.rules.pieces, PieceSet::classic());
+            assert!(!board.rules.verify_movegen);
+        }
+        let searchers: Vec<&Searcher> = results.iter().map(|(searcher, _, _)| searcher).collect();
+        assert!(searchers.iter().all(|searcher| !searcher.pawn_hash.scores.is_empty()));
+        assert!(searchers[0].pawn_hash.scores.keys().all(|key| !searchers[1].pawn_hash.scores.contains_key(key)));
+        let (a, b) = (searchers[0], searchers[1]);
+
+        assert!(!Arc::ptr_eq(&a.tt.entries, &b.tt.entries));
+        assert!(!Arc::ptr_eq(&a.stop, &b.stop));
+        assert!(!Arc::ptr_eq(&a.total_nodes, &b.total_nodes));
+        for (searcher, own, other) in [(a, &positions[0], &positions[1]), (b, &positions[1], &positions[0])] {
+            assert_eq!(searcher.total_nodes.load(Ordering::Relaxed), searcher.nodes);
+            assert!(searcher.tt.probe(position_key(own)).is_some());
+            assert!(searcher.tt.probe(position_key(other)).is_none());
+            // history only ever names pieces of the searcher's own position
+            assert!(searcher.history.keys().all(|(piece, _)| own.state.values().any(|present| present == piece)));
+        }
+        assert!(b.history.keys().all(|(piece, _)| !matches!(piece, Piece::WhitePawn | Piece::BlackPawn)));
+
+        a.stop.store(true, Ordering::Relaxed);
+        assert!(!b.stop.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,90 @@
+// src/selfplay.rs
+use clap::{ArgAction, Args};
+use crate::arena::{play_game, Adjudication, Player, DEFAULT_MAX_PLIES};
+use crate::board::Board;
+use crate::cli::GlobalOptions;
+use crate::export::{self, ExportOptions, GameRecord};
+use crate::game::{format_result, GameResult};
+use crate::notation::format_fen;
+use crate::rules::RuleSet;
+use crate::openings::Rng;
+use crate::tools::legal_moves;
+
+// Random plies played before the engines take over, so games from the one start position differ
+pub const DEFAULT_RANDOM_PLIES: usize = 4;
+
+// The start position after `plies` random legal moves, fewer if a side runs out of them
+pub fn random_start(rng: &mut Rng, plies: usize) -> Board {
+    let mut board = Board::new();
+    for _ in 0..plies {
+        let moves = legal_moves(&mut board);
+        if moves.is_empty() {
+            break;
+        }
+        board.make(moves[rng.below(moves.len())].clone());
+    }
+    board.clone_without_history()
+}
+
+// Every position the engines played through, one `<fen> | <result>` line each: what tune fits
+// the evaluation to
+pub fn format_positions(record: &GameRecord) -> String {
+    let mut board = record.start.clone_without_history();
+    let mut text = String::new();
+    for played in &record.moves {
+        text.push_str(&format!("{} | {}\n", format_fen(&board), format_result(record.result.0)));
+        board.make(played.mv.clone());
+    }
+    text
+}
+
+#[derive(Args, Debug)]
+pub struct SelfplayArgs {
+    #[arg(long, default_value_t = 10)]
+    pub games: u32,
+    #[arg(long, default_value_t = 1, help = "seed of the random openings")]
+    pub seed: u64,
+    #[arg(long, default_value_t = 3)]
+    pub depth: u8,
+    #[arg(long, value_name = "MS", default_value_t = -1, allow_negative_numbers = true, help = "time a move, instead of the depth")]
+    pub movetime: i32,
+    #[arg(long, default_value_t = DEFAULT_MAX_PLIES, help = "games still going after this many plies are draws")]
+    pub plies: usize,
+    #[arg(long, default_value_t = DEFAULT_RANDOM_PLIES, help = "random moves played before the engine takes over")]
+    pub random_plies: usize,
+    #[arg(long, value_name = "exact|translated", default_value = "exact", action = ArgAction::Set, value_parser = RuleSet::parse_repetition, help = "'translated' counts shifted pawnless positions as repeats")]
+    pub repetition: bool,
+    #[arg(long, value_name = "FILE", help = "append every position with its game's result, for tune")]
+    pub positions: Option<String>,
+    #[arg(long, value_name = "FILE", help = "append the games in PGN style")]
+    pub export: Option<String>,
+}
+
+pub fn run(args: &SelfplayArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (games, seed, depth, movetime, max_plies, random_plies) = (args.games, args.seed, args.depth, args.movetime, args.plies, args.random_plies);
+    let (positions, export_path) = (args.positions.as_deref(), args.export.as_deref());
+
+    let mut rng = Rng(seed);
+    let adjudication = Adjudication::new();
+    let (mut white, mut black) = (Player::parse("engine", seed, depth, movetime, options)?, Player::parse("engine", seed, depth, movetime, options)?);
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    for index in 0..games {
+        let record = play_game(&mut white, &mut black, random_start(&mut rng, random_plies), max_plies, Some(&adjudication), None, args.repetition);
+        let (result, reason) = record.result;
+        println!("game {}: {} ({}) after {} plies", index + 1, format_result(result), reason, record.moves.len());
+        match result {
+            GameResult::WhiteWins => wins += 1,
+            GameResult::Draw => draws += 1,
+            GameResult::BlackWins => losses += 1,
+        }
+        if let Some(path) = positions {
+            export::append(path, &format_positions(&record))?;
+        }
+        if let Some(path) = export_path {
+            let tags = [("Event", format!("selfplay game {}", index + 1)), ("White", "engine".to_string()), ("Black", "engine".to_string())];
+            export::append(path, &export::format_game(&record, &tags, ExportOptions { clocks: false, evals: true }))?;
+        }
+    }
+    println!("white +{} ={} -{} in {} games", wins, draws, losses, games);
+    Ok(())
+}
@@ -0,0 +1,153 @@
+// src/server.rs
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use clap::Args;
+use crate::board::Board;
+use crate::notation::{format_coordinate, format_move, parse_coordinate, parse_fen};
+use crate::output;
+use crate::r#move::Move;
+use crate::svg;
+use crate::tools::{destinations, parse_view};
+use crate::validate;
+
+pub const DEFAULT_PORT: u16 = 8080;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEADER_LINES: usize = 100;
+
+// `%xx` escapes and `+` for spaces, as browsers send a query string
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = text.get(index + 1..index + 3).ok_or("truncated % escape")?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| format!("invalid % escape '%{}'", hex))?);
+                index += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| "query is not UTF-8".to_string())
+}
+
+fn query_value(query: &str, name: &str) -> Result<Option<String>, String> {
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if key == name {
+            return percent_decode(value).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+// GET /board.svg?fen=<fen>&viewport=<x,y>:<x,y>, the start position without a fen and the
+// pieces' bounding box without a viewport
+pub fn board_svg(query: &str) -> Result<String, String> {
+    let board = match query_value(query, "fen")? {
+        Some(fen) => {
+            validate::position_text(&fen)?;
+            parse_fen(&fen)?
+        }
+        None => Board::new(),
+    };
+    validate::board(&board)?;
+    let (min, max) = match query_value(query, "viewport")? {
+        Some(viewport) => parse_view(&viewport)?,
+        None => board.bounding_box().ok_or("the board is empty, give a viewport")?,
+    };
+    svg::render(&board, &min, &max)
+}
+
+// GET /moves?fen=<fen>&square=<x,y>, the legal destinations of the piece on a square as a JSON
+// array of {square, move, infinite}, see tools::destinations
+pub fn moves_json(query: &str) -> Result<String, String> {
+    let mut board = match query_value(query, "fen")? {
+        Some(fen) => {
+            validate::position_text(&fen)?;
+            parse_fen(&fen)?
+        }
+        None => Board::new(),
+    };
+    validate::board(&board)?;
+    let square = parse_coordinate(&query_value(query, "square")?.ok_or("give the square of the piece to move")?)?;
+    let objects: Vec<String> = destinations(&mut board, &square)?.iter().map(|destination| output::json_object("destination", &[
+        ("square", output::json_string(&format_coordinate(&destination.square))),
+        ("move", output::json_string(&format_move(&destination.mv, None, false))),
+        ("infinite", matches!(destination.mv, Move::InfiniteMove(..)).to_string()),
+    ])).collect();
+    Ok(format!("[{}]\n", objects.join(",")))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body);
+    // the client may be gone already, there is nobody left to tell
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn handle(mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(clone) => clone,
+        Err(_) => return,
+    });
+
+    let mut request_line = String::new();
+    if reader.by_ref().take(validate::MAX_COMMAND_LENGTH as u64).read_line(&mut request_line).is_err() {
+        return;
+    }
+    // the headers aren't used, but a client expects them to be read
+    for _ in 0..MAX_HEADER_LINES {
+        let mut header = String::new();
+        if reader.by_ref().take(validate::MAX_COMMAND_LENGTH as u64).read_line(&mut header).map_or(true, |read| read == 0) || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        respond(&mut stream, "400 Bad Request", "text/plain", "malformed request\n");
+        return;
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match (method, path) {
+        ("GET", "/board.svg") => match board_svg(query) {
+            Ok(svg) => respond(&mut stream, "200 OK", "image/svg+xml", &svg),
+            Err(err) => respond(&mut stream, "400 Bad Request", "text/plain", &format!("{}\n", err)),
+        },
+        ("GET", "/moves") => match moves_json(query) {
+            Ok(json) => respond(&mut stream, "200 OK", "application/json", &json),
+            Err(err) => respond(&mut stream, "400 Bad Request", "text/plain", &format!("{}\n", err)),
+        },
+        (_, "/board.svg" | "/moves") => respond(&mut stream, "405 Method Not Allowed", "text/plain", "only GET is supported\n"),
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "not found, try /board.svg?fen=...&viewport=x,y:x,y or /moves?fen=...&square=x,y\n"),
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    pub port: u16,
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+}
+
+pub fn run(args: &ServeArgs) -> Result<(), String> {
+    let (host, port) = (&args.host, args.port);
+    let listener = TcpListener::bind((host.as_str(), port)).map_err(|err| format!("cannot listen on {}:{}: {}", host, port, err))?;
+    println!("serving on http://{0}:{1}/board.svg and http://{0}:{1}/moves", host, port);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || handle(stream));
+            }
+            Err(err) => eprintln!("connection failed: {}", err),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,280 @@
+// src/snapshot.rs
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{ToPrimitive, Zero};
+use std::sync::Arc;
+use crate::board::{Board, CastlingRight, Coordinate, Piece};
+use crate::evaluation::{EvalParams, DEFAULT_EVAL};
+use crate::rules::{RuleSet, WinCondition};
+
+// Compact binary form of a position, for storing many positions or sending them around.
+// Layout (all integers are LEB128 varints, signed ones zigzag encoded first):
+//   version byte
+//   flags byte: bit 0 white to move, bits 1-4 castling rights of the classical corners
+//               (q, k, Q, K), bit 5 en passant squares follow, bit 6 pawn rules follow,
+//               bit 7 royal capture
+//   [en passant count, x, y...]
+//   [white rank count, ranks..., black rank count, ranks..., pawn push distance]
+//   castling rooks off the classical corners: white count, x, y..., black count, x, y...
+//   piece count, piece kinds packed two per byte (low nibble first), then x, y per piece
+//   evaluation profile length, the profile as EvalParams::format writes it (empty for
+//   DEFAULT_EVAL), material baseline
+// Move history and search bookkeeping are not part of a snapshot. Older versions are still
+// read: version 1 had no castling rooks besides the corners, versions 1 and 2 a single en
+// passant square and no push distance, and versions up to 3 no evaluation profile.
+pub const SNAPSHOT_VERSION: u8 = 4;
+
+const WHITE_TO_MOVE: u8 = 1;
+const EN_PASSANT: u8 = 1 << 5;
+const CUSTOM_RULES: u8 = 1 << 6;
+const ROYAL_CAPTURE: u8 = 1 << 7;
+
+fn write_varint(bytes: &mut Vec<u8>, value: &BigUint) {
+    let mut value = value.clone();
+    loop {
+        let low = (&value % 128u32).to_u8().unwrap();
+        value >>= 7;
+        if value.is_zero() {
+            bytes.push(low);
+            return;
+        }
+        bytes.push(low | 0x80);
+    }
+}
+
+fn write_signed(bytes: &mut Vec<u8>, value: &BigInt) {
+    // zigzag: 0, -1, 1, -2, ... become 0, 1, 2, 3, ...
+    let zigzag = match value.sign() {
+        Sign::Minus => (value.magnitude() << 1) - 1u32,
+        _ => value.magnitude() << 1,
+    };
+    write_varint(bytes, &zigzag);
+}
+
+pub fn encode(board: &Board) -> Vec<u8> {
+    let mut bytes = vec![SNAPSHOT_VERSION];
+
+    let classical = CastlingRight::classical();
+    let mut flags = 0;
+    for (bit, right) in classical.iter().enumerate() {
+        if board.castling_rights.contains(right) {
+            flags |= 1 << (4 - bit);
+        }
+    }
+    if board.side_to_move {
+        flags |= WHITE_TO_MOVE;
+    }
+    if !board.en_passant.is_empty() {
+        flags |= EN_PASSANT;
+    }
+    if !board.rules.has_classic_pawn_rules() {
+        flags |= CUSTOM_RULES;
+    }
+    if board.rules.royal_capture() {
+        flags |= ROYAL_CAPTURE;
+    }
+    bytes.push(flags);
+
+    if !board.en_passant.is_empty() {
+        write_varint(&mut bytes, &BigUint::from(board.en_passant.len()));
+        for square in &board.en_passant {
+            write_signed(&mut bytes, &square.0);
+            write_signed(&mut bytes, &square.1);
+        }
+    }
+    if !board.rules.has_classic_pawn_rules() {
+        for ranks in [&board.rules.white_pawn_ranks, &board.rules.black_pawn_ranks] {
+            write_varint(&mut bytes, &BigUint::from(ranks.len()));
+            for rank in ranks {
+                write_signed(&mut bytes, &BigInt::from(*rank));
+            }
+        }
+        write_varint(&mut bytes, &BigUint::from(board.rules.pawn_push));
+    }
+    for white in [true, false] {
+        let rooks: Vec<&Coordinate> = board.castling_rights.iter()
+            .filter(|right| right.white == white && !classical.contains(right))
+            .map(|right| &right.rook)
+            .collect();
+        write_varint(&mut bytes, &BigUint::from(rooks.len()));
+        for rook in rooks {
+            write_signed(&mut bytes, &rook.0);
+            write_signed(&mut bytes, &rook.1);
+        }
+    }
+
+    let pieces: Vec<(&Coordinate, &Piece)> = board.state.iter().collect();
+    write_varint(&mut bytes, &BigUint::from(pieces.len()));
+    for pair in pieces.chunks(2) {
+        let low = *pair[0].1 as u8;
+        let high = pair.get(1).map_or(0, |(_, piece)| **piece as u8);
+        bytes.push(low | high << 4);
+    }
+    for (coord, _) in &pieces {
+        write_signed(&mut bytes, &coord.0);
+        write_signed(&mut bytes, &coord.1);
+    }
+
+    let profile = if *board.rules.eval == DEFAULT_EVAL { String::new() } else { board.rules.eval.format() };
+    write_varint(&mut bytes, &BigUint::from(profile.len()));
+    bytes.extend_from_slice(profile.as_bytes());
+    write_signed(&mut bytes, &BigInt::from(board.rules.material_baseline));
+
+    bytes
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.bytes.get(self.position).ok_or("snapshot ends early")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<BigUint, String> {
+        let mut value = BigUint::zero();
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            value |= BigUint::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        let count = self.varint()?.to_usize().ok_or("snapshot count too large")?;
+        // every counted item takes at least one byte, anything larger is corrupt
+        if count > self.bytes.len() - self.position {
+            return Err("snapshot count exceeds its size".to_string());
+        }
+        Ok(count)
+    }
+
+    fn signed(&mut self) -> Result<BigInt, String> {
+        let zigzag = self.varint()?;
+        let magnitude = BigInt::from(&zigzag >> 1);
+        Ok(if zigzag.bit(0) { -magnitude - 1 } else { magnitude })
+    }
+
+    fn coordinate(&mut self) -> Result<Coordinate, String> {
+        Ok(Coordinate(self.signed()?, self.signed()?))
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Board, String> {
+    let mut reader = Reader { bytes, position: 0 };
+    let version = reader.byte()?;
+    if !(1..=SNAPSHOT_VERSION).contains(&version) {
+        return Err(format!("unsupported snapshot version {}", version));
+    }
+
+    let flags = reader.byte()?;
+    let mut board = Board::empty();
+    board.side_to_move = flags & WHITE_TO_MOVE != 0;
+    board.castling_rights = CastlingRight::classical().into_iter()
+        .enumerate()
+        .filter(|(bit, _)| flags & (1 << (4 - bit)) != 0)
+        .map(|(_, right)| right)
+        .collect();
+    if flags & EN_PASSANT != 0 {
+        let count = if version > 2 { reader.count()? } else { 1 };
+        for _ in 0..count {
+            board.en_passant.push(reader.coordinate()?);
+        }
+    }
+    if flags & CUSTOM_RULES != 0 {
+        let mut ranks = || -> Result<Vec<i64>, String> {
+            let count = reader.count()?;
+            (0..count).map(|_| reader.signed()?.to_i64().ok_or("pawn rank out of range".to_string())).collect()
+        };
+        board.rules = RuleSet { white_pawn_ranks: ranks()?, black_pawn_ranks: ranks()?, ..RuleSet::classic() };
+        if version > 2 {
+            board.rules.pawn_push = reader.varint()?.to_u32().ok_or("pawn push distance out of range")?;
+            board.rules.check_pawn_push()?;
+        }
+    }
+    if flags & ROYAL_CAPTURE != 0 {
+        board.rules.win_condition = WinCondition::RoyalCapture;
+    }
+    if version > 1 {
+        for white in [true, false] {
+            for _ in 0..reader.count()? {
+                board.castling_rights.push(CastlingRight { rook: reader.coordinate()?, white });
+            }
+        }
+    }
+
+    let count = reader.count()?;
+    let mut kinds = Vec::with_capacity(count);
+    for index in 0..count {
+        if index % 2 == 0 {
+            let byte = reader.byte()?;
+            kinds.push(byte & 0x0f);
+            if index + 1 < count {
+                kinds.push(byte >> 4);
+            }
+        }
+    }
+    for kind in kinds {
+        if kind >= 12 {
+            return Err(format!("invalid piece {} in snapshot", kind));
+        }
+        let piece = Piece::from_kind(kind as usize % 6, kind < 6);
+        board.state.insert(reader.coordinate()?, piece);
+    }
+    if version > 3 {
+        let length = reader.count()?;
+        let text = std::str::from_utf8(&bytes[reader.position..reader.position + length]).map_err(|_| "evaluation profile is not text")?;
+        reader.position += length;
+        if !text.is_empty() {
+            board.rules.eval = Arc::new(EvalParams::parse(text, &board.rules.pieces).map_err(|err| format!("evaluation profile: {}", err))?);
+        }
+        board.rules.material_baseline = reader.signed()?.to_i32().ok_or("material baseline out of range")?;
+    }
+
+    if reader.position != bytes.len() {
+        return Err("trailing bytes after snapshot".to_string());
+    }
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::{find_variant, VARIANTS};
+
+    // A variant's evaluation profile and material baseline come back with its position
+    #[test]
+    fn snapshots_keep_the_evaluation_profile() {
+        let board = find_variant("peasants").unwrap().board().unwrap();
+        let restored = decode(&encode(&board)).unwrap();
+        assert_eq!(restored.rules.eval, board.rules.eval);
+        assert_eq!(restored.rules.material_baseline, board.rules.material_baseline);
+        assert_eq!(restored.evaluate(), board.evaluate());
+
+        let classical = Board::new();
+        assert_eq!(*decode(&encode(&classical)).unwrap().rules.eval, DEFAULT_EVAL);
+    }
+
+    // Save then load every variant's start position, and its colour-flipped copy whose baseline
+    // is negated, and get the same position and rules back
+    #[test]
+    fn material_baselines_round_trip() {
+        for variant in &VARIANTS {
+            let board = variant.board().unwrap();
+            for board in [board.flipped().unwrap(), board] {
+                let restored = decode(&encode(&board)).unwrap();
+                assert_eq!(restored.rules, board.rules, "{}", variant.name);
+                assert_eq!(restored.state, board.state, "{}", variant.name);
+                assert_eq!(restored.side_to_move, board.side_to_move, "{}", variant.name);
+            }
+        }
+        assert!(VARIANTS.iter().any(|variant| variant.board().unwrap().rules.material_baseline != 0));
+    }
+}
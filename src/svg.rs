@@ -0,0 +1,55 @@
+// src/svg.rs
+use num_bigint::BigInt;
+use num_traits::Zero;
+use crate::board::{Board, Coordinate};
+use crate::tools::MAX_VIEW;
+
+pub const SQUARE: i64 = 45; // side of a square in pixels
+const MARGIN: i64 = 30; // room for the rank and file labels
+const LIGHT: &str = "#f0d9b5";
+const DARK: &str = "#b58863";
+
+// Text nodes take the piece glyphs and labels of a piece set file, which may hold anything
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// The squares between two corners as an SVG image, rank max.1 at the top like Board::show.
+// Pieces outside the view are counted in the title.
+pub fn render(board: &Board, min: &Coordinate, max: &Coordinate) -> Result<String, String> {
+    let width: BigInt = &max.0 - &min.0 + 1;
+    let height: BigInt = &max.1 - &min.1 + 1;
+    if width > BigInt::from(MAX_VIEW) || height > BigInt::from(MAX_VIEW) {
+        return Err(format!("viewport larger than {0}x{0}", MAX_VIEW));
+    }
+    let (width, height): (i64, i64) = (width.try_into().unwrap(), height.try_into().unwrap());
+
+    let (pixels_x, pixels_y) = (width * SQUARE + MARGIN, height * SQUARE + MARGIN);
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n", pixels_x, pixels_y);
+    let outside = board.state.keys().filter(|coord| coord.0 < min.0 || coord.0 > max.0 || coord.1 < min.1 || coord.1 > max.1).count();
+    svg.push_str(&format!("<title>{} to {}, {} pieces outside the view</title>\n", min, max, outside));
+    svg.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", pixels_x, pixels_y));
+
+    for row in 0..height {
+        let rank = &max.1 - row;
+        for column in 0..width {
+            let file = &min.0 + column;
+            let (x, y) = (MARGIN + column * SQUARE, row * SQUARE);
+            // a1 is dark, as on the classical board
+            let color = if ((&file + &rank) % 2u8).is_zero() { DARK } else { LIGHT };
+            svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n", x, y, SQUARE, SQUARE, color));
+            if let Some(piece) = board.get_piece(&Coordinate(file, rank.clone())) {
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                    x + SQUARE / 2, y + SQUARE / 2, SQUARE * 4 / 5, escape(piece.glyph()),
+                ));
+            }
+        }
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\" dominant-baseline=\"central\">{}</text>\n", MARGIN - 4, row * SQUARE + SQUARE / 2, rank));
+    }
+    for column in 0..width {
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"middle\">{}</text>\n", MARGIN + column * SQUARE + SQUARE / 2, height * SQUARE + 18, &min.0 + column));
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
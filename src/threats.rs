@@ -0,0 +1,94 @@
+// src/threats.rs
+use crate::board::{Board, Coordinate, Piece};
+use crate::game::{game_end, GameResult};
+use crate::notation::format_move;
+use crate::r#move::Move;
+use crate::tools::legal_moves;
+
+// What one side could do if it were its move, found one ply deep. The side not to move is
+// looked at through a null move, so these are threats the opponent has to answer.
+pub enum Threat {
+    Mate(Move),
+    Capture { mv: Move, attacker: Piece, victim: Piece, square: Coordinate, defended: bool },
+}
+
+impl Threat {
+    pub fn describe(&self, white: bool) -> String {
+        let side = if white { "White" } else { "Black" };
+        match self {
+            Threat::Mate(mv) => format!("{} threatens mate with {}", side, format_move(mv, None, false)),
+            Threat::Capture { mv, attacker, victim, square, defended } => format!(
+                "{} threatens {}{} winning the {} {}{}",
+                side, attacker.letter(), format_move(mv, None, false), if *defended { "more valuable" } else { "undefended" }, victim.letter(), square,
+            ),
+        }
+    }
+}
+
+// Mates first, then captures of the most valuable pieces. Nothing is reported for the side not
+// to move while the side to move is in check, that position can't arise from a null move.
+pub fn threats(board: &Board, white: bool) -> Vec<Threat> {
+    let mut board = board.clone_without_history();
+    if board.side_to_move != white {
+        if board.in_check(board.side_to_move) {
+            return Vec::new();
+        }
+        board.side_to_move = white;
+        board.en_passant.clear();
+    }
+
+    let mut mates = Vec::new();
+    let mut captures: Vec<Threat> = Vec::new();
+    for mv in legal_moves(&mut board) {
+        if gives_mate(&mut board, &mv) {
+            mates.push(Threat::Mate(mv));
+            continue;
+        }
+        let (from, square) = match &mv {
+            Move::Normal(from, to) | Move::Promotion(from, to, _) => (from.clone(), to.clone()),
+            Move::EnPassant(from, _, pawn) => (from.clone(), pawn.clone()),
+            _ => continue,
+        };
+        let (Some(&attacker), Some(&victim)) = (board.get_piece(&from), board.get_piece(&square)) else { continue };
+        if matches!(victim, Piece::WhiteKing | Piece::BlackKing) {
+            continue;
+        }
+        // a piece never attacks its own square, so these are only the victim's defenders
+        let defended = !board.attackers_of(&square, !white).is_empty();
+        let value = |piece: Piece| board.rules.value(piece);
+        if defended && value(victim) <= value(attacker) {
+            continue;
+        }
+        // one threat per victim, made with the cheapest attacker
+        match captures.iter_mut().find(|threat| matches!(threat, Threat::Capture { square: other, .. } if *other == square)) {
+            Some(Threat::Capture { mv: old, attacker: cheapest, .. }) if value(attacker) < value(*cheapest) => {
+                *old = mv;
+                *cheapest = attacker;
+            }
+            Some(_) => {}
+            None => captures.push(Threat::Capture { mv, attacker, victim, square, defended }),
+        }
+    }
+    captures.sort_by_key(|threat| match threat {
+        Threat::Capture { victim, .. } => -board.rules.value(*victim),
+        Threat::Mate(_) => 0,
+    });
+    mates.extend(captures);
+    mates
+}
+
+fn gives_mate(board: &mut Board, mv: &Move) -> bool {
+    board.make(mv.clone());
+    let mate = matches!(game_end(board, 0), Some((GameResult::WhiteWins | GameResult::BlackWins, _)));
+    board.unmake(mv.clone());
+    mate
+}
+
+// Threats of both sides as text, the side to move first
+pub fn describe_threats(board: &Board) -> Vec<String> {
+    let mut facts = Vec::new();
+    for white in [board.side_to_move, !board.side_to_move] {
+        facts.extend(threats(board, white).iter().map(|threat| threat.describe(white)));
+    }
+    facts
+}
@@ -0,0 +1,205 @@
+// src/tools.rs
+use crate::board::{Board, Coordinate};
+use std::time::Instant;
+use num_bigint::BigInt;
+use crate::search::Searcher;
+use crate::hash::position_key;
+use crate::snapshot;
+use crate::notation::{format_fen, format_move, parse_coordinate, parse_fen, parse_move};
+use crate::r#move::{Move, MoveGen, MoveList};
+use num_traits::ToPrimitive;
+use clap::{Args, Subcommand};
+
+// Largest viewport side `attacks` renders, every cell checks every piece
+pub const MAX_VIEW: i64 = 64;
+
+// Moves of the side to move that do not leave its own king in check
+pub fn legal_moves(board: &mut Board) -> Vec<Move> {
+    let mut move_list = MoveList::new();
+    MoveGen::generate_moves(board, &mut move_list);
+
+    let mut legal = Vec::new();
+    for mv in &move_list.moves[..move_list.count as usize] {
+        if board.make(mv.clone()) {
+            legal.push(mv.clone());
+        }
+        board.unmake(mv.clone());
+    }
+    legal
+}
+
+// A square the selected piece can be dropped on, and the move that puts it there
+pub struct Destination {
+    pub square: Coordinate,
+    pub mv: Move,
+}
+
+// Legal destinations of the side to move's piece on a square, for click-to-move front-ends.
+// An infinite slide has no square of its own, so it is proposed once, on the first square of
+// its ray, for the front-end to extend along the ray.
+pub fn destinations(board: &mut Board, square: &Coordinate) -> Result<Vec<Destination>, String> {
+    let piece = board.get_piece(square).ok_or_else(|| format!("no piece on {}", square))?;
+    if piece.is_white() != board.side_to_move {
+        return Err(format!("the piece on {} isn't the side to move's", square));
+    }
+    let mut destinations = Vec::new();
+    for mv in legal_moves(board) {
+        match &mv {
+            Move::Normal(from, to) | Move::Castling(from, to) | Move::EnPassant(from, to, _) | Move::Promotion(from, to, _) if from == square => {
+                destinations.push(Destination { square: to.clone(), mv });
+            }
+            Move::InfiniteMove(from, direction) if from == square => {
+                destinations.push(Destination { square: from.step(*direction, &BigInt::from(1)), mv });
+            }
+            _ => {}
+        }
+    }
+    Ok(destinations)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(format!("invalid hex '{}'", text));
+    }
+    (0..text.len()).step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16).map_err(|_| format!("invalid hex '{}'", text)))
+        .collect()
+}
+
+// Wall clock milliseconds from starting a `movetime` search to having its move, with a fresh
+// searcher every run so no run profits from the TT of the previous one
+pub fn measure_latency(board: &Board, movetime: i32, runs: usize) -> Vec<u128> {
+    (0..runs)
+        .map(|_| {
+            let mut searcher = Searcher::new();
+            searcher.print_info = false;
+            searcher.movetime = movetime;
+            let start = Instant::now();
+            searcher.search_position(&mut board.clone_without_history(), u8::MAX);
+            start.elapsed().as_millis()
+        })
+        .collect()
+}
+
+// Two opposite corners `x,y:x,y`, returned as (lower left, upper right)
+pub fn parse_view(text: &str) -> Result<(Coordinate, Coordinate), String> {
+    let (a, b) = text.split_once(':').ok_or_else(|| format!("invalid view '{}', expected '<x,y>:<x,y>'", text))?;
+    let (a, b) = (parse_coordinate(a)?, parse_coordinate(b)?);
+    Ok((Coordinate((&a.0).min(&b.0).clone(), (&a.1).min(&b.1).clone()), Coordinate((&a.0).max(&b.0).clone(), (&a.1).max(&b.1).clone())))
+}
+
+// The position a tool works on: a FEN or snapshot (the start position without either) and moves
+// played from it
+#[derive(Args, Debug)]
+pub struct SetupArgs {
+    #[arg(long, conflicts_with = "snapshot", help = "position in extended FEN")]
+    pub fen: Option<String>,
+    #[arg(long, value_name = "HEX", help = "position as snapshot bytes")]
+    pub snapshot: Option<String>,
+    #[arg(long, num_args = 1.., allow_hyphen_values = true, help = "moves played from the position")]
+    pub moves: Vec<String>,
+}
+
+impl SetupArgs {
+    pub fn board(&self) -> Result<Board, String> {
+        let mut board = match (&self.fen, &self.snapshot) {
+            (Some(fen), _) => parse_fen(fen)?,
+            (None, Some(hex)) => snapshot::decode(&from_hex(hex)?)?,
+            (None, None) => Board::new(),
+        };
+        for text in &self.moves {
+            let mv = parse_move(&board, text)?;
+            if !board.make(mv.clone()) {
+                return Err(format!("move '{}' leaves the king in check", text));
+            }
+        }
+        Ok(board)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Tool {
+    #[command(about = "hash of the position")]
+    Hash(SetupArgs),
+    #[command(about = "the position in extended FEN")]
+    Fen(SetupArgs),
+    #[command(about = "the colour-flipped position")]
+    Flip(SetupArgs),
+    #[command(about = "the position turned around the board centre")]
+    Rotate(SetupArgs),
+    #[command(about = "the whole position shifted")]
+    Translate {
+        #[arg(long, value_name = "DX,DY", value_parser = parse_coordinate, allow_hyphen_values = true)]
+        by: Coordinate,
+        #[command(flatten)]
+        setup: SetupArgs,
+    },
+    #[command(about = "legal moves of the side to move")]
+    Legal(SetupArgs),
+    #[command(about = "the position as snapshot bytes")]
+    Snapshot(SetupArgs),
+    #[command(about = "attacked squares, the view defaults to the pieces' bounding box")]
+    Attacks {
+        #[arg(long, value_name = "X,Y:X,Y", value_parser = parse_view, allow_hyphen_values = true)]
+        view: Option<(Coordinate, Coordinate)>,
+        #[arg(long, value_name = "X,Y", value_parser = parse_coordinate, allow_hyphen_values = true, help = "also list the pieces attacking this square")]
+        square: Option<Coordinate>,
+        #[command(flatten)]
+        setup: SetupArgs,
+    },
+    #[command(about = "wall clock time of movetime searches")]
+    Latency {
+        #[arg(long, value_name = "MS", default_value_t = 100)]
+        movetime: i32,
+        #[arg(long, default_value_t = 20)]
+        runs: usize,
+        #[command(flatten)]
+        setup: SetupArgs,
+    },
+}
+
+pub fn run(tool: &Tool) -> Result<(), String> {
+    match tool {
+        Tool::Hash(setup) => println!("{:016x}", position_key(&setup.board()?)),
+        Tool::Fen(setup) => println!("{}", format_fen(&setup.board()?)),
+        Tool::Flip(setup) => println!("{}", format_fen(&setup.board()?.flipped()?)),
+        Tool::Rotate(setup) => println!("{}", format_fen(&setup.board()?.rotate180()?)),
+        Tool::Translate { by, setup } => {
+            let (Some(dx), Some(dy)) = (by.0.to_i64(), by.1.to_i64()) else {
+                return Err("translation offset out of range".to_string());
+            };
+            println!("{}", format_fen(&setup.board()?.translate(dx, dy)?));
+        }
+        Tool::Snapshot(setup) => println!("{}", to_hex(&snapshot::encode(&setup.board()?))),
+        Tool::Attacks { view, square, setup } => {
+            let board = setup.board()?;
+            let (min, max) = match view {
+                Some(view) => view.clone(),
+                None => board.bounding_box().ok_or("the board is empty, give a --view")?,
+            };
+            if &max.0 - &min.0 >= BigInt::from(MAX_VIEW) || &max.1 - &min.1 >= BigInt::from(MAX_VIEW) {
+                return Err(format!("viewport larger than {0}x{0}, narrow it with --view", MAX_VIEW));
+            }
+            board.show_attacks(&min, &max);
+            if let Some(square) = square {
+                board.show_attackers(square);
+            }
+        }
+        Tool::Latency { movetime, runs, setup } => {
+            let mut times = measure_latency(&setup.board()?, *movetime, (*runs).max(1));
+            times.sort();
+            let percentile = |p: usize| times[(times.len() * p / 100).min(times.len() - 1)];
+            println!("movetime {} runs {} p50 {} p95 {} max {}", movetime, times.len(), percentile(50), percentile(95), times[times.len() - 1]);
+        }
+        Tool::Legal(setup) => {
+            for mv in legal_moves(&mut setup.board()?) {
+                println!("{}", format_move(&mv, None, false));
+            }
+        }
+    }
+    Ok(())
+}
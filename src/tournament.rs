@@ -0,0 +1,275 @@
+// src/tournament.rs
+use std::collections::HashMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread;
+use clap::{ArgAction, Args};
+use clap::builder::BoolishValueParser;
+use crate::arena::{config_name, parse_time_control, play_game, Adjudication, Player, DEFAULT_MAX_PLIES, PLAYERS};
+use crate::board::Board;
+use crate::clock::Clock;
+use crate::rules::RuleSet;
+use crate::cli::GlobalOptions;
+use crate::export::{self, ExportOptions};
+use crate::game::{format_result, GameResult};
+use crate::rating::{parse_label, print_ratings, RatingFile};
+
+// A player of the tournament, `engine` limits overridden per entrant as `engine:d4` or `engine:250ms`
+#[derive(Debug, Clone)]
+pub struct Entrant {
+    pub player: String,
+    pub depth: u8,
+    pub movetime: i32,
+    pub name: String, // see arena::config_name
+}
+
+impl Entrant {
+    pub fn parse(spec: &str, depth: u8, movetime: i32, label: Option<&str>) -> Result<Entrant, String> {
+        let (player, limit) = spec.split_once(':').unwrap_or((spec, ""));
+        if !PLAYERS.contains(&player) {
+            return Err(format!("unknown player '{}', expected one of {}", player, PLAYERS.join(", ")));
+        }
+        let invalid = || format!("invalid limit '{}' in '{}', expected d<depth> or <ms>ms", limit, spec);
+        let (depth, movetime) = match limit {
+            "" => (depth, movetime),
+            _ if player != "engine" => return Err(format!("only engine players take a limit, not '{}'", spec)),
+            _ if limit.starts_with('d') => (limit[1..].parse().map_err(|_| invalid())?, -1),
+            _ => (depth, limit.strip_suffix("ms").and_then(|ms| ms.parse().ok()).ok_or_else(invalid)?),
+        };
+        Ok(Entrant { player: player.to_string(), depth, movetime, name: config_name(player, depth, movetime, label) })
+    }
+}
+
+// One game of the schedule, entrants by index
+#[derive(Debug, Clone, Copy)]
+pub struct Pairing {
+    pub game: usize,
+    pub white: usize,
+    pub black: usize,
+}
+
+// Every pair of entrants, or the first entrant (the champion) against each of the others,
+// with colours alternating inside each round
+pub fn schedule(entrants: usize, gauntlet: bool, rounds: usize) -> Vec<Pairing> {
+    let pairs: Vec<(usize, usize)> = if gauntlet {
+        (1..entrants).map(|other| (0, other)).collect()
+    } else {
+        (0..entrants).flat_map(|a| (a + 1..entrants).map(move |b| (a, b))).collect()
+    };
+    let mut pairings = Vec::new();
+    for _ in 0..rounds {
+        for &(a, b) in &pairs {
+            for (white, black) in [(a, b), (b, a)] {
+                pairings.push(Pairing { game: pairings.len() + 1, white, black });
+            }
+        }
+    }
+    pairings
+}
+
+pub struct Finished {
+    pub game: usize,
+    pub white: String,
+    pub black: String,
+    pub result: GameResult,
+    pub reason: String,
+}
+
+// Games already played, one line each as `<game> <white> <black> <result> <reason>`, so a
+// tournament that was interrupted (or crashed) picks up where it stopped when run again
+pub struct StateFile {
+    pub path: String,
+}
+
+impl StateFile {
+    pub fn load(&self) -> Result<Vec<Finished>, String> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(format!("{}: {}", self.path, err)),
+        };
+        let mut finished = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')) {
+            let invalid = || format!("{}:{}: expected '<game> <white> <black> <result> <reason>'", self.path, number + 1);
+            let mut fields = line.splitn(5, ' ');
+            let (Some(game), Some(white), Some(black), Some(result)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                return Err(invalid());
+            };
+            let result = match result {
+                "1-0" => GameResult::WhiteWins,
+                "0-1" => GameResult::BlackWins,
+                "1/2-1/2" => GameResult::Draw,
+                _ => return Err(invalid()),
+            };
+            finished.push(Finished {
+                game: game.parse().map_err(|_| invalid())?,
+                white: white.to_string(),
+                black: black.to_string(),
+                result,
+                reason: fields.next().unwrap_or("").to_string(),
+            });
+        }
+        Ok(finished)
+    }
+
+    pub fn record(&self, finished: &Finished) -> Result<(), String> {
+        export::append(&self.path, &format!("{} {} {} {} {}\n", finished.game, finished.white, finished.black, format_result(finished.result), finished.reason))
+    }
+}
+
+// Wins, draws and losses of every entrant
+pub fn print_standings(entrants: &[Entrant], finished: &[Finished]) {
+    let mut scores: HashMap<&str, (u32, u32, u32)> = entrants.iter().map(|entrant| (entrant.name.as_str(), (0, 0, 0))).collect();
+    for game in finished {
+        // white's and black's column: 0 for a win, 1 for a draw, 2 for a loss
+        let columns = match game.result {
+            GameResult::WhiteWins => [0, 2],
+            GameResult::BlackWins => [2, 0],
+            GameResult::Draw => [1, 1],
+        };
+        for (name, column) in [game.white.as_str(), game.black.as_str()].into_iter().zip(columns) {
+            if let Some(score) = scores.get_mut(name) {
+                match column {
+                    0 => score.0 += 1,
+                    1 => score.1 += 1,
+                    _ => score.2 += 1,
+                }
+            }
+        }
+    }
+    let mut table: Vec<(&str, (u32, u32, u32))> = scores.into_iter().collect();
+    table.sort_by(|a, b| {
+        let points = |(wins, draws, _): (u32, u32, u32)| 2 * wins + draws;
+        points(b.1).cmp(&points(a.1)).then(a.0.cmp(b.0))
+    });
+    println!("{:<24} {:>7} {:>6} {:>6} {:>6}", "player", "points", "wins", "draws", "losses");
+    for (name, (wins, draws, losses)) in table {
+        println!("{:<24} {:>7.1} {:>6} {:>6} {:>6}", name, wins as f64 + draws as f64 / 2.0, wins, draws, losses);
+    }
+}
+
+// Entrant specs are checked by Entrant::parse
+#[derive(Args, Debug)]
+pub struct TournamentArgs {
+    #[arg(num_args = 2.., required = true, value_name = "PLAYER", help = "random, greedy, depth1 or engine, engine:d<depth> or engine:<ms>ms for limits of its own")]
+    pub players: Vec<String>,
+    #[arg(long, value_name = "roundrobin|gauntlet", value_parser = ["roundrobin", "gauntlet"], default_value = "roundrobin", help = "gauntlet plays the first player against each other one")]
+    pub mode: String,
+    #[arg(long, default_value_t = 1)]
+    pub rounds: usize,
+    #[arg(long, default_value_t = 1, help = "games played at the same time")]
+    pub concurrency: usize,
+    #[arg(long, value_name = "FILE", help = "games played so far, to resume from")]
+    pub state: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<String>,
+    #[arg(long, value_name = "clocks,evals|none", default_value = "clocks,evals", value_parser = ExportOptions::parse)]
+    pub export_comments: ExportOptions,
+    #[arg(long, value_name = "FILE")]
+    pub ratings: Option<String>,
+    #[arg(long, value_parser = parse_label)]
+    pub label: Option<String>,
+    #[arg(long, default_value_t = 1)]
+    pub seed: u64,
+    #[arg(long, default_value_t = 3)]
+    pub depth: u8,
+    #[arg(long, value_name = "MS", default_value_t = -1, allow_negative_numbers = true)]
+    pub movetime: i32,
+    #[arg(long, value_name = "MS+MS", value_parser = parse_time_control)]
+    pub tc: Option<Clock>,
+    #[arg(long, default_value_t = DEFAULT_MAX_PLIES)]
+    pub plies: usize,
+    #[arg(long, value_name = "on|off", default_value = "on", action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    pub adjudicate: bool,
+    #[arg(long, value_name = "exact|translated", default_value = "exact", action = ArgAction::Set, value_parser = RuleSet::parse_repetition)]
+    pub repetition: bool,
+}
+
+pub fn run(args: &TournamentArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (gauntlet, seed, max_plies, adjudicate, clock) = (args.mode == "gauntlet", args.seed, args.plies, args.adjudicate, args.tc.clone());
+    let state = args.state.as_ref().map(|path| StateFile { path: path.clone() });
+    let ratings = args.ratings.as_ref().map(|path| RatingFile { path: path.clone() });
+    let (export_path, export_options) = (args.export.as_deref(), args.export_comments);
+    let entrants = args.players.iter().map(|spec| Entrant::parse(spec, args.depth, args.movetime, args.label.as_deref())).collect::<Result<Vec<_>, _>>()?;
+    for (index, entrant) in entrants.iter().enumerate() {
+        if entrants[..index].iter().any(|other| other.name == entrant.name) {
+            return Err(format!("{} is entered twice", entrant.name));
+        }
+    }
+
+    let pairings = schedule(entrants.len(), gauntlet, args.rounds);
+    let mut finished = match &state {
+        Some(state) => state.load()?,
+        None => Vec::new(),
+    };
+    for game in &finished {
+        let matches = pairings.get(game.game.wrapping_sub(1)).is_some_and(|pairing| entrants[pairing.white].name == game.white && entrants[pairing.black].name == game.black);
+        if !matches {
+            return Err(format!("game {} in the state file doesn't belong to this tournament", game.game));
+        }
+    }
+    let mut pending: Vec<Pairing> = pairings.iter().filter(|pairing| !finished.iter().any(|game| game.game == pairing.game)).copied().collect();
+    if !finished.is_empty() {
+        println!("resuming: {} of {} games already played", finished.len(), pairings.len());
+    }
+    pending.reverse(); // taken from the back, so games start in schedule order
+
+    let adjudication = Adjudication::new();
+    let queue = Mutex::new(pending);
+    let results = Mutex::new(Vec::new());
+    let errors = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..args.concurrency.max(1) {
+            scope.spawn(|| loop {
+                let Some(pairing) = queue.lock().unwrap().pop() else { break };
+                let (white, black) = (&entrants[pairing.white], &entrants[pairing.black]);
+                let game_seed = seed ^ (pairing.game as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                // a game that panics loses only itself: it is reported, stays unplayed in the
+                // state file and is played again on the next run
+                let played = panic::catch_unwind(AssertUnwindSafe(|| -> Result<_, String> {
+                    let mut white_player = Player::parse(&white.player, game_seed, white.depth, white.movetime, options)?;
+                    let mut black_player = Player::parse(&black.player, game_seed.rotate_left(32), black.depth, black.movetime, options)?;
+                    Ok(play_game(&mut white_player, &mut black_player, Board::new(), max_plies, adjudicate.then_some(&adjudication), clock.clone(), args.repetition))
+                }));
+                let record = match played {
+                    Ok(Ok(record)) => record,
+                    Ok(Err(err)) => {
+                        errors.lock().unwrap().push(format!("game {}: {}", pairing.game, err));
+                        continue;
+                    }
+                    Err(_) => {
+                        errors.lock().unwrap().push(format!("game {}: {} vs {} crashed, it is played again on the next run", pairing.game, white.name, black.name));
+                        continue;
+                    }
+                };
+
+                let game = Finished { game: pairing.game, white: white.name.clone(), black: black.name.clone(), result: record.result.0, reason: record.result.1.to_string() };
+                // one game's bookkeeping at a time, so the files never interleave
+                let mut results = results.lock().unwrap();
+                println!("game {}: {} vs {} {} ({})", game.game, game.white, game.black, format_result(game.result), game.reason);
+                let saved = state.as_ref().map_or(Ok(()), |state| state.record(&game))
+                    .and_then(|_| ratings.as_ref().map_or(Ok(()), |ratings| ratings.record(&game.white, &game.black, game.result)))
+                    .and_then(|_| export_path.map_or(Ok(()), |path| {
+                        let tags = [("Event", format!("tournament game {}", game.game)), ("White", game.white.clone()), ("Black", game.black.clone())];
+                        export::append(path, &export::format_game(&record, &tags, export_options))
+                    }));
+                if let Err(err) = saved {
+                    errors.lock().unwrap().push(err);
+                }
+                results.push(game);
+            });
+        }
+    });
+
+    finished.extend(results.into_inner().unwrap());
+    print_standings(&entrants, &finished);
+    if let Some(ratings) = &ratings {
+        print_ratings(ratings)?;
+    }
+    let errors = errors.into_inner().unwrap();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// src/tt.rs
+use std::sync::{Arc, Mutex};
+use crate::r#move::Move;
+use crate::search::MATE_SCORE;
+
+pub const HASH_EXACT: u8 = 0;
+pub const HASH_ALPHA: u8 = 1; // score is an upper bound
+pub const HASH_BETA: u8 = 2; // score is a lower bound
+
+// Quiescence results are stored with this depth, so any main search entry outranks them
+pub const QUIESCENCE_DEPTH: i8 = 0;
+
+pub const DEFAULT_TT_ENTRIES: usize = 1 << 16;
+// Largest table `--hash` and the UCI Hash option accept
+pub const MAX_HASH_MB: usize = 16384;
+
+#[derive(Debug, Clone)]
+pub struct TTEntry {
+    pub key: u64,
+    pub depth: i8,
+    pub flag: u8,
+    pub score: i32,
+    pub best_move: Move,
+}
+
+const ENTRY_BYTES: usize = std::mem::size_of::<Mutex<Option<TTEntry>>>();
+
+// Entries of a table of at most `mb` megabytes, clamped to 1..=MAX_HASH_MB. Tables are a power
+// of two in size, so this rounds down to stay within the limit.
+pub fn entries_for_mb(mb: usize) -> usize {
+    1 << (mb.clamp(1, MAX_HASH_MB) * 1024 * 1024 / ENTRY_BYTES).ilog2()
+}
+
+// Megabytes taken by that many entries, rounded up
+pub fn mb_for_entries(entries: usize) -> usize {
+    (entries * ENTRY_BYTES).div_ceil(1024 * 1024)
+}
+
+// Clones share the same entries, which is how search threads share what they found. Every
+// slot has its own lock, so threads only wait for each other on the very same slot.
+// Slots are not prefetched: a key is hashed from the whole board right before its probe, so
+// there is no work for a prefetch to hide the memory access behind.
+#[derive(Debug, Clone)]
+pub struct TranspositionTable {
+    pub entries: Arc<Vec<Mutex<Option<TTEntry>>>>,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> Self {
+        TranspositionTable {
+            entries: Arc::new((0..size.max(1).next_power_of_two()).map(|_| Mutex::new(None)).collect()),
+        }
+    }
+
+    pub fn with_mb(mb: usize) -> Self {
+        TranspositionTable::new(entries_for_mb(mb))
+    }
+
+    pub fn clear(&self) {
+        for entry in self.entries.iter() {
+            *entry.lock().unwrap() = None;
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        self.entries[self.index(key)].lock().unwrap().as_ref().filter(|entry| entry.key == key).cloned()
+    }
+
+    // Usable score for a window, with mate scores converted back from "distance from this
+    // node" to "distance from the root"
+    pub fn probe_score(&self, key: u64, depth: i8, alpha: i32, beta: i32, ply: u8) -> Option<i32> {
+        let entry = self.probe(key)?;
+        if entry.depth < depth {
+            return None;
+        }
+
+        let mut score = entry.score;
+        if score < -MATE_SCORE {
+            score += ply as i32;
+        } else if score > MATE_SCORE {
+            score -= ply as i32;
+        }
+
+        match entry.flag {
+            HASH_EXACT => Some(score),
+            HASH_ALPHA if score <= alpha => Some(alpha),
+            HASH_BETA if score >= beta => Some(beta),
+            _ => None,
+        }
+    }
+
+    pub fn best_move(&self, key: u64) -> Option<Move> {
+        self.probe(key).map(|entry| entry.best_move).filter(|mv| *mv != Move::None)
+    }
+
+    // Depth-preferred replacement: shallower results never evict deeper ones of the same position
+    pub fn store(&self, key: u64, depth: i8, flag: u8, mut score: i32, best_move: Move, ply: u8) {
+        let mut entry = self.entries[self.index(key)].lock().unwrap();
+        if let Some(existing) = entry.as_ref() {
+            if existing.key == key && existing.depth > depth {
+                return;
+            }
+        }
+
+        if score < -MATE_SCORE {
+            score -= ply as i32;
+        } else if score > MATE_SCORE {
+            score += ply as i32;
+        }
+
+        *entry = Some(TTEntry { key, depth, flag, score, best_move });
+    }
+}
@@ -0,0 +1,145 @@
+// src/tune.rs
+use std::borrow::Cow;
+use std::fs;
+use std::sync::Arc;
+use clap::Args;
+use crate::board::{Board, Piece};
+use crate::cli::GlobalOptions;
+use crate::evaluation::{EvalParams, PawnHash, DEFAULT_EVAL};
+use crate::notation::parse_fen;
+use crate::pieces::PieceSet;
+
+// Weights tune adjusts: the piece values but the king's, then the king safety terms
+pub const TUNED_WEIGHTS: usize = 7;
+
+// A position and how the game it came from ended, from white's point of view: 1 for a win,
+// 0.5 for a draw and 0 for a loss
+pub struct Sample {
+    pub board: Board,
+    pub result: f64,
+}
+
+// `<fen> | <result>` lines as written by `selfplay --positions`
+pub fn parse_samples(text: &str) -> Result<Vec<Sample>, String> {
+    let mut samples = Vec::new();
+    for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')) {
+        let invalid = || format!("line {}: expected '<fen> | 1-0|0-1|1/2-1/2'", number + 1);
+        let (fen, result) = line.rsplit_once('|').ok_or_else(invalid)?;
+        let result = match result.trim() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            "1/2-1/2" => 0.5,
+            _ => return Err(invalid()),
+        };
+        let board = parse_fen(fen.trim()).map_err(|err| format!("line {}: {}", number + 1, err))?;
+        samples.push(Sample { board, result });
+    }
+    Ok(samples)
+}
+
+// White's expected score for an evaluation: the logistic curve Texel tuning fits, 400cp ahead
+// winning ten times as often as losing
+pub fn expected_score(score: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-score as f64 / 400.0))
+}
+
+// Mean squared difference between the results and what the static evaluation expects
+pub fn error(samples: &mut [Sample], params: &EvalParams) -> f64 {
+    let params = Arc::new(params.clone());
+    let mut pawns = PawnHash::default();
+    let total: f64 = samples.iter_mut()
+        .map(|sample| {
+            sample.board.rules.eval = params.clone();
+            (sample.result - expected_score(sample.board.evaluate_white_relative_cached(&mut pawns))).powi(2)
+        })
+        .sum();
+    total / samples.len().max(1) as f64
+}
+
+fn weight(params: &EvalParams, set: &PieceSet, index: usize) -> i32 {
+    match index {
+        0..=4 => params.value(Piece::from_kind(index, true), set),
+        5 => params.king_zone_attack,
+        _ => params.safe_check,
+    }
+}
+
+fn set_weight(params: &mut EvalParams, set: &PieceSet, index: usize, value: i32) {
+    match index {
+        0..=4 => {
+            let values = params.piece_values.get_or_insert_with(|| std::array::from_fn(|kind| set.defs[kind].value));
+            values[index] = value.clamp(0, i16::MAX as i32) as i16;
+        }
+        5 => params.king_zone_attack = value,
+        _ => params.safe_check = value,
+    }
+}
+
+// Coordinate descent from `start`: each weight moves by the step for as long as that lowers the
+// error, and the step halves once a round moves none of them. Piece values start from `set`
+// where `start` has none. Returns the weights and their error.
+pub fn tune(samples: &mut [Sample], start: &EvalParams, set: &PieceSet, step: i32, rounds: usize) -> (EvalParams, f64) {
+    let mut params = start.clone();
+    let mut best = error(samples, &params);
+    let mut step = step.max(1);
+    for _ in 0..rounds {
+        let mut improved = false;
+        for index in 0..TUNED_WEIGHTS {
+            for delta in [step, -step] {
+                let mut candidate = params.clone();
+                set_weight(&mut candidate, set, index, weight(&params, set, index) + delta);
+                let candidate_error = error(samples, &candidate);
+                if candidate_error < best {
+                    (params, best, improved) = (candidate, candidate_error, true);
+                    break;
+                }
+            }
+        }
+        if !improved {
+            if step == 1 {
+                break;
+            }
+            step /= 2;
+        }
+    }
+    (params, best)
+}
+
+#[derive(Args, Debug)]
+pub struct TuneArgs {
+    #[arg(value_name = "POSITIONS FILE", help = "written by selfplay --positions")]
+    pub positions: String,
+    #[arg(long, default_value_t = 50, help = "passes over all the weights at most")]
+    pub rounds: usize,
+    #[arg(long, value_name = "CP", default_value_t = 16, help = "first change tried on each weight, halved when none helps")]
+    pub step: i32,
+    #[arg(long, value_name = "FILE", help = "write the weights here, in the --params format, instead of printing them")]
+    pub out: Option<String>,
+}
+
+// Starts from --params when given, in the values of --pieces
+pub fn run(args: &TuneArgs, options: &GlobalOptions) -> Result<(), String> {
+    let (path, rounds, step, output_path) = (&args.positions, args.rounds, args.step, &args.out);
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut samples = parse_samples(&text).map_err(|err| format!("{}: {}", path, err))?;
+    if samples.is_empty() {
+        return Err(format!("{}: no positions to tune on", path));
+    }
+
+    let set = options.pieces.clone().unwrap_or_else(|| Arc::new(PieceSet::classic()));
+    for sample in samples.iter_mut() {
+        sample.board.rules.pieces = set.clone();
+    }
+    let start = options.params.as_deref().unwrap_or(&DEFAULT_EVAL);
+    let before = error(&mut samples, start);
+    let (mut params, after) = tune(&mut samples, start, &set, step, rounds);
+    params.name = Cow::Borrowed("tuned");
+    println!("{} positions, error {:.6} -> {:.6}", samples.len(), before, after);
+    match output_path {
+        Some(output_path) => fs::write(output_path, params.format()).map_err(|err| format!("{}: {}", output_path, err)),
+        None => {
+            print!("{}", params.format());
+            Ok(())
+        }
+    }
+}
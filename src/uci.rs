@@ -0,0 +1,450 @@
+// src/uci.rs
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use num_bigint::BigInt;
+use crate::board::Board;
+use crate::notation::{parse_fen, parse_icn, parse_move};
+use crate::output;
+use crate::rules::RuleSet;
+use crate::search::Searcher;
+use crate::tt::{entries_for_mb, mb_for_entries, TranspositionTable, DEFAULT_TT_ENTRIES, MAX_HASH_MB};
+use crate::validate;
+use crate::capabilities;
+use crate::variants::find_variant;
+
+// Depth used for a plain `go` without any limits
+pub const DEFAULT_DEPTH: u8 = 6;
+pub const MAX_THREADS: usize = 256;
+// Highest NPSLimit the option declares, larger values are taken as this
+pub const MAX_NPS: u64 = 100_000_000;
+
+// `Threads auto` and the default: one search thread per available CPU
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get()).min(MAX_THREADS)
+}
+
+// Options set with setoption. A search runs on the snapshot taken at its `go`, so options
+// arriving while it runs are stored here and only reach the next search.
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub threads: usize,
+    pub coordinate_limit: Option<BigInt>, // applied to every position set up, for opponents with finite boards
+    pub max_nps: Option<u64>, // NPSLimit, None for full speed
+    pub hash: usize, // MB, see tt::entries_for_mb
+}
+
+impl SearchConfig {
+    pub fn new() -> Self {
+        SearchConfig { threads: available_threads(), coordinate_limit: None, max_nps: None, hash: mb_for_entries(DEFAULT_TT_ENTRIES) }
+    }
+
+    pub fn apply(&self, searcher: &mut Searcher, board: &mut Board) {
+        searcher.threads = self.threads;
+        searcher.max_nps = self.max_nps;
+        // a new size means a new table, the same one keeps what it holds
+        if searcher.tt.entries.len() != entries_for_mb(self.hash) {
+            searcher.tt = TranspositionTable::with_mb(self.hash);
+        }
+        board.rules.coordinate_limit = self.coordinate_limit.clone();
+    }
+}
+
+// Parameters of `go`, those that stand alone and those followed by a number
+const GO_FLAGS: [&str; 2] = ["infinite", "ponder"];
+const GO_VALUES: [&str; 8] = ["depth", "nodes", "movetime", "wtime", "btime", "winc", "binc", "movestogo"];
+
+// The whole `go` line is checked before a search starts, rather than ignoring what's not understood
+fn check_go(tokens: &[&str]) -> Result<(), String> {
+    let mut tokens = tokens.iter();
+    while let Some(token) = tokens.next() {
+        if GO_FLAGS.contains(token) {
+            continue;
+        }
+        if !GO_VALUES.contains(token) {
+            return Err(format!("unknown go parameter '{}'", token));
+        }
+        let value = tokens.next().ok_or_else(|| format!("go {} needs a number", token))?;
+        let valid = if *token == "nodes" { value.parse::<u64>().is_ok() } else { value.parse::<i32>().is_ok() };
+        if !valid {
+            return Err(format!("invalid number '{}' for go {}", value, token));
+        }
+    }
+    Ok(())
+}
+
+pub struct Uci {
+    pub board: Board,
+    pub searcher: Option<Searcher>, // None while a search thread owns it
+    pub search_thread: Option<JoinHandle<Searcher>>,
+    pub stop_flag: Arc<AtomicBool>, // stop flag of the searcher currently running
+    pub ponder_flag: Arc<AtomicBool>, // its ponder flag, cleared on ponderhit
+    pub options: SearchConfig, // as of the last setoption, snapshotted by every go
+}
+
+impl Uci {
+    pub fn new() -> Self {
+        let searcher = Searcher::new();
+        Uci {
+            board: Board::new(),
+            stop_flag: searcher.stop.clone(),
+            ponder_flag: searcher.ponder.clone(),
+            searcher: Some(searcher),
+            search_thread: None,
+            options: SearchConfig::new(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else { break };
+            output::log(&format!("> {}", line));
+            if !self.handle_command(line.trim()) {
+                break;
+            }
+        }
+        self.stop();
+    }
+
+    // Returns false once the engine should quit
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        if let Err(err) = validate::command(line) {
+            output::error(&err);
+            return true;
+        }
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                output::emit("id name Infinity-Chess-Bot\nid author FirePlank", "id", &[
+                    ("name", output::json_string("Infinity-Chess-Bot")),
+                    ("author", output::json_string("FirePlank")),
+                ]);
+                output::emit(&format!("option name Threads type spin default {} min 1 max {}", available_threads(), MAX_THREADS), "option", &[
+                    ("name", output::json_string("Threads")),
+                    ("type", output::json_string("spin")),
+                    ("default", available_threads().to_string()),
+                    ("min", "1".to_string()),
+                    ("max", MAX_THREADS.to_string()),
+                ]);
+                output::emit(&format!("option name Hash type spin default {} min 1 max {}", mb_for_entries(DEFAULT_TT_ENTRIES), MAX_HASH_MB), "option", &[
+                    ("name", output::json_string("Hash")),
+                    ("type", output::json_string("spin")),
+                    ("default", mb_for_entries(DEFAULT_TT_ENTRIES).to_string()),
+                    ("min", "1".to_string()),
+                    ("max", MAX_HASH_MB.to_string()),
+                ]);
+                output::emit("option name CoordinateLimit type string default none", "option", &[
+                    ("name", output::json_string("CoordinateLimit")),
+                    ("type", output::json_string("string")),
+                    ("default", output::json_string("none")),
+                ]);
+                output::emit(&format!("option name NPSLimit type spin default 0 min 0 max {}", MAX_NPS), "option", &[
+                    ("name", output::json_string("NPSLimit")),
+                    ("type", output::json_string("spin")),
+                    ("default", "0".to_string()),
+                    ("min", "0".to_string()),
+                    ("max", MAX_NPS.to_string()),
+                ]);
+                output::emit("option name Ponder type check default false", "option", &[
+                    ("name", output::json_string("Ponder")),
+                    ("type", output::json_string("check")),
+                    ("default", "false".to_string()),
+                ]);
+                output::emit("uciok", "uciok", &[]);
+            }
+            Some("isready") => output::emit("readyok", "readyok", &[]),
+            Some("ucinewgame") => {
+                self.stop();
+                self.board = Board::new();
+                self.board.rules.coordinate_limit = self.options.coordinate_limit.clone();
+                if let Some(searcher) = self.searcher.as_mut() {
+                    searcher.new_game();
+                }
+            }
+            Some("position") => {
+                self.stop();
+                if let Err(err) = self.parse_position(&tokens.collect::<Vec<_>>()) {
+                    output::error(&err);
+                }
+            }
+            Some("go") => {
+                self.stop();
+                if let Err(err) = self.go(&tokens.collect::<Vec<_>>()) {
+                    output::error(&err);
+                }
+            }
+            // a running search keeps its snapshot, so there's no need to stop it
+            Some("setoption") => {
+                if let Err(err) = self.set_option(&tokens.collect::<Vec<_>>()) {
+                    output::error(&err);
+                }
+            }
+            Some("stop") => self.stop(),
+            // the ponder search carries on, now against the clock of the go command that started it
+            Some("ponderhit") => self.ponder_flag.store(false, Ordering::Relaxed),
+            Some("quit") => return false,
+            Some("d") => self.board.show(true),
+            Some("capabilities") => capabilities::report(true),
+            Some(command) => output::error(&format!("unknown command '{}'", command)),
+            None => {}
+        }
+        true
+    }
+
+    // position <startpos | fen <extended fen> | icn <compressed ICN> | variant <name>> [moves <move>...]
+    fn parse_position(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let moves_index = tokens.iter().position(|token| *token == "moves");
+        let position = tokens.get(1..moves_index.unwrap_or(tokens.len())).unwrap_or_default().join(" ");
+        validate::position_text(&position)?;
+        let mut board = match tokens.first() {
+            Some(&"startpos") => Board::new(),
+            Some(&"fen") => parse_fen(&position)?,
+            Some(&"icn") => parse_icn(&position)?,
+            Some(&"variant") => find_variant(tokens.get(1).ok_or("expected 'position variant <name>'")?)?.board()?,
+            _ => return Err("expected 'position startpos|fen <fen>|icn <icn>|variant <name> [moves ...]'".to_string()),
+        };
+        board.rules.coordinate_limit = self.options.coordinate_limit.clone();
+        validate::board(&board)?;
+
+        if let Some(index) = moves_index {
+            validate::moves(&tokens[index + 1..])?;
+            for text in &tokens[index + 1..] {
+                let mv = parse_move(&board, text)?;
+                if !board.make(mv.clone()) {
+                    board.unmake(mv);
+                    return Err(format!("illegal move '{}'", text));
+                }
+            }
+        }
+
+        self.board = board;
+        Ok(())
+    }
+
+    // setoption name <name> value <value>
+    fn set_option(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let value_index = tokens.iter().position(|token| *token == "value");
+        if tokens.first() != Some(&"name") || value_index.is_none() {
+            return Err("expected 'setoption name <name> value <value>'".to_string());
+        }
+        let name = tokens[1..value_index.unwrap()].join(" ");
+        let value = tokens[value_index.unwrap() + 1..].join(" ");
+
+        match name.to_ascii_lowercase().as_str() {
+            "threads" if value == "auto" => self.options.threads = available_threads(),
+            "threads" => self.options.threads = value.parse::<usize>().ok().filter(|threads| (1..=MAX_THREADS).contains(threads))
+                .ok_or_else(|| format!("Threads must be 'auto' or between 1 and {}", MAX_THREADS))?,
+            "hash" => self.options.hash = value.parse::<usize>().ok().filter(|mb| (1..=MAX_HASH_MB).contains(mb))
+                .ok_or_else(|| format!("Hash must be between 1 and {} MB", MAX_HASH_MB))?,
+            // 0 searches at full speed, values over the declared max are clamped to it
+            "npslimit" => {
+                let nps: u64 = value.parse().map_err(|_| "NPSLimit must be a number of nodes a second")?;
+                self.options.max_nps = (nps > 0).then_some(nps.min(MAX_NPS));
+            }
+            // pondering only needs `go ponder` and `ponderhit`, the option just tells the GUI so
+            "ponder" => {}
+            "coordinatelimit" => {
+                validate::move_text(&value)?;
+                self.options.coordinate_limit = RuleSet::parse_coordinate_limit(&value)?;
+                self.board.rules.coordinate_limit = self.options.coordinate_limit.clone();
+            }
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+        if self.search_thread.as_ref().is_some_and(|handle| !handle.is_finished()) {
+            output::info_string(&format!("{} applies from the next search", name));
+        }
+        Ok(())
+    }
+
+    // go [infinite] [ponder] [depth N] [nodes N] [movetime MS] [wtime MS] [btime MS] [winc MS] [binc MS] [movestogo N]
+    fn go(&mut self, tokens: &[&str]) -> Result<(), String> {
+        check_go(tokens)?;
+        let mut searcher = self.searcher.take().unwrap_or_else(Searcher::new);
+        searcher.clear_heuristics(true);
+        let white = self.board.side_to_move;
+        let value = |name: &str| -> Option<i32> {
+            let index = tokens.iter().position(|token| *token == name)?;
+            tokens.get(index + 1)?.parse().ok()
+        };
+
+        searcher.infinite = tokens.contains(&"infinite");
+        searcher.pondering = tokens.contains(&"ponder");
+        searcher.movetime = value("movetime").unwrap_or(-1);
+        searcher.playtime = value(if white { "wtime" } else { "btime" }).unwrap_or(-1);
+        searcher.inc = value(if white { "winc" } else { "binc" }).unwrap_or(0);
+        searcher.movestogo = value("movestogo").unwrap_or(30);
+        searcher.max_nodes = tokens.iter().position(|token| *token == "nodes").and_then(|index| tokens.get(index + 1)?.parse().ok());
+
+        let timed = searcher.movetime != -1 || searcher.playtime != -1 || searcher.max_nodes.is_some();
+        let depth = match value("depth") {
+            Some(depth) => depth.clamp(1, u8::MAX as i32) as u8,
+            None if timed || searcher.infinite || searcher.pondering => u8::MAX,
+            None => DEFAULT_DEPTH,
+        };
+
+        searcher.stop.store(false, Ordering::Relaxed);
+        searcher.ponder.store(searcher.pondering, Ordering::Relaxed);
+        self.stop_flag = searcher.stop.clone();
+        self.ponder_flag = searcher.ponder.clone();
+        let mut board = self.board.clone_without_history();
+        self.options.apply(&mut searcher, &mut board);
+        self.search_thread = Some(thread::spawn(move || {
+            let result = searcher.search_position(&mut board, depth);
+            // a ponder search that ran out of depth still answers only after ponderhit or stop
+            while searcher.ponder.load(Ordering::Relaxed) && !searcher.stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            output::bestmove(&result.best_move.to_string());
+            if let Some(Err(err)) = searcher.experience.as_mut().map(|experience| experience.save()) {
+                output::error(&format!("failed to save experience: {}", err));
+            }
+            searcher
+        }));
+        Ok(())
+    }
+
+    // Stop a running search and wait for it to report its best move
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.search_thread.take() {
+            self.stop_flag.store(true, Ordering::Relaxed);
+            self.searcher = handle.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{format_fen, format_move};
+    use crate::output::{info_line, Score};
+
+    fn uci() -> Uci {
+        let mut uci = Uci::new();
+        uci.searcher.as_mut().unwrap().print_info = false;
+        uci
+    }
+
+    fn tokens(line: &str) -> Vec<&str> {
+        line.split_whitespace().skip(1).collect()
+    }
+
+    // Each malformed line is refused with the error the engine reports, changes nothing, and the
+    // engine keeps answering
+    #[test]
+    fn malformed_position_lines_are_refused() {
+        let mut uci = uci();
+        let before = format_fen(&uci.board);
+        for (line, error) in [
+            ("position", "expected 'position startpos"),
+            ("position nowhere", "expected 'position startpos"),
+            ("position fen", "expected"),
+            ("position fen K1,1;k8,8 x - -", ""),
+            ("position fen K1,1;k8,8 w - - 10/7:999", "invalid pawn push"),
+            ("position startpos moves 5,2>5,9", "illegal move"),
+            ("position startpos moves e2e4 é7é5", "expected 'from>to'"),
+            ("position variant nosuch", "unknown variant"),
+        ] {
+            let err = uci.parse_position(&tokens(line)).expect_err(line);
+            assert!(err.contains(error), "{}: {}", line, err);
+            assert!(uci.handle_command(line), "{}", line);
+            assert_eq!(format_fen(&uci.board), before, "{}", line);
+        }
+        assert!(uci.handle_command("isready"));
+    }
+
+    #[test]
+    fn malformed_go_lines_start_no_search() {
+        let mut uci = uci();
+        for (line, error) in [
+            ("go depth", "go depth needs a number"),
+            ("go depth x", "invalid number 'x' for go depth"),
+            ("go nodes -1", "invalid number '-1' for go nodes"),
+            ("go movetime 1e3", "invalid number '1e3' for go movetime"),
+            ("go searchmoves 5,2>5,4", "unknown go parameter 'searchmoves'"),
+            ("go wtime 1000 btime", "go btime needs a number"),
+        ] {
+            assert_eq!(uci.go(&tokens(line)).expect_err(line), error);
+            assert!(uci.search_thread.is_none(), "{}", line);
+            assert!(uci.handle_command(line), "{}", line);
+            assert!(uci.search_thread.is_none(), "{}", line);
+        }
+        assert!(uci.go(&tokens("go depth 1 wtime -50 winc 0")).is_ok());
+        uci.stop();
+        assert!(uci.searcher.is_some());
+    }
+
+    #[test]
+    fn malformed_setoption_lines_change_nothing() {
+        let mut uci = uci();
+        for (line, error) in [
+            ("setoption", "expected 'setoption name <name> value <value>'"),
+            ("setoption name Threads", "expected 'setoption name <name> value <value>'"),
+            ("setoption Threads value 2", "expected 'setoption name <name> value <value>'"),
+            ("setoption name Threads value 0", "Threads must be"),
+            ("setoption name Threads value many", "Threads must be"),
+            ("setoption name Nonsense value 1", "unknown option 'Nonsense'"),
+            ("setoption name CoordinateLimit value x,y", ""),
+        ] {
+            let err = uci.set_option(&tokens(line)).expect_err(line);
+            assert!(err.contains(error), "{}: {}", line, err);
+            assert!(uci.handle_command(line), "{}", line);
+            assert_eq!(uci.options.threads, available_threads(), "{}", line);
+            assert_eq!(uci.options.coordinate_limit, None, "{}", line);
+        }
+        assert!(!uci.handle_command("quit"));
+    }
+
+    #[test]
+    fn nps_limit_is_clamped_to_its_declared_max() {
+        let mut uci = uci();
+        uci.set_option(&tokens("setoption name NPSLimit value 5000")).unwrap();
+        assert_eq!(uci.options.max_nps, Some(5000));
+        uci.set_option(&tokens("setoption name NPSLimit value 999999999999")).unwrap();
+        assert_eq!(uci.options.max_nps, Some(MAX_NPS));
+        uci.set_option(&tokens("setoption name NPSLimit value 0")).unwrap();
+        assert_eq!(uci.options.max_nps, None);
+        assert!(uci.set_option(&tokens("setoption name NPSLimit value -1")).is_err());
+    }
+
+    // Threads starts at the detected parallelism, which `Threads auto` goes back to
+    #[test]
+    fn threads_default_to_the_available_parallelism() {
+        let mut uci = uci();
+        assert_eq!(uci.options.threads, available_threads());
+        assert!((1..=MAX_THREADS).contains(&uci.options.threads));
+        uci.set_option(&tokens("setoption name Threads value 3")).unwrap();
+        assert_eq!(uci.options.threads, 3);
+        uci.set_option(&tokens("setoption name Threads value auto")).unwrap();
+        assert_eq!(uci.options.threads, available_threads());
+    }
+
+    // `info ... pv <move>...` with every field a number and every PV move one token that reads
+    // back as a move
+    #[test]
+    fn info_lines_follow_the_grammar() {
+        let mut board = Board::new();
+        let mut searcher = Searcher::new();
+        searcher.print_info = false;
+        let result = searcher.search_position(&mut board, 3);
+        let pv: Vec<String> = result.pv.iter().map(|mv| format_move(mv, None, false)).collect();
+        let line = info_line(&Score::Centipawns(result.score), result.depth, result.seldepth, result.nodes, 1000, 5, &pv);
+
+        let words: Vec<&str> = line.split(' ').collect();
+        assert_eq!(words[..2], ["info", "score"]);
+        assert!(matches!(words[2], "cp" | "mate"));
+        words[3].parse::<i32>().unwrap();
+        for (index, name) in ["depth", "seldepth", "nodes", "nps", "time"].iter().enumerate() {
+            assert_eq!(words[4 + index * 2], *name);
+            words[5 + index * 2].parse::<u64>().unwrap();
+        }
+        assert_eq!(words[14], "pv");
+        assert_eq!(words.len(), 15 + pv.len());
+        for mv in &words[15..] {
+            let played = parse_move(&board, mv).unwrap();
+            assert!(board.make(played));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// src/validate.rs
+use std::collections::HashSet;
+use crate::board::{Board, Coordinate, Piece};
+
+// Bounds for positions and moves that arrive from outside, the UCI channel or whatever bridge
+// a server talks through. Anything past them is refused with an error before it reaches Board,
+// so a hostile or broken peer cannot make the engine panic or parse numbers for minutes.
+pub const MAX_COMMAND_LENGTH: usize = 1 << 20;
+pub const MAX_NUMBER_TEXT: usize = 64; // characters in one coordinate number, shorthand included
+pub const MAX_COORDINATE_BITS: u64 = 1 << 16;
+pub const MAX_PIECES: usize = 4096;
+pub const MAX_MOVES: usize = 10_000; // moves replayed by a single `position` command
+
+pub fn command(line: &str) -> Result<(), String> {
+    if line.len() > MAX_COMMAND_LENGTH {
+        return Err(format!("command of {} bytes is longer than the {} allowed", line.len(), MAX_COMMAND_LENGTH));
+    }
+    Ok(())
+}
+
+// Numbers are the runs between the separators of FEN, ICN and move text
+fn numbers(text: &str) -> Result<(), String> {
+    match text.split([';', '|', ',', '>', ' ', '(', ')']).find(|part| part.len() > MAX_NUMBER_TEXT) {
+        Some(part) => Err(format!("'{}...' is longer than the {} characters allowed for a number", part.chars().take(16).collect::<String>(), MAX_NUMBER_TEXT)),
+        None => Ok(()),
+    }
+}
+
+// A FEN or ICN position before it is parsed
+pub fn position_text(text: &str) -> Result<(), String> {
+    numbers(text)?;
+    let entries = text.split([';', '|']).count();
+    if entries > MAX_PIECES * 2 {
+        return Err(format!("position has {} entries, at most {} pieces are allowed", entries, MAX_PIECES));
+    }
+    Ok(())
+}
+
+pub fn move_text(text: &str) -> Result<(), String> {
+    numbers(text)
+}
+
+pub fn moves(texts: &[&str]) -> Result<(), String> {
+    if texts.len() > MAX_MOVES {
+        return Err(format!("{} moves given, at most {} are replayed", texts.len(), MAX_MOVES));
+    }
+    texts.iter().try_for_each(|text| move_text(text))
+}
+
+fn coordinate(coord: &Coordinate) -> Result<(), String> {
+    if coord.0.bits() > MAX_COORDINATE_BITS || coord.1.bits() > MAX_COORDINATE_BITS {
+        return Err(format!("coordinate with more than {} bits", MAX_COORDINATE_BITS));
+    }
+    Ok(())
+}
+
+// A parsed position the engine can search without tripping over it: bounded size and pawn
+// pushes, at most one king a side (a side without one can't be checked, see Board::in_check), and castling and en passant squares that make sense
+pub fn board(board: &Board) -> Result<(), String> {
+    if board.state.len() > MAX_PIECES {
+        return Err(format!("position has {} pieces, at most {} are allowed", board.state.len(), MAX_PIECES));
+    }
+    board.state.keys().try_for_each(coordinate)?;
+    board.en_passant.iter().try_for_each(coordinate)?;
+    board.rules.check_pawn_push()?;
+
+    for (king, side) in [(Piece::WhiteKing, "white"), (Piece::BlackKing, "black")] {
+        let count = board.state.values().filter(|piece| **piece == king).count();
+        if count > 1 {
+            return Err(format!("{} has {} kings, expected at most one", side, count));
+        }
+    }
+
+    let mut rooks = HashSet::new();
+    for right in &board.castling_rights {
+        let rook = if right.white { Piece::WhiteRook } else { Piece::BlackRook };
+        if board.get_piece(&right.rook) != Some(&rook) || !rooks.insert(&right.rook) {
+            return Err(format!("castling right for {} without a rook of that colour there", right.rook));
+        }
+    }
+    if board.en_passant.iter().any(|square| board.get_piece(square).is_some()) {
+        return Err("en passant square is occupied".to_string());
+    }
+    Ok(())
+}
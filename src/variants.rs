@@ -0,0 +1,100 @@
+// src/variants.rs
+use std::borrow::Cow;
+use std::sync::Arc;
+use crate::board::{Board, CastlingRight, Piece, PIECE_VALUES};
+use crate::evaluation::{EvalParams, DEFAULT_EVAL, KING_ZONE_ATTACK_PENALTY, SAFE_CHECK_PENALTY};
+use crate::notation::parse_fen;
+
+// A named start position. Each side brings its own army, written as FEN piece placements
+// (`K5,1;N2,1;...`, lowercase letters for black), so the armies need not mirror each other.
+pub struct Variant {
+    pub name: &'static str,
+    pub white: &'static str,
+    pub black: &'static str,
+    pub eval: &'static EvalParams, // selected whenever the variant is set up
+}
+
+// The pawn row is the whole of black's army and its passers are the game, the knights have no
+// queen to fear
+const PEASANTS_EVAL: EvalParams = EvalParams {
+    name: Cow::Borrowed("peasants"),
+    piece_values: Some([160, 700, 300, 400, 1200, 0]),
+    king_zone_attack: 0,
+    safe_check: SAFE_CHECK_PENALTY / 2,
+};
+
+// Four knights hunt a king up close and four bishops rake it from afar, so king safety weighs
+// more, and the knights, each worth a little less than a bishop, carry more of the attack
+const KNIGHTS_BISHOPS_EVAL: EvalParams = EvalParams {
+    name: Cow::Borrowed("knights-bishops"),
+    piece_values: Some([PIECE_VALUES[0], PIECE_VALUES[1], 330, 400, PIECE_VALUES[4], 0]),
+    king_zone_attack: KING_ZONE_ATTACK_PENALTY * 3 / 2,
+    safe_check: SAFE_CHECK_PENALTY * 3 / 2,
+};
+
+pub const VARIANTS: [Variant; 3] = [
+    Variant {
+        name: "classical",
+        white: "R1,1;N2,1;B3,1;Q4,1;K5,1;B6,1;N7,1;R8,1;P1,2;P2,2;P3,2;P4,2;P5,2;P6,2;P7,2;P8,2",
+        black: "r1,8;n2,8;b3,8;q4,8;k5,8;b6,8;n7,8;r8,8;p1,7;p2,7;p3,7;p4,7;p5,7;p6,7;p7,7;p8,7",
+        eval: &DEFAULT_EVAL,
+    },
+    // four knights and a pawn against a row of pawns
+    Variant {
+        name: "peasants",
+        white: "N2,1;N3,1;K5,1;N6,1;N7,1;P5,2",
+        black: "k5,8;p1,7;p2,7;p3,7;p4,7;p5,7;p6,7;p7,7;p8,7",
+        eval: &PEASANTS_EVAL,
+    },
+    // white's bishops become knights, black's knights become bishops
+    Variant {
+        name: "knights-bishops",
+        white: "R1,1;N2,1;N3,1;Q4,1;K5,1;N6,1;N7,1;R8,1;P1,2;P2,2;P3,2;P4,2;P5,2;P6,2;P7,2;P8,2",
+        black: "r1,8;b2,8;b3,8;q4,8;k5,8;b6,8;b7,8;r8,8;p1,7;p2,7;p3,7;p4,7;p5,7;p6,7;p7,7;p8,7",
+        eval: &KNIGHTS_BISHOPS_EVAL,
+    },
+];
+
+pub fn find_variant(name: &str) -> Result<&'static Variant, String> {
+    VARIANTS.iter().find(|variant| variant.name == name).ok_or_else(|| {
+        let names: Vec<&str> = VARIANTS.iter().map(|variant| variant.name).collect();
+        format!("unknown variant '{}', expected one of {}", name, names.join(", "))
+    })
+}
+
+impl Variant {
+    // The start position with white to move. Pawns may double-step from the ranks they start
+    // on, rooks on their king's rank may castle, and whatever material one army has over the
+    // other becomes the baseline the evaluation counts from, in the values of its profile.
+    pub fn board(&self) -> Result<Board, String> {
+        let mut board = parse_fen(&format!("{};{} w - -", self.white, self.black))?;
+        board.rules.eval = Arc::new(self.eval.clone());
+        board.rules.white_pawn_ranks.clear();
+        board.rules.black_pawn_ranks.clear();
+
+        let mut baseline = 0;
+        for (coord, piece) in &board.state {
+            baseline += if piece.is_white() { board.rules.value(*piece) } else { -board.rules.value(*piece) };
+            match piece {
+                Piece::WhitePawn | Piece::BlackPawn => {
+                    let ranks = if piece.is_white() { &mut board.rules.white_pawn_ranks } else { &mut board.rules.black_pawn_ranks };
+                    let rank = i64::try_from(&coord.1).map_err(|_| format!("pawn rank {} out of range", coord.1))?;
+                    if !ranks.contains(&rank) {
+                        ranks.push(rank);
+                    }
+                }
+                Piece::WhiteRook | Piece::BlackRook => {
+                    let king = Piece::from_kind(Piece::WhiteKing.kind(), piece.is_white());
+                    if board.state.iter().any(|(square, other)| *other == king && square.1 == coord.1) {
+                        board.castling_rights.push(CastlingRight { rook: coord.clone(), white: piece.is_white() });
+                    }
+                }
+                _ => {}
+            }
+        }
+        board.rules.white_pawn_ranks.sort();
+        board.rules.black_pawn_ranks.sort();
+        board.rules.material_baseline = baseline;
+        Ok(board)
+    }
+}